@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+
+use workspace_unused_pub::{ItemKind, Options, UnusedItem};
+
+/// A possibly unused item.
+#[pyclass(frozen, get_all)]
+struct Finding {
+    /// Path relative to the workspace root.
+    path: String,
+    /// Zero-based line of the name in the definition.
+    line: usize,
+    /// Zero-based column of the name in the definition.
+    column: usize,
+    /// `function` or `method`.
+    kind: String,
+    symbol: String,
+    display_name: String,
+    fingerprint: String,
+}
+
+#[pymethods]
+impl Finding {
+    fn __repr__(&self) -> String {
+        format!(
+            "Finding({}:{} {} {})",
+            self.path,
+            self.line + 1,
+            self.display_name,
+            self.fingerprint
+        )
+    }
+}
+
+impl From<UnusedItem> for Finding {
+    fn from(item: UnusedItem) -> Self {
+        Self {
+            path: item.path,
+            line: item.span.start_line,
+            column: item.span.start_column,
+            kind: item.kind.to_string(),
+            symbol: item.symbol,
+            display_name: item.display_name,
+            fingerprint: item.fingerprint,
+        }
+    }
+}
+
+fn parse_kind(kind: &str) -> PyResult<ItemKind> {
+    match kind {
+        "functions" | "function" => Ok(ItemKind::Function),
+        "methods" | "method" => Ok(ItemKind::Method),
+        _ => Err(PyValueError::new_err(format!("Unknown kind {:?}", kind))),
+    }
+}
+
+/// Run the analysis on a workspace like `cargo workspace-unused-pub check`, returning the
+/// findings.
+///
+/// The index is generated with rust-analyzer if no SCIP file is given and none exists.
+#[pyfunction]
+#[pyo3(signature = (path, scip=None, packages=None, kinds=None, no_cache=false))]
+fn analyze(
+    py: Python<'_>,
+    path: PathBuf,
+    scip: Option<Vec<PathBuf>>,
+    packages: Option<Vec<String>>,
+    kinds: Option<Vec<String>>,
+    no_cache: bool,
+) -> PyResult<Vec<Finding>> {
+    let mut options = Options::new(path);
+    options.scip = scip.unwrap_or_default();
+    options.packages = packages.unwrap_or_default();
+    if let Some(kinds) = kinds {
+        options.kinds = kinds
+            .iter()
+            .map(|k| parse_kind(k))
+            .collect::<PyResult<_>>()?;
+    }
+    options.no_cache = no_cache;
+    // The analysis does not touch Python objects
+    let report = py
+        .detach(|| workspace_unused_pub::analyze(options))
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    Ok(report.findings.into_iter().map(Finding::from).collect())
+}
+
+/// Detection of unused pub items in a Cargo workspace.
+#[pymodule]
+#[pyo3(name = "workspace_unused_pub")]
+fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Finding>()?;
+    m.add_function(wrap_pyfunction!(analyze, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,346 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use itertools::Itertools;
+use scip::types::{descriptor::Suffix, symbol_information::Kind, Descriptor};
+use serde::Serialize;
+
+use crate::index::{Index, Occurrence, Sym, Symbol};
+use crate::passes::Hooks;
+use crate::symbols::{visibility, Sources};
+use crate::Location;
+
+/// Functions and methods of the given crates (with `-` replaced by `_`) that are reachable from
+/// the root of their crate, i.e. that other crates than the ones of the index may use.
+///
+/// An item is reachable if it is `pub` in a reachable module or type, or if it is re-exported
+/// with a `pub use` item on a single line, possibly as a `pub` item of a module re-exported with
+/// a glob, e.g. `pub use imp::*`.
+pub fn reachable(index: &Index, workspace: &Path, crates: &HashSet<String>) -> HashSet<Sym> {
+    let mut api = Api::new(index, workspace);
+    index
+        .symbols
+        .values()
+        .filter(|s| s.kind == Kind::Method || s.kind == Kind::Function)
+        .filter(|s| crate::symbol_package(index.str(s.symbol)).is_some_and(|p| crates.contains(&p)))
+        .map(|s| s.symbol)
+        .filter(|s| api.is_reachable(index.str(*s)))
+        .collect()
+}
+
+struct Api<'a> {
+    index: &'a Index,
+    occurrences: HashMap<Sym, Vec<&'a Occurrence>>,
+    sources: Sources<'a>,
+    /// Memoized results, by symbol.
+    reachable: HashMap<String, bool>,
+}
+
+impl<'a> Api<'a> {
+    fn new(index: &'a Index, workspace: &'a Path) -> Self {
+        let mut occurrences: HashMap<Sym, Vec<&Occurrence>> = HashMap::new();
+        for o in &index.occurrences {
+            occurrences.entry(o.symbol).or_default().push(o);
+        }
+        Self {
+            index,
+            occurrences,
+            sources: Sources::new(workspace),
+            reachable: HashMap::new(),
+        }
+    }
+
+    fn is_reachable(&mut self, symbol: &str) -> bool {
+        if let Some(r) = self.reachable.get(symbol) {
+            return *r;
+        }
+        // Breaks cycles, e.g. between re-exporting modules
+        self.reachable.insert(symbol.to_string(), false);
+        let r = match parent(symbol) {
+            None => true,
+            Some(parent) => {
+                (self.is_pub(symbol, &parent)
+                    && (self.is_reachable(&parent) || self.is_glob_reexported(&parent)))
+                    || self.is_reexported(symbol)
+            }
+        };
+        self.reachable.insert(symbol.to_string(), r);
+        r
+    }
+
+    fn occurrences(&self, symbol: &str) -> Vec<&'a Occurrence> {
+        self.index
+            .strings
+            .get(symbol)
+            .and_then(|s| self.occurrences.get(&s))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Whether the item is declared `pub`. Trait items have the visibility of the trait.
+    fn is_pub(&mut self, symbol: &str, parent: &str) -> bool {
+        let index = self.index;
+        let parent_kind = index
+            .strings
+            .get(parent)
+            .and_then(|p| index.symbols.get(&p))
+            .map(|p| p.kind);
+        if parent_kind == Some(Kind::Trait) {
+            return true;
+        }
+        let occurrences = self.occurrences(symbol);
+        let name = scip::symbol::parse_symbol(symbol)
+            .ok()
+            .and_then(|s| s.descriptors.last().map(|d| d.name.clone()))
+            .unwrap_or_default();
+        occurrences.iter().any(|o| {
+            let span = o.span();
+            let Some(line) = self
+                .sources
+                .line(&index.documents[o.document], span.start_line)
+            else {
+                return false;
+            };
+            // Modules in their own file are declared by the `mod` item referencing them
+            let declares = o.is_definition() || line.contains(&format!("mod {}", name));
+            declares && visibility(line, span.start_column) == "pub"
+        })
+    }
+
+    /// Whether the item is re-exported with `pub use`, rather than being a prefix of the path of
+    /// a re-exported item.
+    fn is_reexported(&mut self, symbol: &str) -> bool {
+        let occurrences = self.occurrences(symbol);
+        occurrences.iter().any(|o| self.is_reexport(o))
+    }
+
+    /// Whether the module is re-exported with a `pub use module::*` glob, making its `pub` items
+    /// available at the path of the re-exporting module.
+    fn is_glob_reexported(&mut self, module: &str) -> bool {
+        let occurrences = self.occurrences(module);
+        occurrences.iter().any(|o| self.is_glob(o))
+    }
+
+    /// Whether an occurrence is the item re-exported by a `pub use` on a single line.
+    fn is_reexport(&mut self, o: &Occurrence) -> bool {
+        let span = o.span();
+        !o.is_definition()
+            && self
+                .sources
+                .line(&self.index.documents[o.document], span.start_line)
+                .is_some_and(|l| {
+                    l.trim_start().starts_with("pub use")
+                        && !l
+                            .get(span.end_column..)
+                            .is_some_and(|rest| rest.trim_start().starts_with("::"))
+                })
+    }
+
+    /// Whether an occurrence is the module of a `pub use module::*` glob re-export.
+    fn is_glob(&mut self, o: &Occurrence) -> bool {
+        let span = o.span();
+        !o.is_definition()
+            && self
+                .sources
+                .line(&self.index.documents[o.document], span.start_line)
+                .is_some_and(|l| {
+                    l.trim_start().starts_with("pub use")
+                        && l.get(span.end_column..).is_some_and(|rest| {
+                            rest.trim_start()
+                                .strip_prefix("::")
+                                .is_some_and(|r| r.trim_start().starts_with('*'))
+                        })
+                })
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReexportKind {
+    /// Re-exported by several `pub use` items.
+    Duplicate,
+    /// Re-exported by a glob, but shadowed by an explicit item or re-export of the same name.
+    Shadowed,
+    /// Module re-exported by a glob, none of whose `pub` items is referenced.
+    Unused,
+}
+
+/// An item re-exported under several public paths, or whose glob re-export is shadowed.
+#[derive(Serialize, Debug)]
+pub struct Reexport {
+    pub kind: ReexportKind,
+    pub symbol: String,
+    /// The `pub use` items for duplicates, the glob re-export followed by the shadowing item for
+    /// shadowed items, and the glob re-export for unused modules.
+    pub locations: Vec<Location>,
+}
+
+/// Items of the workspace re-exported with `pub use` on several lines, items of a module
+/// re-exported with a glob in a file also declaring or re-exporting another item with the same
+/// name, which takes precedence, and modules re-exported with a glob whose `pub` items are never
+/// referenced. As for [`reachable`], only the `pub use` items on a single line are considered,
+/// and the explicit items are looked up in the whole file containing the glob.
+pub fn reexports(index: &Index, workspace: &Path) -> Vec<Reexport> {
+    let mut api = Api::new(index, workspace);
+    let location = |o: &Occurrence| Location {
+        path: index.documents[o.document].clone(),
+        span: o.span(),
+    };
+    let name = |s: Sym| {
+        index.symbols.get(&s).map_or_else(
+            || {
+                scip::symbol::parse_symbol(index.str(s))
+                    .ok()
+                    .and_then(|s| s.descriptors.last().map(|d| d.name.clone()))
+                    .unwrap_or_default()
+            },
+            |s| index.str(s.display_name).to_string(),
+        )
+    };
+    let mut referenced: HashSet<Sym> = HashSet::new();
+    let mut reexports: HashMap<Sym, Vec<&Occurrence>> = HashMap::new();
+    let mut globs = vec![];
+    for o in &index.occurrences {
+        if !o.is_definition() {
+            referenced.insert(o.symbol);
+        }
+        if api.is_glob(o) {
+            globs.push(o);
+        } else if api.is_reexport(o) {
+            reexports.entry(o.symbol).or_default().push(o);
+        }
+    }
+
+    let mut found = vec![];
+    for (symbol, occurrences) in &reexports {
+        let occurrences = occurrences
+            .iter()
+            .unique_by(|o| (o.document, o.span().start_line))
+            .collect_vec();
+        if occurrences.len() > 1 {
+            found.push(Reexport {
+                kind: ReexportKind::Duplicate,
+                symbol: index.str(*symbol).into(),
+                locations: occurrences.iter().map(|o| location(o)).collect(),
+            });
+        }
+    }
+
+    for glob in globs {
+        let module = index.str(glob.symbol);
+        // Items declared at the top of a module, or re-exported, in the file of the glob
+        let explicit: HashMap<String, &Occurrence> = index
+            .occurrences
+            .iter()
+            .filter(|o| o.document == glob.document && o.symbol != glob.symbol)
+            .filter(|o| {
+                let module_level = o.is_definition()
+                    && parent(index.str(o.symbol))
+                        .and_then(|p| scip::symbol::parse_symbol(&p).ok())
+                        .and_then(|p| p.descriptors.last().map(|d| d.suffix.enum_value()))
+                        == Some(Ok(Suffix::Namespace));
+                module_level
+                    || reexports
+                        .get(&o.symbol)
+                        .is_some_and(|r| r.iter().any(|r| std::ptr::eq(*r, *o)))
+            })
+            .map(|o| (name(o.symbol), o))
+            .collect();
+        let mut items = 0;
+        let mut used = false;
+        for s in index.symbols.values() {
+            let symbol = index.str(s.symbol);
+            if parent(symbol).as_deref() != Some(module) || !api.is_pub(symbol, module) {
+                continue;
+            }
+            items += 1;
+            used |= referenced.contains(&s.symbol);
+            let Some(shadowing) = explicit
+                .get(index.str(s.display_name))
+                .filter(|o| o.symbol != s.symbol)
+            else {
+                continue;
+            };
+            found.push(Reexport {
+                kind: ReexportKind::Shadowed,
+                symbol: symbol.into(),
+                locations: vec![location(glob), location(shadowing)],
+            });
+        }
+        if items > 0 && !used {
+            found.push(Reexport {
+                kind: ReexportKind::Unused,
+                symbol: module.into(),
+                locations: vec![location(glob)],
+            });
+        }
+    }
+    found.sort_by(|a, b| {
+        let key = |r: &Reexport| (r.locations[0].path.clone(), r.locations[0].span.start_line);
+        key(a).cmp(&key(b)).then_with(|| a.symbol.cmp(&b.symbol))
+    });
+    found
+}
+
+/// Symbol of the module, type or trait declaring an item, or `None` for the root module.
+///
+/// Methods of implementations are declared by the implemented type, assumed to be in the same
+/// module.
+pub(crate) fn parent(symbol: &str) -> Option<String> {
+    let mut symbol = scip::symbol::parse_symbol(symbol).ok()?;
+    let mut descriptors = std::mem::take(&mut symbol.descriptors);
+    // Trailing parameters and the root module are not declarations
+    while descriptors.last().is_some_and(|d| {
+        matches!(
+            d.suffix.enum_value(),
+            Ok(Suffix::Parameter | Suffix::TypeParameter | Suffix::Meta)
+        )
+    }) {
+        descriptors.pop();
+    }
+    if descriptors
+        .first()
+        .is_some_and(|d| d.suffix.enum_value() == Ok(Suffix::Namespace) && d.name == "crate")
+    {
+        descriptors.remove(0);
+    }
+    descriptors.pop()?;
+    if descriptors.is_empty() {
+        descriptors.push(Descriptor {
+            name: "crate".into(),
+            suffix: Suffix::Namespace.into(),
+            ..Default::default()
+        });
+    }
+    if let Some(i) = descriptors
+        .iter()
+        .position(|d| d.suffix.enum_value() == Ok(Suffix::Type) && d.name == "impl")
+    {
+        let ty = descriptors.get(i + 1)?.name.clone();
+        descriptors.truncate(i);
+        descriptors.push(Descriptor {
+            name: ty,
+            suffix: Suffix::Type.into(),
+            ..Default::default()
+        });
+    }
+    symbol.descriptors = descriptors;
+    Some(scip::symbol::format_symbol(symbol))
+}
+
+/// Name of the pass removing the candidates exported according to rustdoc, with
+/// `--respect-public-api`.
+pub const RUSTDOC_PASS: &str = "rustdoc API";
+
+/// Records the candidates removed as part of the public API documented by rustdoc, which are
+/// unused within the workspace.
+#[derive(Default)]
+pub struct ExternallyVisible(pub HashSet<Sym>);
+
+impl Hooks for ExternallyVisible {
+    fn on_filtered(&mut self, _index: &Index, pass: &str, candidate: &Symbol) {
+        if pass == RUSTDOC_PASS {
+            self.0.insert(candidate.symbol);
+        }
+    }
+}
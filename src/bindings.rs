@@ -0,0 +1,529 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use log::*;
+use scip::types::symbol_information::Kind;
+
+use crate::config;
+use crate::index::{Index, Sym};
+use crate::passes::Candidates;
+use crate::symbols::Sources;
+
+/// A candidate, as seen by the bindings.
+struct Item<'a> {
+    /// Rust name.
+    name: &'a str,
+    attributes: &'a [String],
+    /// Attributes of the `impl` block of a method.
+    block: &'a [String],
+    /// Type implementing a method.
+    ty: Option<&'a str>,
+}
+
+/// Names of an item in a language, if it is exported to it.
+type Names = fn(&Item) -> Option<Vec<String>>;
+
+/// A language calling into the workspace through generated bindings.
+pub struct Language {
+    /// Name of the pass, e.g. `javascript`.
+    pub pass: &'static str,
+    /// Name of the language in the reports.
+    pub label: &'static str,
+    /// Extensions of its source files.
+    extensions: &'static [&'static str],
+    /// Directories not searched, e.g. of dependencies.
+    skipped: &'static [&'static str],
+    names: Names,
+    /// Directories of its sources in the configuration.
+    dirs: fn(&config::Bindings) -> &[PathBuf],
+    /// Generated declarations of all the exports in the configuration, which are checked against
+    /// them rather than counted as usages.
+    headers: fn(&config::Bindings) -> &[PathBuf],
+}
+
+/// The `#[wasm_bindgen]` exports, called from JavaScript or TypeScript.
+pub const JAVASCRIPT: Language = Language {
+    pass: "javascript",
+    label: "JavaScript",
+    extensions: &["js", "mjs", "cjs", "jsx", "ts", "mts", "cts", "tsx"],
+    skipped: &["node_modules"],
+    names: wasm_bindgen_names,
+    dirs: |bindings| &bindings.javascript,
+    headers: |_| &[],
+};
+
+/// The functions and methods exported by PyO3 modules, called from Python.
+pub const PYTHON: Language = Language {
+    pass: "python",
+    label: "Python",
+    extensions: &["py"],
+    skipped: &["__pycache__", ".venv", "venv", "site-packages"],
+    names: pyo3_names,
+    dirs: |bindings| &bindings.python,
+    headers: |_| &[],
+};
+
+/// The `#[no_mangle]` and `#[export_name]` functions, called from C or C++.
+pub const C: Language = Language {
+    pass: "c",
+    label: "C/C++",
+    extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hh"],
+    skipped: &[],
+    names: ffi_names,
+    dirs: |bindings| &bindings.c,
+    headers: |bindings| &bindings.c_headers,
+};
+
+/// The languages, in the order of their passes.
+pub const LANGUAGES: [&Language; 3] = [&JAVASCRIPT, &PYTHON, &C];
+
+/// Language of a pass, if it is one of theirs.
+pub fn language(pass: &str) -> Option<&'static Language> {
+    LANGUAGES.into_iter().find(|l| l.pass == pass)
+}
+
+impl Language {
+    /// Whether directories of sources are configured for the language.
+    pub fn is_configured(&self, bindings: &config::Bindings) -> bool {
+        !(self.dirs)(bindings).is_empty()
+    }
+}
+
+/// Name of an attribute without its arguments and path, e.g. `wasm_bindgen` for
+/// `wasm_bindgen::prelude::wasm_bindgen(js_name = f)]`.
+fn attribute_name(attribute: &str) -> &str {
+    let name = attribute.split(['(', ']', ' ']).next().unwrap_or_default();
+    name.rsplit("::").next().unwrap_or_default()
+}
+
+/// Value of an argument of an attribute, e.g. `f` for `js_name = f` or `js_name = "f"`.
+fn argument<'a>(attribute: &'a str, key: &str) -> Option<&'a str> {
+    let (_, rest) = attribute.split_once('(')?;
+    rest.split(',').find_map(|a| {
+        let (k, v) = a.split_once('=')?;
+        (k.trim() == key).then(|| v.trim().trim_end_matches([')', ']']).trim_matches('"'))
+    })
+}
+
+/// Rust name in camel case, e.g. `getValue` for `get_value`.
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_').filter(|p| !p.is_empty());
+    let mut camel = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        camel.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        camel.push_str(chars.as_str());
+    }
+    camel
+}
+
+/// Names of a `#[wasm_bindgen]` export: its `js_name` if set, and otherwise its Rust name or
+/// its camel-cased version, which the JavaScript wrappers often use.
+fn wasm_bindgen_names(item: &Item) -> Option<Vec<String>> {
+    let is_export = |a: &&String| attribute_name(a) == "wasm_bindgen";
+    let own = item.attributes.iter().find(is_export);
+    if own.is_none() && !item.block.iter().any(|a| is_export(&a)) {
+        return None;
+    }
+    if let Some(js_name) = own.and_then(|a| argument(a, "js_name")) {
+        return Some(vec![js_name.to_string()]);
+    }
+    Some(vec![item.name.to_string(), camel_case(item.name)])
+}
+
+/// Name of a `#[pyfunction]`, or of a method of a `#[pymethods]` implementation, in Python: the
+/// `name` of its `#[pyo3]` attribute if set, the property of getters and setters, and the class
+/// for the constructors and the special methods (e.g. `__repr__`), which are called implicitly.
+fn pyo3_names(item: &Item) -> Option<Vec<String>> {
+    let attribute = |name: &str| item.attributes.iter().find(|a| attribute_name(a) == name);
+    let function = attribute("pyfunction");
+    let method = item.block.iter().any(|a| attribute_name(a) == "pymethods");
+    if function.is_none() && !method {
+        return None;
+    }
+    let renamed = [attribute("pyo3"), function]
+        .into_iter()
+        .flatten()
+        .find_map(|a| argument(a, "name"));
+    if let Some(name) = renamed {
+        return Some(vec![name.to_string()]);
+    }
+    let special = item.name.starts_with("__") && item.name.ends_with("__");
+    if method && (special || attribute("new").is_some()) {
+        return Some(item.ty.into_iter().map(String::from).collect());
+    }
+    for (accessor, prefix) in [("getter", "get_"), ("setter", "set_")] {
+        if let Some(a) = attribute(accessor) {
+            let property = a
+                .split_once('(')
+                .map(|(_, p)| p.trim_end_matches([')', ']']).trim())
+                .filter(|p| !p.is_empty());
+            let property = property.unwrap_or(item.name.strip_prefix(prefix).unwrap_or(item.name));
+            return Some(vec![property.to_string()]);
+        }
+    }
+    Some(vec![item.name.to_string()])
+}
+
+/// Symbol name of a `#[no_mangle]` or `#[export_name = "f"]` function, whose attribute may be
+/// wrapped in `unsafe(...)` as required since the 2024 edition.
+fn ffi_names(item: &Item) -> Option<Vec<String>> {
+    item.attributes.iter().find_map(|a| {
+        let a = a.strip_prefix("unsafe(").unwrap_or(a);
+        let a = a.trim_end_matches([')', ']']).trim();
+        if a == "no_mangle" {
+            return Some(vec![item.name.to_string()]);
+        }
+        let (key, value) = a.split_once('=')?;
+        (key.trim() == "export_name").then(|| vec![value.trim().trim_matches('"').to_string()])
+    })
+}
+
+/// Names in the language of the candidates exported to it.
+pub fn exports(
+    index: &Index,
+    workspace: &Path,
+    language: &Language,
+    candidates: &Candidates,
+) -> HashMap<Sym, Vec<String>> {
+    let mut sources = Sources::new(workspace);
+    index
+        .occurrences
+        .iter()
+        .filter(|o| o.is_definition())
+        .filter_map(|o| {
+            let d = candidates.get(&o.symbol)?;
+            let path = &index.documents[o.document];
+            let line = o.span().start_line;
+            let attributes = crate::attributes_above(&mut sources, path, line);
+            let block = match d.kind {
+                Kind::Method => impl_block(&mut sources, path, line)
+                    .map(|l| crate::attributes_above(&mut sources, path, l))
+                    .unwrap_or_default(),
+                _ => vec![],
+            };
+            let item = Item {
+                name: index.str(d.display_name),
+                attributes: &attributes,
+                block: &block,
+                ty: crate::passes::implementation(index.str(d.symbol)).map(|(ty, _)| ty),
+            };
+            Some((o.symbol, (language.names)(&item)?))
+        })
+        .collect()
+}
+
+/// Line of the `impl` block enclosing a method, i.e. the closest line above starting with `impl`
+/// and less indented.
+fn impl_block(sources: &mut Sources, path: &str, line: usize) -> Option<usize> {
+    let indentation = |l: &str| l.len() - l.trim_start().len();
+    let method = indentation(sources.line(path, line)?);
+    (0..line).rev().find(|l| {
+        sources.line(path, *l).is_some_and(|l| {
+            let trimmed = l.trim_start();
+            (trimmed.starts_with("impl ") || trimmed.starts_with("impl<"))
+                && indentation(l) < method
+        })
+    })
+}
+
+/// Warn about the exports to the language of the workspace that are not declared in its
+/// configured headers (files, or directories of files with its extensions), and about the
+/// declared functions that are no longer exported, e.g. when the headers generated by cbindgen
+/// are stale.
+pub fn check_headers(
+    index: &Index,
+    workspace: &Path,
+    language: &Language,
+    bindings: &config::Bindings,
+) {
+    let headers = (language.headers)(bindings);
+    if headers.is_empty() {
+        return;
+    }
+    let mut declared: HashMap<String, PathBuf> = HashMap::new();
+    for header in headers {
+        let files = walkdir::WalkDir::new(workspace.join(header))
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|f| {
+                f.file_type().is_file()
+                    && f.path()
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| language.extensions.contains(&e))
+            });
+        for f in files {
+            match std::fs::read(f.path()) {
+                Ok(contents) => {
+                    let path = f.path().strip_prefix(workspace).unwrap_or(f.path());
+                    for name in declarations(&String::from_utf8_lossy(&contents)) {
+                        declared.entry(name).or_insert(path.into());
+                    }
+                }
+                Err(e) => {
+                    warn!(path:% = f.path().display(); "Failed to read {:?}: {}", f.path(), e)
+                }
+            }
+        }
+    }
+    let functions: Candidates = index
+        .symbols
+        .values()
+        .filter(|s| s.kind == Kind::Function || s.kind == Kind::Method)
+        .map(|s| (s.symbol, s))
+        .collect();
+    let mut exported = HashSet::new();
+    for (s, names) in exports(index, workspace, language, &functions) {
+        for name in names {
+            if !declared.contains_key(&name) {
+                let path = crate::index::rust_path(index.str(s));
+                warn!(
+                    "{} is exported as `{}` but not declared in the {} headers",
+                    path.as_deref().unwrap_or(&name),
+                    name,
+                    language.label
+                );
+            }
+            exported.insert(name);
+        }
+    }
+    for (name, path) in declared {
+        if !exported.contains(&name) {
+            warn!(
+                path:% = path.display();
+                "{} is declared in {:?} but not exported by the workspace", name, path
+            );
+        }
+    }
+}
+
+/// Names of the functions declared in a C or C++ header, e.g. `f` for `int32_t f(void);`, outside
+/// of the comments, the preprocessor directives and the bodies of the types and functions.
+fn declarations(contents: &str) -> HashSet<String> {
+    let bytes = contents.as_bytes();
+    // Offsets of the code, without the comments, the literals and the directives
+    let mut code = vec![];
+    let (mut from, mut i) = (0, 0);
+    while i < bytes.len() {
+        let directive = bytes[i] == b'#'
+            && contents[..i]
+                .trim_end_matches([' ', '\t'])
+                .chars()
+                .last()
+                .is_none_or(|c| c == '\n');
+        let end = if directive {
+            let mut end = i;
+            // Directives continue on the next line after a backslash
+            loop {
+                end = contents[end..]
+                    .find('\n')
+                    .map_or(bytes.len(), |e| end + e + 1);
+                if end == bytes.len() || !contents[..end - 1].trim_end().ends_with('\\') {
+                    break Some(end);
+                }
+            }
+        } else {
+            crate::components::literal_end(contents, i)
+        };
+        match end {
+            Some(end) => {
+                code.push(from..i);
+                (from, i) = (end, end);
+            }
+            None => i += 1,
+        }
+    }
+    code.push(from..bytes.len());
+
+    let mut names = HashSet::new();
+    let mut statement = String::new();
+    // Whether each open brace is a body, rather than an `extern "C"` block or a namespace
+    let mut bodies: Vec<bool> = vec![];
+    for range in code {
+        for c in contents[range].chars() {
+            match c {
+                '{' => {
+                    let block = statement.trim_start();
+                    bodies.push(!(block.starts_with("extern") || block.starts_with("namespace")));
+                    statement.clear();
+                }
+                '}' => {
+                    bodies.pop();
+                    statement.clear();
+                }
+                ';' => {
+                    if !bodies.contains(&true) {
+                        names.extend(declared(&statement));
+                    }
+                    statement.clear();
+                }
+                c => statement.push(c),
+            }
+        }
+        statement.push(' ');
+    }
+    names
+}
+
+/// Name of the function declared by a statement of a header, e.g. `f` for `void f(int32_t x)`.
+/// Function pointers, e.g. `void (*f)(void)`, and type definitions are skipped.
+fn declared(statement: &str) -> Option<String> {
+    let statement = statement.trim();
+    if statement.starts_with("typedef") {
+        return None;
+    }
+    let (before, _) = statement.split_once('(')?;
+    // Also with a space before the parameters, e.g. `void f (void)`
+    let before = before.trim_end();
+    let start = before.rfind(|c: char| !(c.is_alphanumeric() || c == '_'))? + 1;
+    let name = &before[start..];
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Number of lines of the sources of the language under the configured directories, relative to
+/// the workspace root, containing each name as an identifier, except in the skipped directories.
+pub fn count(
+    workspace: &Path,
+    language: &Language,
+    bindings: &config::Bindings,
+    names: &HashSet<&str>,
+) -> HashMap<String, usize> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let headers: Vec<PathBuf> = (language.headers)(bindings)
+        .iter()
+        .map(|h| workspace.join(h))
+        .collect();
+    for dir in (language.dirs)(bindings) {
+        let files = walkdir::WalkDir::new(workspace.join(dir))
+            .into_iter()
+            .filter_entry(|e| {
+                !e.file_name()
+                    .to_str()
+                    .is_some_and(|n| language.skipped.contains(&n))
+                    && !headers.iter().any(|h| e.path().starts_with(h))
+            })
+            .filter_map(Result::ok)
+            .filter(|f| {
+                f.file_type().is_file()
+                    && f.path()
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| language.extensions.contains(&e))
+            });
+        for f in files {
+            let contents = match std::fs::read(f.path()) {
+                Ok(c) => c,
+                Err(e) => {
+                    warn!(path:% = f.path().display(); "Failed to read {:?}: {}", f.path(), e);
+                    continue;
+                }
+            };
+            for line in String::from_utf8_lossy(&contents).lines() {
+                let identifiers: HashSet<&str> = line
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                    .filter(|i| names.contains(i))
+                    .collect();
+                for i in identifiers {
+                    *counts.entry(i.to_string()).or_default() += 1;
+                }
+            }
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item<'a>(name: &'a str, attributes: &'a [String], block: &'a [String]) -> Item<'a> {
+        Item {
+            name,
+            attributes,
+            block,
+            ty: Some("Point"),
+        }
+    }
+
+    fn attributes(attributes: &[&str]) -> Vec<String> {
+        attributes.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn wasm_bindgen_names() {
+        let names = |a: &[&str], b: &[&str]| {
+            super::wasm_bindgen_names(&item("get_value", &attributes(a), &attributes(b)))
+        };
+        let both = Some(vec!["get_value".to_string(), "getValue".to_string()]);
+        assert_eq!(names(&["wasm_bindgen]"], &[]), both);
+        assert_eq!(names(&["wasm_bindgen::prelude::wasm_bindgen]"], &[]), both);
+        // Methods of exported implementations
+        assert_eq!(names(&["inline]"], &["wasm_bindgen]"]), both);
+        assert_eq!(
+            names(&["wasm_bindgen(js_name = value)]"], &[]),
+            Some(vec!["value".to_string()])
+        );
+        assert_eq!(
+            names(
+                &["wasm_bindgen(skip_typescript, js_name = \"value\")]"],
+                &[]
+            ),
+            Some(vec!["value".to_string()])
+        );
+        assert_eq!(names(&["inline]"], &["derive(Debug)]"]), None);
+    }
+
+    #[test]
+    fn pyo3_names() {
+        let names = |name: &str, a: &[&str], b: &[&str]| {
+            super::pyo3_names(&item(name, &attributes(a), &attributes(b)))
+        };
+        let name = |n: &str| Some(vec![n.to_string()]);
+        assert_eq!(names("f", &["pyfunction]"], &[]), name("f"));
+        assert_eq!(names("f", &["pyfunction(name = \"g\")]"], &[]), name("g"));
+        assert_eq!(
+            names("f", &["pyfunction]", "pyo3(name = \"h\")]"], &[]),
+            name("h")
+        );
+        assert_eq!(names("f", &["inline]"], &[]), None);
+        // Methods, the constructors and special methods being called through the class
+        let methods: &[&str] = &["pymethods]"];
+        assert_eq!(names("area", &[], methods), name("area"));
+        assert_eq!(names("new", &["new]"], methods), name("Point"));
+        assert_eq!(names("__repr__", &[], methods), name("Point"));
+        assert_eq!(names("get_x", &["getter]"], methods), name("x"));
+        assert_eq!(names("x", &["getter]"], methods), name("x"));
+        assert_eq!(names("get_x", &["getter(y)]"], methods), name("y"));
+        assert_eq!(names("set_x", &["setter]"], methods), name("x"));
+    }
+
+    #[test]
+    fn declarations() {
+        let header = r#"#include <stdint.h>
+#define MAX(a, b) \
+  ((a) > (b) ? (a) : (b));
+/* int32_t commented(void); */
+// int32_t line_comment(void);
+typedef void (*callback)(int32_t);
+#ifdef __cplusplus
+extern "C" {
+namespace ns {
+#endif
+int32_t add(int32_t a, int32_t b);
+const char *name(const char *s = "x;");
+struct S { int (*f)(void); };
+static inline int helper(int x) { return add(x, 1); }
+#ifdef __cplusplus
+}}
+#endif
+"#;
+        let mut names = super::declarations(header).into_iter().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["add", "name"]);
+        assert_eq!(super::declared("void f(int32_t x)").as_deref(), Some("f"));
+        assert_eq!(super::declared("uint8_t *f (void)").as_deref(), Some("f"));
+        assert_eq!(super::declared("typedef int (f)(void)"), None);
+        assert_eq!(super::declared("int x"), None);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use itertools::Itertools;
+use log::*;
+use serde::Serialize;
+
+use crate::Finding;
+
+/// Last commit changing the definition line of a finding, according to `git blame`.
+#[derive(Serialize, Clone, Debug)]
+pub struct Blame {
+    pub commit: String,
+    pub author: String,
+    /// Author date, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl Blame {
+    /// Author date, as `YYYY-MM-DD`.
+    pub fn date(&self) -> String {
+        crate::history::date(self.timestamp)
+    }
+}
+
+/// Blame of the definition lines of the findings, by path and zero-based line. Lines that are
+/// not committed yet, and files outside of a git repository, are omitted.
+pub fn blame(workspace: &Path, findings: &[&Finding]) -> HashMap<(String, usize), Blame> {
+    let mut blames = HashMap::new();
+    for (path, findings) in &findings.iter().group_by(|f| f.path) {
+        // Out of range lines, e.g. with a stale index, fail the whole command
+        let n_lines = crate::read_source(&workspace.join(path))
+            .map(|c| c.lines().count())
+            .unwrap_or_default();
+        let mut args = vec!["blame".to_string(), "--porcelain".into()];
+        for f in findings.filter(|f| f.span.start_line < n_lines) {
+            args.extend(["-L".into(), format!("{0},{0}", f.span.start_line + 1)]);
+        }
+        args.extend(["--".into(), path.into()]);
+        let output = match duct::cmd("git", &args)
+            .dir(workspace)
+            .stderr_capture()
+            .read()
+        {
+            Ok(output) => output,
+            Err(e) => {
+                debug!("Failed to blame {}: {}", path, e);
+                continue;
+            }
+        };
+        for (line, blame) in parse(&output) {
+            blames.insert((path.to_string(), line), blame);
+        }
+    }
+    blames
+}
+
+/// Parse the porcelain output of `git blame`, which only describes each commit the first time
+/// it appears.
+fn parse(output: &str) -> Vec<(usize, Blame)> {
+    let mut commits: HashMap<&str, (Option<&str>, Option<u64>)> = HashMap::new();
+    let mut lines = vec![];
+    let mut current = None;
+    for l in output.lines() {
+        if l.starts_with('\t') {
+            continue;
+        }
+        let mut parts = l.split(' ');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(sha), Some(_), Some(line))
+                if sha.len() == 40 && sha.bytes().all(|b| b.is_ascii_hexdigit()) =>
+            {
+                commits.entry(sha).or_default();
+                if let Ok(line) = line.parse::<usize>() {
+                    lines.push((line - 1, sha));
+                }
+                current = Some(sha);
+            }
+            _ => {
+                let Some(commit) = current.and_then(|c| commits.get_mut(c)) else {
+                    continue;
+                };
+                if let Some(author) = l.strip_prefix("author ") {
+                    commit.0 = Some(author);
+                } else if let Some(time) = l.strip_prefix("author-time ") {
+                    commit.1 = time.parse().ok();
+                }
+            }
+        }
+    }
+    lines
+        .into_iter()
+        // Uncommitted changes
+        .filter(|(_, sha)| sha.bytes().any(|b| b != b'0'))
+        .filter_map(|(line, sha)| {
+            let (author, timestamp) = commits.get(sha)?;
+            Some((
+                line,
+                Blame {
+                    commit: sha.to_string(),
+                    author: (*author)?.to_string(),
+                    timestamp: (*timestamp)?,
+                },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse() {
+        let sha = "d406aeb02dda3e74a2e7c6d859dd0c519075fa60";
+        let uncommitted = "0".repeat(40);
+        let output = format!(
+            "{sha} 1 1 1\nauthor Jane Doe\nauthor-mail <jane@example.com>\n\
+             author-time 1791988132\nauthor-tz +0000\nsummary Add a 1 2 3\n\
+             filename src/lib.rs\n\tuse std::collections::HashMap;\n\
+             {uncommitted} 7 5 1\nauthor Not Committed Yet\nauthor-time 1791988200\n\
+             filename src/lib.rs\n\tfn f() {{}}\n\
+             {sha} 3 9\n\tpub fn g() {{}}\n"
+        );
+        let blames = super::parse(&output);
+        // Zero-based lines of the current file, the commit being described once
+        assert_eq!(
+            blames
+                .iter()
+                .map(|(line, b)| (*line, b.commit.as_str(), b.author.as_str(), b.timestamp))
+                .collect::<Vec<_>>(),
+            [
+                (0, sha, "Jane Doe", 1791988132),
+                (8, sha, "Jane Doe", 1791988132)
+            ]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
+
+use log::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::index::Index;
+
+/// Bumped whenever the cached representations change.
+const VERSION: u32 = 11;
+
+/// The cargo target directory.
+pub fn target_dir(workspace: &Path) -> PathBuf {
+    std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| workspace.join("target"))
+}
+
+/// Directory for the files generated by this tool, inside the cargo target directory.
+pub fn dir(workspace: &Path) -> PathBuf {
+    target_dir(workspace).join("workspace-unused-pub")
+}
+
+/// Hash of the contents of a file.
+fn hash_file(path: &Path) -> anyhow::Result<u64> {
+    let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+    let mut file = std::fs::File::open(path)?;
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    hasher.update(&VERSION.to_le_bytes());
+    Ok(hasher.digest())
+}
+
+/// Hash of some bytes, e.g. file contents.
+pub fn hash(bytes: &[u8]) -> u64 {
+    xxhash_rust::xxh3::xxh3_64_with_seed(bytes, VERSION as u64)
+}
+
+/// Read a cached value, returning `None` if it is missing, was written by another version or
+/// cannot be deserialized.
+pub fn read<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path).ok()?);
+    // Other representations could be deserialized as huge lengths
+    if bincode::deserialize_from::<_, u32>(&mut reader).ok() != Some(VERSION) {
+        debug!("Ignoring {:?}, written by another version", path);
+        return None;
+    }
+    match bincode::deserialize_from(reader) {
+        Ok(value) => {
+            debug!("Loaded {:?} from cache", path);
+            Some(value)
+        }
+        Err(e) => {
+            warn!(path:% = path.display(); "Failed to read the cache {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Write a value to the cache, warning on failure.
+pub fn write<T: Serialize>(path: &Path, value: &T) {
+    let write = || -> anyhow::Result<()> {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        // Write atomically, as concurrent runs may read the cache
+        let tmp = path.with_extension("tmp");
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        bincode::serialize_into(&mut writer, &VERSION)?;
+        bincode::serialize_into(writer, value)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    };
+    match write() {
+        Ok(()) => debug!("Cached {:?}", path),
+        Err(e) => warn!(path:% = path.display(); "Failed to write the cache {:?}: {}", path, e),
+    }
+}
+
+/// Whether a path designates the standard input, as `-`.
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Open an index file or the standard input, decompressing it if it is compressed with gzip or
+/// zstd.
+pub fn open(path: &Path) -> anyhow::Result<Box<dyn BufRead>> {
+    let reader: Box<dyn Read> = if is_stdin(path) {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::fs::File::open(path)?)
+    };
+    let mut reader = std::io::BufReader::new(reader);
+    // Detected from the magic numbers rather than the extension
+    let magic = reader.fill_buf()?;
+    Ok(if magic.starts_with(&[0x1f, 0x8b]) {
+        Box::new(std::io::BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(reader),
+        ))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Box::new(std::io::BufReader::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Box::new(reader)
+    })
+}
+
+/// Parser of an index file.
+pub type Parser = fn(&mut dyn BufRead) -> anyhow::Result<Index>;
+
+/// Load the index parsed from a file, from the cache if it is available.
+pub fn load_index(
+    workspace: &Path,
+    path: &Path,
+    parser: Parser,
+    use_cache: bool,
+) -> anyhow::Result<Index> {
+    let parse = || -> anyhow::Result<Index> { parser(&mut open(path)?) };
+    // Standard input cannot be hashed before being read
+    if !use_cache || is_stdin(path) {
+        return parse();
+    }
+
+    let dir = dir(workspace);
+    // Keyed by the path as well, so that the caches of several indices coexist
+    let prefix = format!("index-{:016x}-", hash(path.as_os_str().as_encoded_bytes()));
+    let cached = dir.join(format!("{}{:016x}.bin", prefix, hash_file(path)?));
+    if let Some(index) = read(&cached) {
+        return Ok(index);
+    }
+    let index = parse()?;
+    // Remove caches of previous versions of the index
+    for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if name.starts_with(&prefix) && name.ends_with(".bin") {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+    write(&cached, &index);
+    Ok(index)
+}
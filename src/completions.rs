@@ -0,0 +1,32 @@
+use std::ffi::OsStr;
+use std::io::Write;
+
+use clap::CommandFactory;
+use clap_complete::CompletionCandidate;
+
+use crate::{metadata, CompletionsFlags, MainFlags};
+
+/// Print the completion script of a shell for `cargo workspace-unused-pub`.
+pub fn completions(args: &CompletionsFlags) -> anyhow::Result<()> {
+    // clap_complete panics on write errors, e.g. a closed pipe
+    let mut script = vec![];
+    clap_complete::generate(args.shell, &mut MainFlags::command(), "cargo", &mut script);
+    std::io::stdout().lock().write_all(&script)?;
+    Ok(())
+}
+
+/// Names of the members of the workspace in the current directory starting with the completed
+/// value, for the dynamic completions of `--package`.
+pub fn packages(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(mut workspace) = std::env::current_dir() else {
+        return vec![];
+    };
+    crate::discover_workspace(&mut workspace);
+    let current = current.to_string_lossy();
+    metadata::Workspace::load(&workspace)
+        .members
+        .into_iter()
+        .filter(|m| m.name.starts_with(&*current))
+        .map(|m| CompletionCandidate::new(m.name))
+        .collect()
+}
@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use log::*;
+use rayon::prelude::*;
+
+use crate::{search, AnalysisFlags};
+
+/// Macros of the UI frameworks whose invocations name components, e.g. `<Counter />` in Yew's
+/// `html!`, Leptos' `view!` or Dioxus' `rsx!`.
+pub const DEFAULT_MACROS: [&str; 3] = ["html", "view", "rsx"];
+
+/// Count the invocations of the macros naming each name as a token in the Rust files under the
+/// roots, relative to the workspace root. The invocations of a name are only counted inside its
+/// scope if it has one, e.g. the crate of a binary.
+pub fn count(
+    args: &AnalysisFlags,
+    roots: &[PathBuf],
+    macros: &[String],
+    names: &[&str],
+    scopes: &[Option<PathBuf>],
+) -> Vec<usize> {
+    if macros.is_empty() {
+        return vec![0; names.len()];
+    }
+    let macros: Vec<&str> = macros.iter().map(String::as_str).collect();
+    count_with(args, roots, names, scopes, |contents| {
+        invocations(contents, &macros)
+    })
+}
+
+/// Count the sets of tokens naming each name, as returned by `scan` for the contents of each Rust
+/// file under the roots, inside the scope of the name if it has one.
+pub(crate) fn count_with(
+    args: &AnalysisFlags,
+    roots: &[PathBuf],
+    names: &[&str],
+    scopes: &[Option<PathBuf>],
+    scan: impl for<'c> Fn(&'c str) -> Vec<HashSet<&'c str>> + Sync,
+) -> Vec<usize> {
+    let workspace = &args.workspace;
+    let mut totals = vec![0usize; names.len()];
+    if names.is_empty() {
+        return totals;
+    }
+    let indices: HashMap<&str, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    let scopes: Vec<Option<PathBuf>> = scopes
+        .iter()
+        .map(|s| s.as_ref().map(|s| workspace.join(s)))
+        .collect();
+    let counts: Vec<(PathBuf, Vec<usize>)> = search::rust_files(args, roots)
+        .into_par_iter()
+        .filter_map(|path| {
+            let contents = match std::fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(e) => {
+                    debug!("Skipping {:?}: {}", path, e);
+                    return None;
+                }
+            };
+            let named = scan(&contents)
+                .into_iter()
+                .flatten()
+                .filter_map(|token| indices.get(token).copied())
+                .collect();
+            Some((path, named))
+        })
+        .collect();
+    for (path, named) in counts {
+        for i in named {
+            if scopes[i].as_ref().is_none_or(|s| path.starts_with(s)) {
+                totals[i] += 1;
+            }
+        }
+    }
+    totals
+}
+
+pub(crate) fn is_identifier(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// Identifiers of each invocation of the macros in the contents of a Rust file, e.g. `Counter`
+/// and `count` for `html! { <Counter value={count} /> }`, outside of the string literals and
+/// comments. Invocations nested in another one are part of it.
+pub(crate) fn invocations<'a>(contents: &'a str, macros: &[&str]) -> Vec<HashSet<&'a str>> {
+    let bytes = contents.as_bytes();
+    let mut invocations = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(end) = literal_end(contents, i) {
+            i = end;
+            continue;
+        }
+        if !is_identifier(bytes[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && is_identifier(bytes[i]) {
+            i += 1;
+        }
+        if bytes.get(i) != Some(&b'!') || !macros.contains(&&contents[start..i]) {
+            continue;
+        }
+        let mut open = i + 1;
+        while bytes.get(open).is_some_and(|c| c.is_ascii_whitespace()) {
+            open += 1;
+        }
+        if !matches!(bytes.get(open), Some(b'{' | b'(' | b'[')) {
+            continue;
+        }
+        let (tokens, close) = tokens(contents, open);
+        invocations.push(tokens);
+        i = close;
+    }
+    invocations
+}
+
+/// Identifiers between the delimiter at an offset and the matching one, which is returned with
+/// them. Delimiters are not told apart, as the tokens of macro invocations are balanced.
+pub(crate) fn tokens(contents: &str, open: usize) -> (HashSet<&str>, usize) {
+    let bytes = contents.as_bytes();
+    let mut tokens = HashSet::new();
+    let mut depth = 0;
+    let mut i = open;
+    while i < bytes.len() {
+        if let Some(end) = literal_end(contents, i) {
+            i = end;
+            continue;
+        }
+        match &bytes[i..] {
+            [b'{' | b'(' | b'[', ..] => depth += 1,
+            [b'}' | b')' | b']', ..] => {
+                depth -= 1;
+                if depth == 0 {
+                    return (tokens, i + 1);
+                }
+            }
+            [c, ..] if is_identifier(*c) => {
+                let len = bytes[i..].iter().take_while(|c| is_identifier(**c)).count();
+                if !c.is_ascii_digit() {
+                    tokens.insert(&contents[i..i + len]);
+                }
+                i += len;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    (tokens, bytes.len())
+}
+
+/// Offset just after the comment, or the string or character literal, starting at an offset.
+pub(crate) fn literal_end(contents: &str, i: usize) -> Option<usize> {
+    let bytes = contents.as_bytes();
+    let len = bytes.len();
+    if let Some(end) = raw_string_end(contents, i) {
+        return Some(end);
+    }
+    let after = |from: usize, c: u8| {
+        bytes
+            .get(from..)
+            .and_then(|rest| rest.iter().position(|b| *b == c))
+            .map_or(len, |e| from + e + 1)
+    };
+    Some(match &bytes[i..] {
+        [b'/', b'/', ..] => after(i, b'\n'),
+        [b'/', b'*', ..] => contents[i + 2..].find("*/").map_or(len, |e| i + e + 4),
+        [b'"', ..] => {
+            let mut j = i + 1;
+            while j < len && bytes[j] != b'"' {
+                j += if bytes[j] == b'\\' { 2 } else { 1 };
+            }
+            (j + 1).min(len)
+        }
+        // Character literals, e.g. `'"'` or `'\''`, rather than lifetimes
+        [b'\'', b'\\', ..] => after(i + 3, b'\''),
+        [b'\'', _, b'\'', ..] => i + 3,
+        _ => return None,
+    })
+}
+
+/// Offset just after the raw string literal starting at an offset, e.g. `r#"..."#` or `br"..."`,
+/// whose contents have no escapes.
+fn raw_string_end(contents: &str, i: usize) -> Option<usize> {
+    let bytes = contents.as_bytes();
+    let prefix = match &bytes[i..] {
+        [b'b' | b'c', b'r', ..] => 2,
+        [b'r', ..] => 1,
+        _ => return None,
+    };
+    // Rather than the end of an identifier
+    if i > 0 && is_identifier(bytes[i - 1]) {
+        return None;
+    }
+    let hashes = bytes[i + prefix..]
+        .iter()
+        .take_while(|b| **b == b'#')
+        .count();
+    let open = i + prefix + hashes;
+    if bytes.get(open) != Some(&b'"') {
+        return None;
+    }
+    let close = format!("\"{}", "#".repeat(hashes));
+    Some(
+        contents[open + 1..]
+            .find(&close)
+            .map_or(bytes.len(), |e| open + 1 + e + close.len()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn invocations() {
+        let contents = r###"
+fn f() -> Html {
+    // html! { <Commented /> }
+    let s = "html! { <InString /> }";
+    html! {
+        <Counter value={count} label={"Label {"} />
+        { view! { <Nested /> } }
+    }
+}
+fn g() { myhtml! { <Other /> }; view!(<Paren text=r#"}"# />) }
+"###;
+        let invocations = super::invocations(contents, &["html", "view"])
+            .into_iter()
+            .map(|tokens| tokens.into_iter().collect::<BTreeSet<_>>())
+            .collect::<Vec<_>>();
+        // Nested invocations are part of the enclosing one
+        assert_eq!(
+            invocations,
+            [
+                BTreeSet::from(["Counter", "Nested", "count", "label", "value", "view"]),
+                BTreeSet::from(["Paren", "text"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn literal_end() {
+        let end = |contents: &str| super::literal_end(contents, 0);
+        assert_eq!(end("// }\nx"), Some(5));
+        assert_eq!(end("/* } */x"), Some(7));
+        assert_eq!(end(r#""a\"}"x"#), Some(6));
+        assert_eq!(end("'}'x"), Some(3));
+        assert_eq!(end(r"'\''x"), Some(4));
+        assert_eq!(end("'a x"), None);
+        assert_eq!(end(r##"r#"a"}"#x"##), Some(8));
+        assert_eq!(end(r#"br"\"x"#), Some(5));
+        assert_eq!(end("r#type"), None);
+        assert_eq!(super::literal_end(r#"ar"x""#, 1), None);
+    }
+}
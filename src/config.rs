@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Configuration read from `workspace-unused-pub.toml` at the workspace root.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Fingerprints of findings to ignore.
+    pub ignore: Vec<String>,
+    /// Command generating the SCIP index, overridden by `--indexer-cmd`.
+    pub indexer_cmd: Option<String>,
+    /// Directories containing the sources, relative to the workspace root, for build systems
+    /// other than Cargo (e.g. Bazel or Buck). When set, the workspace does not need a
+    /// `Cargo.toml`, only these directories are searched, and the document paths of the index
+    /// are also resolved relative to them.
+    pub source_roots: Vec<PathBuf>,
+    /// Other workspaces calling into this one, e.g. through path dependencies, relative to the
+    /// workspace root. Their indices are merged, and their items analyzed as well.
+    pub workspaces: Vec<PathBuf>,
+    /// Names of the filter passes to run, in order. Defaults to all of them.
+    pub passes: Option<Vec<String>>,
+    /// Macros whose invocations name components as tokens, e.g. `<Counter />`, which the index
+    /// may not resolve. Defaults to `html`, `view` and `rsx`.
+    pub component_macros: Option<Vec<String>>,
+    /// External filters, which run after the built-in passes unless ordered with `passes`.
+    pub filters: Vec<Filter>,
+    /// Search settings by file extension, e.g. `sql`, overriding `--extensions`.
+    pub extensions: BTreeMap<String, Extension>,
+    /// Patterns that the lines of the files matching a glob must match to count, by glob
+    /// relative to the workspace root, e.g. `templates/**/*.html`. The files are then searched.
+    pub patterns: BTreeMap<String, Vec<String>>,
+    /// Items registered by name in configuration files, e.g. the handlers of a job scheduler in
+    /// YAML.
+    pub registrations: Vec<Registration>,
+    /// How the mentions in the Markdown files, e.g. the READMEs, are considered.
+    pub markdown: Markdown,
+    /// Sources in other languages calling into the workspace through bindings.
+    pub bindings: Bindings,
+}
+
+/// Directories of the sources calling the exports of the workspace, relative to the workspace
+/// root.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Bindings {
+    /// JavaScript and TypeScript sources, for the `#[wasm_bindgen]` exports.
+    pub javascript: Vec<PathBuf>,
+    /// Python sources, for the exports of PyO3 modules.
+    pub python: Vec<PathBuf>,
+    /// C and C++ sources, for the `#[no_mangle]` and `#[export_name]` functions.
+    pub c: Vec<PathBuf>,
+    /// Headers declaring these functions, e.g. generated by cbindgen, to check against them.
+    pub c_headers: Vec<PathBuf>,
+}
+
+/// Role of the Markdown files in the analysis.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Markdown {
+    /// Mentions are documentation: the items only mentioned there are listed separately.
+    #[default]
+    Documentation,
+    /// Mentions count as usages, like in the other searched files.
+    Usage,
+    /// The Markdown files are not searched.
+    Ignored,
+}
+
+/// Names captured in the files with some extensions, which count as usages of the items.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Registration {
+    /// Extensions of the files, e.g. `yaml` and `yml`, which are then searched.
+    pub extensions: Vec<String>,
+    /// Regular expression whose first capture group is the name, possibly with a path, e.g.
+    /// `handler: (\S+)`.
+    pub regex: String,
+}
+
+/// How the files with an extension are searched.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Extension {
+    /// Whether the files count as usage evidence, regardless of `--extensions`. Defaults to
+    /// `true`.
+    pub search: Option<bool>,
+    /// Regular expression that the lines must match to count, in which `{name}` stands for the
+    /// searched name, e.g. `-- handler: {name}`.
+    pub pattern: Option<String>,
+}
+
+/// An external filter, see [`crate::passes::External`].
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Filter {
+    pub name: String,
+    /// Command receiving the candidates on stdin, run in the workspace root.
+    pub command: String,
+}
+
+impl Config {
+    pub fn path(workspace: &Path) -> PathBuf {
+        workspace.join("workspace-unused-pub.toml")
+    }
+
+    pub fn load(workspace: &Path) -> anyhow::Result<Self> {
+        let path = Self::path(workspace);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        toml::from_str(&contents).map_err(|e| anyhow::anyhow!("Failed to parse {:?}: {}", path, e))
+    }
+
+    /// Append fingerprints to the `ignore` list, preserving the formatting of the file.
+    pub fn add_ignored(workspace: &Path, fingerprints: &[String]) -> anyhow::Result<()> {
+        let path = Self::path(workspace);
+        let mut doc: toml_edit::DocumentMut = if path.exists() {
+            std::fs::read_to_string(&path)?.parse()?
+        } else {
+            Default::default()
+        };
+        let ignore = doc
+            .entry("ignore")
+            .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+            .as_array_mut()
+            .ok_or_else(|| anyhow::anyhow!("`ignore` in {:?} is not an array", path))?;
+        for f in fingerprints {
+            if !ignore.iter().any(|v| v.as_str() == Some(f)) {
+                ignore.push(f.as_str());
+            }
+        }
+        std::fs::write(&path, doc.to_string())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::io::{BufRead, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use clap::Parser;
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::index::Index;
+use crate::{cache, AnalysisFlags, Command, MainFlags, ReportFormat, WhoUsesFlags};
+
+/// A request, sent as a line of JSON.
+#[derive(Serialize, Deserialize)]
+struct Request {
+    /// Arguments after `cargo workspace-unused-pub`, starting with the command unless it is the
+    /// default `check`.
+    args: Vec<String>,
+    /// Working directory of the client, against which the relative paths of the arguments are
+    /// resolved.
+    cwd: PathBuf,
+    /// Whether the client writes to a terminal.
+    color: bool,
+}
+
+/// A parsed request.
+enum Requested {
+    Check(AnalysisFlags, ReportFormat),
+    WhoUses(WhoUsesFlags),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Response {
+    /// Output, as printed by the command.
+    output: String,
+    findings: usize,
+    error: Option<String>,
+}
+
+fn socket(workspace: &Path) -> PathBuf {
+    cache::dir(workspace).join("daemon.sock")
+}
+
+/// Identifies a version of the SCIP files, to reload the index when they change.
+type Stamp = Vec<Option<(u64, SystemTime)>>;
+
+fn stamp(scips: &[PathBuf]) -> Stamp {
+    scips
+        .iter()
+        .map(|scip| {
+            let metadata = std::fs::metadata(scip).ok()?;
+            Some((metadata.len(), metadata.modified().ok()?))
+        })
+        .collect()
+}
+
+struct Loaded {
+    index: Index,
+    /// Metadata of the workspace, reloaded with the index.
+    metadata: crate::metadata::Workspace,
+    stamp: Stamp,
+}
+
+/// Answer `check` and `who-uses` requests on a socket in the cache directory, keeping the parsed
+/// index in memory between requests.
+pub fn serve(mut args: AnalysisFlags) -> anyhow::Result<()> {
+    // The requests are answered in the working directory of their client
+    args.workspace = std::path::absolute(&args.workspace)?;
+    args.scip = absolute(crate::scip_paths(&args)?)?;
+    let path = socket(&args.workspace);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    // Left over by a previous daemon
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    info!("Listening on {:?}", path);
+    let mut loaded = None;
+    for stream in listener.incoming() {
+        let res = stream
+            .map_err(anyhow::Error::from)
+            .and_then(|s| handle(&args, &mut loaded, s));
+        if let Err(e) = res {
+            warn!("Failed to answer a request: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle(
+    daemon_args: &AnalysisFlags,
+    loaded: &mut Option<Loaded>,
+    stream: UnixStream,
+) -> anyhow::Result<()> {
+    let mut line = String::new();
+    std::io::BufReader::new(&stream).read_line(&mut line)?;
+    let request: Request = serde_json::from_str(&line)?;
+    let response = match answer(daemon_args, loaded, &request) {
+        Ok((output, findings)) => Response {
+            output,
+            findings,
+            error: None,
+        },
+        Err(e) => Response {
+            output: String::new(),
+            findings: 0,
+            error: Some(e.to_string()),
+        },
+    };
+    serde_json::to_writer(&stream, &response)?;
+    Ok(())
+}
+
+/// Canonical paths, or absolute ones for those that do not exist.
+fn absolute(paths: Vec<PathBuf>) -> std::io::Result<Vec<PathBuf>> {
+    paths
+        .iter()
+        .map(|p| p.canonicalize().or_else(|_| std::path::absolute(p)))
+        .collect()
+}
+
+/// Parse the arguments of a request, in the working directory of the client.
+fn parse(daemon_args: &AnalysisFlags, request: &Request) -> anyhow::Result<Requested> {
+    std::env::set_current_dir(&request.cwd)?;
+    let argv = ["cargo", "workspace-unused-pub"]
+        .into_iter()
+        .map(String::from)
+        .chain(request.args.iter().cloned());
+    let MainFlags::WorkspaceUnusedPub(flags) = MainFlags::try_parse_from(argv)?;
+    let mut requested = match flags.command.unwrap_or(Command::Check(flags.check)) {
+        Command::Check(check) => {
+            anyhow::ensure!(
+                !check.tui && !check.daemon && !check.print_schema && check.emit_graph.is_none(),
+                "--tui, --daemon, --print-schema and --emit-graph cannot be sent to the daemon"
+            );
+            Requested::Check(check.analysis, check.format)
+        }
+        Command::WhoUses(who_uses) => Requested::WhoUses(who_uses),
+        _ => anyhow::bail!("The daemon only answers check and who-uses requests"),
+    };
+    let args = match &mut requested {
+        Requested::Check(args, _) => args,
+        Requested::WhoUses(who_uses) => &mut who_uses.analysis,
+    };
+    // The socket is specific to the workspace
+    args.workspace = daemon_args.workspace.clone();
+    let scips = absolute(crate::scip_paths(args)?)?;
+    anyhow::ensure!(
+        scips == daemon_args.scip,
+        "The daemon serves the index {:?}",
+        daemon_args.scip
+    );
+    Ok(requested)
+}
+
+fn answer(
+    daemon_args: &AnalysisFlags,
+    loaded: &mut Option<Loaded>,
+    request: &Request,
+) -> anyhow::Result<(String, usize)> {
+    let requested = parse(daemon_args, request)?;
+    let args = match &requested {
+        Requested::Check(args, _) => args,
+        Requested::WhoUses(who_uses) => &who_uses.analysis,
+    };
+    let scips = &daemon_args.scip;
+    let loaded = match loaded {
+        Some(l) if !args.reindex && l.stamp == stamp(scips) => l,
+        _ => loaded.insert(Loaded {
+            index: crate::load_index(args)?,
+            metadata: crate::metadata::Workspace::load(&args.workspace),
+            stamp: stamp(scips),
+        }),
+    };
+    let mut output = vec![];
+    colored::control::set_override(request.color);
+    let res = match &requested {
+        Requested::Check(args, format) => (|| {
+            let config = Config::load(&args.workspace)?;
+            let (findings, passes, listed) =
+                crate::find_unused_listed(&loaded.index, args, &config, &loaded.metadata)?;
+            let ctx = crate::passes::Context {
+                index: &loaded.index,
+                args,
+                config: &config,
+                metadata: &loaded.metadata,
+            };
+            crate::write_report(&ctx, *format, &findings, passes, &listed, &mut output)?;
+            Ok(findings.len())
+        })(),
+        Requested::WhoUses(who_uses) => {
+            crate::who_uses::write(&loaded.index, who_uses, &mut output).map(|()| 0)
+        }
+    };
+    colored::control::unset_override();
+    let findings = res?;
+    Ok((String::from_utf8(output)?, findings))
+}
+
+/// Forward the current `check` or `who-uses` invocation to a daemon serving the workspace, if any.
+pub fn forward(args: &AnalysisFlags) -> Option<anyhow::Result<()>> {
+    let path = socket(&args.workspace);
+    // The daemon parses the arguments again, and could not read the same file list
+    if !path.exists() || args.files_from.is_some() {
+        return None;
+    }
+    let stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        Err(e) => {
+            debug!("No daemon listening on {:?}: {}", path, e);
+            return None;
+        }
+    };
+    info!("Forwarding to the daemon listening on {:?}", path);
+    Some(request(stream))
+}
+
+fn request(mut stream: UnixStream) -> anyhow::Result<()> {
+    // Arguments after `cargo-workspace-unused-pub workspace-unused-pub`
+    let args = std::env::args_os()
+        .skip(2)
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+    let request = Request {
+        args,
+        cwd: std::env::current_dir()?,
+        // Detected for stdout, unless overridden by --color
+        color: colored::control::SHOULD_COLORIZE.should_colorize(),
+    };
+    writeln!(stream, "{}", serde_json::to_string(&request)?)?;
+    let response: Response = serde_json::from_reader(&stream)?;
+    if let Some(error) = response.error {
+        anyhow::bail!("{}", error);
+    }
+    print!("{}", response.output);
+    anyhow::ensure!(
+        response.findings == 0,
+        "Found {} possibly unused functions",
+        response.findings
+    );
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use std::io::Write;
+use std::path::Path;
+
+use colored::Colorize;
+use itertools::Itertools;
+
+use crate::{analyze, cache, indexer, metadata, AnalysisFlags, DoctorFlags, Input};
+
+/// Workspace analyzed end-to-end, in which only `unused` should be reported.
+const FIXTURE: [(&str, &str); 3] = [
+    (
+        "Cargo.toml",
+        "[package]\nname = \"doctor-fixture\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    ),
+    ("src/lib.rs", "pub fn used() {}\n\npub fn unused() {}\n"),
+    ("src/main.rs", "fn main() {\n    doctor_fixture::used();\n}\n"),
+];
+
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Outcome of a check, with a hint on how to fix it.
+struct Check {
+    name: &'static str,
+    status: Status,
+    message: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn failed(name: &'static str, status: Status, message: impl Into<String>, hint: &str) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+            hint: Some(hint.into()).filter(|h: &String| !h.is_empty()),
+        }
+    }
+}
+
+/// Check the environment and analyze a small bundled workspace, printing the problems with how
+/// to fix them.
+pub fn doctor(args: DoctorFlags) -> anyhow::Result<()> {
+    let args = args.analysis;
+    let mut checks = vec![cargo(), indexer(&args)];
+    if matches!(checks[1].status, Status::Ok) {
+        checks.push(fixture(&args));
+    }
+    checks.extend(workspace(&args));
+
+    let mut out = std::io::stdout().lock();
+    for c in &checks {
+        let status = match c.status {
+            Status::Ok => "ok".green(),
+            Status::Warning => "warning".yellow(),
+            Status::Error => "error".red(),
+        };
+        writeln!(out, "{:>7} {}: {}", status.bold(), c.name, c.message)?;
+        if let Some(hint) = &c.hint {
+            writeln!(out, "        {}", hint.dimmed())?;
+        }
+    }
+    let errors = checks
+        .iter()
+        .filter(|c| matches!(c.status, Status::Error))
+        .count();
+    anyhow::ensure!(errors == 0, "{} of {} checks failed", errors, checks.len());
+    Ok(())
+}
+
+fn cargo() -> Check {
+    match duct::cmd!("cargo", "--version").stderr_capture().read() {
+        Ok(version) => Check::ok("cargo", version.trim()),
+        Err(e) => Check::failed(
+            "cargo",
+            Status::Error,
+            format!("`cargo --version` failed: {}", e),
+            &format!(
+                "Install Rust with rustup, and check that cargo is in the PATH: {}",
+                std::env::var("PATH").unwrap_or_default()
+            ),
+        ),
+    }
+}
+
+fn indexer(args: &AnalysisFlags) -> Check {
+    if args.input != Input::Scip {
+        return Check::ok("indexer", "not needed for this input format");
+    }
+    match indexer::preflight(args) {
+        Ok(Some(version)) => Check::ok("indexer", version),
+        Ok(None) => Check::ok("indexer", "custom indexer found"),
+        Err(e) => Check::failed(
+            "indexer",
+            Status::Error,
+            e.to_string(),
+            &format!(
+                "The indexer is looked up in the PATH: {}",
+                std::env::var("PATH").unwrap_or_default()
+            ),
+        ),
+    }
+}
+
+/// Generate the index of the fixture and analyze it.
+fn fixture(args: &AnalysisFlags) -> Check {
+    let dir = std::env::temp_dir().join(format!(
+        "workspace-unused-pub-doctor-{}",
+        std::process::id()
+    ));
+    let res = run_fixture(args, &dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    match res {
+        Ok(findings) if findings == ["unused"] => Check::ok(
+            "fixture",
+            "indexed and analyzed a bundled workspace, with the expected finding",
+        ),
+        Ok(findings) => Check::failed(
+            "fixture",
+            Status::Error,
+            format!(
+                "expected only `unused` to be reported in the bundled workspace, got [{}]",
+                findings.join(", ")
+            ),
+            "The indexer may not resolve the references, e.g. if it is too old or cannot run `cargo check`.",
+        ),
+        Err(e) => Check::failed(
+            "fixture",
+            Status::Error,
+            format!("failed to analyze the bundled workspace: {:#}", e),
+            "Run with RUST_LOG=debug for the output of the indexer.",
+        ),
+    }
+}
+
+fn run_fixture(args: &AnalysisFlags, dir: &Path) -> anyhow::Result<Vec<String>> {
+    for (path, contents) in FIXTURE {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, contents)?;
+    }
+    let mut options = AnalysisFlags::new(dir);
+    options.indexer_cmd = args.indexer_cmd.clone();
+    options.regenerate = true;
+    // Not in the target directory, which may be shared with the index of other workspaces
+    options.scip = vec![dir.join("index.scip")];
+    options.no_cache = true;
+    let report = analyze(options)?;
+    Ok(report
+        .findings
+        .into_iter()
+        .map(|f| f.display_name)
+        .sorted()
+        .collect())
+}
+
+/// Checks of the analyzed workspace: its metadata, the permissions of the target directory, and
+/// its index if it was generated.
+fn workspace(args: &AnalysisFlags) -> Vec<Check> {
+    let workspace = &args.workspace;
+    if !workspace.join("Cargo.toml").exists() {
+        return vec![Check::failed(
+            "workspace",
+            Status::Warning,
+            format!(
+                "{:?} is not a cargo workspace, skipping its checks",
+                workspace
+            ),
+            "Run from the workspace, or pass its path.",
+        )];
+    }
+    let mut checks = vec![match metadata::load(workspace) {
+        Ok(m) => Check::ok(
+            "workspace",
+            format!(
+                "{} members in {:?}",
+                metadata::members(workspace, &m).len(),
+                workspace
+            ),
+        ),
+        Err(e) => Check::failed(
+            "workspace",
+            Status::Error,
+            format!("failed to load the cargo metadata: {}", e),
+            "Run `cargo metadata --no-deps` in the workspace for details.",
+        ),
+    }];
+
+    let dir = cache::dir(workspace);
+    let probe = dir.join(".doctor");
+    let writable = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&probe, b""));
+    let _ = std::fs::remove_file(&probe);
+    checks.push(match writable {
+        Ok(()) => Check::ok("permissions", format!("{:?} is writable", dir)),
+        Err(e) => Check::failed(
+            "permissions",
+            Status::Error,
+            format!("cannot write to {:?}: {}", dir, e),
+            "The index and caches are stored there. Fix its permissions, or set CARGO_TARGET_DIR to a writable directory.",
+        ),
+    });
+
+    if args.input != Input::Scip {
+        return checks;
+    }
+    let scips = match crate::scip_paths(args) {
+        Ok(scips) => scips,
+        Err(e) => {
+            checks.push(Check::failed("index", Status::Error, e.to_string(), ""));
+            return checks;
+        }
+    };
+    if let Some(missing) = scips.iter().find(|s| !s.exists() && !cache::is_stdin(s)) {
+        checks.push(Check::failed(
+            "index",
+            Status::Warning,
+            format!("no index at {:?}", missing),
+            "It is generated on the first run, which can take a while on large workspaces.",
+        ));
+        return checks;
+    }
+    if scips.iter().any(|s| cache::is_stdin(s)) {
+        return checks;
+    }
+    // Parse the index itself rather than its cached version
+    let options = AnalysisFlags {
+        no_cache: true,
+        ..args.clone()
+    };
+    checks.push(match crate::read_index(&options) {
+        Ok(index) => match scips
+            .iter()
+            .find_map(|s| indexer::staleness(workspace, s))
+        {
+            Some(reason) => Check::failed(
+                "index",
+                Status::Warning,
+                format!("the index may be stale: {}", reason),
+                "Run with --reindex or --refresh-stale.",
+            ),
+            None => Check::ok(
+                "index",
+                format!("{} documents in {}", index.documents.len(), {
+                    scips.iter().map(|s| format!("{:?}", s)).join(", ")
+                }),
+            ),
+        },
+        Err(e) => Check::failed(
+            "index",
+            Status::Error,
+            format!("failed to parse the index: {:#}", e),
+            "The file may be truncated or generated by an incompatible indexer. Regenerate it with --regenerate.",
+        ),
+    });
+    checks
+}
@@ -0,0 +1,35 @@
+use std::path::Path;
+
+/// Open a file at a given (one-based) line and column in `$VISUAL` or `$EDITOR`, waiting for it
+/// to exit.
+pub fn open(path: &Path, line: usize, column: usize) -> anyhow::Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .map_err(|_| anyhow::anyhow!("Neither $VISUAL nor $EDITOR is set"))?;
+    // The editor variable may contain arguments, e.g. `code --wait`.
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or_default();
+    let mut args: Vec<String> = parts.map(String::from).collect();
+    let name = Path::new(program)
+        .file_stem()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let path = path.display();
+    // Most editors accept `+line`, with different syntaxes for the column.
+    match name {
+        "code" | "codium" => {
+            args.extend(["--goto".into(), format!("{}:{}:{}", path, line, column)])
+        }
+        "hx" | "helix" | "zed" | "subl" => args.push(format!("{}:{}:{}", path, line, column)),
+        "vi" | "vim" | "nvim" => args.extend([
+            format!("+call cursor({}, {})", line, column),
+            path.to_string(),
+        ]),
+        "emacs" | "emacsclient" => args.extend([format!("+{}:{}", line, column), path.to_string()]),
+        "nano" => args.extend([format!("+{},{}", line, column), path.to_string()]),
+        _ => args.extend([format!("+{}", line), path.to_string()]),
+    }
+    let status = std::process::Command::new(program).args(&args).status()?;
+    anyhow::ensure!(status.success(), "{} exited with {}", program, status);
+    Ok(())
+}
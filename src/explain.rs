@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::bindings::{self, Language};
+use crate::config::Config;
+use crate::index::{Index, Sym};
+use crate::passes::{self, Candidates, Context};
+use crate::{metadata, AnalysisFlags, Finding, PassStats};
+
+/// Evidence for keeping a finding in a pass.
+#[derive(Serialize, Clone, Debug)]
+pub struct Explanation {
+    pub pass: String,
+    pub evidence: String,
+}
+
+/// Explain why each finding went through the passes, by symbol.
+///
+/// The search, macros and routes passes are run again for the names of the findings, without the
+/// cache so that the one of the whole analysis is kept.
+pub fn explain<'a>(
+    index: &Index,
+    args: &AnalysisFlags,
+    metadata: &metadata::Workspace,
+    findings: &[Finding<'a>],
+    passes: &[PassStats],
+) -> anyhow::Result<HashMap<&'a str, Vec<Explanation>>> {
+    let candidates: Candidates = findings
+        .iter()
+        .filter_map(|f| {
+            let sym = index.strings.get(f.symbol)?;
+            Some((sym, index.symbols.get(&sym)?))
+        })
+        .collect();
+    let mut references: HashMap<Sym, usize> = HashMap::new();
+    for o in &index.occurrences {
+        if !o.is_definition() && candidates.contains_key(&o.symbol) {
+            *references.entry(o.symbol).or_default() += 1;
+        }
+    }
+    let config = Config::load(&args.workspace)?;
+    let uncached = AnalysisFlags {
+        no_cache: true,
+        ..args.clone()
+    };
+    let ctx = Context {
+        index,
+        args: &uncached,
+        config: &config,
+        metadata,
+    };
+    let counts = if passes.iter().any(|p| p.name == "search") {
+        passes::Search::counts(&ctx, &candidates)?
+            .into_iter()
+            .map(|(name, n)| (name.to_string(), n))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let invocations = if passes.iter().any(|p| p.name == passes::MACROS_PASS) {
+        passes::ComponentMacros::counts(&ctx, &candidates)?
+    } else {
+        HashMap::new()
+    };
+    let routes = if passes.iter().any(|p| p.name == passes::ROUTES_PASS) {
+        passes::Routes::counts(&ctx, &candidates)?
+    } else {
+        HashMap::new()
+    };
+    // Lines of the exports in the sources of the other languages, by pass
+    let exports: HashMap<&str, (&Language, HashMap<Sym, usize>)> = bindings::LANGUAGES
+        .into_iter()
+        .filter(|l| passes.iter().any(|p| p.name == l.pass))
+        .map(|l| (l.pass, (l, passes::Bindings(l).counts(&ctx, &candidates))))
+        .collect();
+
+    let max_uses = args.max_uses;
+    Ok(findings
+        .iter()
+        .map(|f| {
+            let n_references = index
+                .strings
+                .get(f.symbol)
+                .and_then(|s| references.get(&s))
+                .copied()
+                .unwrap_or_default();
+            let n_lines = counts.get(f.display_name).copied().unwrap_or_default();
+            let n_invocations = invocations.get(f.display_name).copied().unwrap_or_default();
+            let n_routes = routes.get(f.display_name).copied().unwrap_or_default();
+            let explanations = passes
+                .iter()
+                .filter_map(|p| {
+                    let evidence = match p.name.as_str() {
+                        "declarations" | "packages, kinds" | "ignored" => return None,
+                        "workspace.exclude" => {
+                            "not defined in a directory excluded from the workspace".into()
+                        }
+                        "examples" => "not defined in an example".into(),
+                        "proc-macro entry points" => "not a macro of a proc-macro crate".into(),
+                        "public API" => {
+                            "not reachable from the root of a crate whose API is kept".into()
+                        }
+                        "references" if n_references > max_uses => format!(
+                            "{} references in the index, all from other findings",
+                            n_references
+                        ),
+                        "references" => format!(
+                            "{} references in the index, at most --max-uses ({})",
+                            n_references, max_uses
+                        ),
+                        "heuristics" => {
+                            "not named `main`, and no `test` or trait name in its symbol".into()
+                        }
+                        passes::MACROS_PASS => format!(
+                            "`{}` in {} invocations of the component macros, at most --max-uses ({})",
+                            f.display_name, n_invocations, max_uses
+                        ),
+                        passes::ROUTES_PASS => format!(
+                            "`{}` in {} route registrations, at most --max-uses ({})",
+                            f.display_name, n_routes, max_uses
+                        ),
+                        "search" if n_lines > 1 + max_uses => format!(
+                            "`{}` on {} lines of the searched files, the others being other findings",
+                            f.display_name, n_lines
+                        ),
+                        "search" => format!(
+                            "`{}` on {} lines of the searched files, at most 1 + --max-uses ({})",
+                            f.display_name,
+                            n_lines,
+                            1 + max_uses
+                        ),
+                        passes::STRINGS_PASS => format!(
+                            "`{}` not in the string literals more than --max-uses ({}) times",
+                            f.display_name, max_uses
+                        ),
+                        passes::DOCUMENTATION_PASS => format!(
+                            "`{}` not in the doc comments or Markdown files, besides its own doc comment, more than --max-uses ({}) times",
+                            f.display_name, max_uses
+                        ),
+                        crate::api::RUSTDOC_PASS => {
+                            "not exported according to the rustdoc JSON output".into()
+                        }
+                        "transitive" if n_references > max_uses => {
+                            "only referenced by other findings".into()
+                        }
+                        "transitive" => "unused, not added by the transitive analysis".into(),
+                        name if exports.contains_key(name) => {
+                            let (language, lines) = &exports[name];
+                            match index.strings.get(f.symbol).and_then(|s| lines.get(&s)) {
+                                Some(n) => format!(
+                                    "exported to {}, on {} lines of its sources, at most --max-uses ({})",
+                                    language.label, n, max_uses
+                                ),
+                                None => format!(
+                                    "not exported to {}, or no sources of it configured",
+                                    language.label
+                                ),
+                            }
+                        }
+                        crate::MACRO_GENERATED => {
+                            "name preceded by `fn`, not in a macro invocation".into()
+                        }
+                        "rustc dead code" => {
+                            "no dead_code warning of rustc at its definition".into()
+                        }
+                        "min lines" => format!(
+                            "definition of at least --min-lines ({}) lines, or of unknown size",
+                            args.min_lines
+                        ),
+                        "files from" => format!("defined in {}, listed in --files-from", f.path),
+                        _ => "kept by the pass".into(),
+                    };
+                    Some(Explanation {
+                        pass: p.name.clone(),
+                        evidence,
+                    })
+                })
+                .collect_vec();
+            (f.symbol, explanations)
+        })
+        .collect())
+}
@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use itertools::Itertools;
+use log::*;
+
+use crate::{find_unused, load_index, Config, Finding, FixFlags};
+
+pub fn fix(args: FixFlags) -> anyhow::Result<()> {
+    let workspace = &args.analysis.workspace;
+    let index = load_index(&args.analysis)?;
+    let config = Config::load(workspace)?;
+    let (mut findings, _) = find_unused(&index, &args.analysis, &config)?;
+    if let Some(only) = &args.only {
+        findings.retain(|f| f.fingerprint() == *only);
+        anyhow::ensure!(!findings.is_empty(), "No finding with fingerprint {}", only);
+    }
+    if !args.allow_dirty {
+        ensure_clean(workspace, &findings)?;
+    }
+    apply(workspace, &findings)
+}
+
+/// Remove the `pub` qualifier from the findings, leaving the actual deletion to the user once
+/// rustc's `dead_code` lint picks them up.
+///
+/// The findings must be sorted by path.
+pub fn apply(workspace: &Path, findings: &[Finding]) -> anyhow::Result<()> {
+    let mut n_fixed = 0;
+    for (path, findings) in &findings.iter().group_by(|f| f.path) {
+        let full_path = workspace.join(path);
+        if !full_path.exists() {
+            warn!("{} not found, is the SCIP file up-to-date?", path);
+            continue;
+        }
+        // Rewriting lossily decoded contents would corrupt the file
+        let Ok(contents) = String::from_utf8(std::fs::read(&full_path)?) else {
+            warn!("Not fixing {}, which is not valid UTF-8", path);
+            continue;
+        };
+        let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+        for f in findings {
+            match lines
+                .get(f.span.start_line)
+                .and_then(|l| remove_pub(l, f.display_name))
+            {
+                Some(fixed) => {
+                    lines[f.span.start_line] = fixed;
+                    n_fixed += 1;
+                }
+                None => warn!(
+                    "Could not find the visibility of {} at {}:{}",
+                    f.display_name,
+                    path,
+                    f.span.start_line + 1
+                ),
+            }
+        }
+        // `lines` also splits on CRLF, which is preserved
+        let newline = if contents.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let mut contents_fixed = lines.join(newline);
+        if contents.ends_with('\n') {
+            contents_fixed.push_str(newline);
+        }
+        std::fs::write(&full_path, contents_fixed)?;
+    }
+    info!(
+        "Removed the pub qualifier from {} functions. Run `cargo check` to find the ones that are now dead code.",
+        n_fixed
+    );
+    Ok(())
+}
+
+/// Refuse to modify files with uncommitted changes, like `cargo fix`.
+pub fn ensure_clean(workspace: &Path, findings: &[Finding]) -> anyhow::Result<()> {
+    let status = duct::cmd!("git", "status", "--porcelain", "--no-renames", ".")
+        .dir(workspace)
+        .stderr_null()
+        .read()
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "{:?} is not in a git repository, pass --allow-dirty to fix anyway",
+                workspace
+            )
+        })?;
+    let prefix = duct::cmd!("git", "rev-parse", "--show-prefix")
+        .dir(workspace)
+        .read()?;
+    let dirty: HashSet<&str> = status
+        .lines()
+        .filter_map(|l| l.get(3..))
+        .filter_map(|p| p.strip_prefix(prefix.as_str()))
+        .collect();
+    let dirty = findings
+        .iter()
+        .map(|f| f.path)
+        .filter(|p| dirty.contains(p))
+        .unique()
+        .collect_vec();
+    anyhow::ensure!(
+        dirty.is_empty(),
+        "The following files have uncommitted changes, pass --allow-dirty to fix anyway: {}",
+        dirty.join(", ")
+    );
+    Ok(())
+}
+
+/// Remove the visibility qualifier (e.g. `pub`, `pub(crate)`) from a line defining `name`.
+fn remove_pub(line: &str, name: &str) -> Option<String> {
+    let range = visibility_range(line, name)?;
+    Some(format!("{}{}", &line[..range.start], &line[range.end..]))
+}
+
+/// Byte range of the visibility qualifier of a line defining `name`, with the following
+/// whitespace.
+pub(crate) fn visibility_range(line: &str, name: &str) -> Option<std::ops::Range<usize>> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let def = line
+        .match_indices(&format!("fn {}", name))
+        .map(|(i, m)| i + m.len())
+        .find(|&end| !line[end..].starts_with(is_ident))?;
+    let start = line[..def]
+        .match_indices("pub")
+        .map(|(i, _)| i)
+        .filter(|&i| {
+            !line[..i].ends_with(is_ident)
+                && line[i + 3..].starts_with(|c: char| c == '(' || c.is_whitespace())
+        })
+        .last()?;
+    let mut end = start + 3;
+    if line[end..].starts_with('(') {
+        end += line[end..].find(')')? + 1;
+    }
+    end += line[end..].len() - line[end..].trim_start().len();
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn visibility_range() {
+        assert_eq!(
+            super::visibility_range("    pub fn f() {}", "f"),
+            Some(4..8)
+        );
+        assert_eq!(
+            super::visibility_range("pub(crate) fn f()", "f"),
+            Some(0..11)
+        );
+        assert_eq!(
+            super::visibility_range("pub(in crate::a)   async fn f()", "f"),
+            Some(0..19)
+        );
+        // Other functions with the name as a prefix, and words containing `pub`
+        assert_eq!(super::visibility_range("pub fn ff() {}", "f"), None);
+        assert_eq!(super::visibility_range("republish fn f() {}", "f"), None);
+        assert_eq!(super::visibility_range("fn f() {}", "f"), None);
+        assert_eq!(
+            super::visibility_range("pub fn g() {} pub fn f() {}", "f"),
+            Some(14..18)
+        );
+    }
+
+    #[test]
+    fn remove_pub() {
+        assert_eq!(
+            super::remove_pub("    pub(crate) unsafe fn f() {}", "f").as_deref(),
+            Some("    unsafe fn f() {}")
+        );
+        assert_eq!(
+            super::remove_pub("pub const fn f() -> u8 { 0 }", "f").as_deref(),
+            Some("const fn f() -> u8 { 0 }")
+        );
+        assert_eq!(super::remove_pub("pub struct f;", "f"), None);
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+use itertools::Itertools;
+use log::*;
+use scip::types::symbol_information::Kind;
+use serde::Serialize;
+
+use crate::index::{self, Index, Span, Sym};
+use crate::passes::{Candidates, Context, Heuristics, Pass};
+use crate::symbols::csv_field;
+use crate::Finding;
+
+/// References from a definition to a symbol, e.g. calls from the body of a function, or
+/// recursive calls.
+pub struct Edge {
+    pub from: Sym,
+    pub to: Sym,
+    /// Number of references.
+    pub count: usize,
+}
+
+/// Reference graph between the symbols declared in the index.
+pub struct Graph {
+    pub edges: Vec<Edge>,
+    /// Symbols with references outside of any definition.
+    pub unattributed: HashSet<Sym>,
+}
+
+impl Graph {
+    /// Attribute each reference to the innermost definition enclosing it.
+    ///
+    /// This requires the enclosing ranges of the definitions, which are only provided by recent
+    /// rust-analyzer versions in SCIP indices.
+    pub fn build(index: &Index) -> Self {
+        let mut definitions: HashMap<usize, Vec<(Span, Sym)>> = HashMap::new();
+        for o in &index.occurrences {
+            if let (true, Some(span)) = (o.is_definition(), o.enclosing_span()) {
+                if index.symbols.contains_key(&o.symbol) {
+                    definitions
+                        .entry(o.document)
+                        .or_default()
+                        .push((span, o.symbol));
+                }
+            }
+        }
+        if definitions.is_empty() {
+            warn!("The index has no definition ranges, the reference graph is empty");
+        }
+        let mut edges: HashMap<(Sym, Sym), usize> = HashMap::new();
+        let mut unattributed = HashSet::new();
+        for o in &index.occurrences {
+            if o.is_definition() || !index.symbols.contains_key(&o.symbol) {
+                continue;
+            }
+            let span = o.span();
+            let enclosing = definitions
+                .get(&o.document)
+                .into_iter()
+                .flatten()
+                .filter(|(d, _)| d.contains(span.start_line, span.start_column))
+                .max_by_key(|(d, _)| (d.start_line, d.start_column));
+            match enclosing {
+                Some((_, from)) => *edges.entry((*from, o.symbol)).or_default() += 1,
+                None => {
+                    unattributed.insert(o.symbol);
+                }
+            }
+        }
+        let edges = edges
+            .into_iter()
+            .map(|((from, to), count)| Edge { from, to, count })
+            .sorted_by(|a, b| {
+                let key = |e: &Edge| (index.str(e.from), index.str(e.to));
+                key(a).cmp(&key(b))
+            })
+            .collect();
+        Self {
+            edges,
+            unattributed,
+        }
+    }
+
+    /// Only keep the edges from or to the findings.
+    pub fn neighborhood(&mut self, index: &Index, findings: &[Finding]) {
+        let findings: HashSet<&str> = findings.iter().map(|f| f.symbol).collect();
+        self.edges
+            .retain(|e| findings.contains(index.str(e.from)) || findings.contains(index.str(e.to)));
+    }
+}
+
+/// Add the functions and methods that are only referenced by dead ones to the dead candidates,
+/// until a fixed point is reached.
+///
+/// Only the declarations passing the [`Heuristics`] are considered, and the search pass is not
+/// applied to them, as their names appear in the dead code.
+pub fn extend_dead<'a>(ctx: &Context<'a>, dead: &mut Candidates<'a>) -> anyhow::Result<()> {
+    let index = ctx.index;
+    let graph = Graph::build(index);
+    let mut eligible: Candidates = index
+        .symbols
+        .values()
+        .filter(|s| s.kind == Kind::Method || s.kind == Kind::Function)
+        .filter(|s| !dead.contains_key(&s.symbol) && !graph.unattributed.contains(&s.symbol))
+        .map(|s| (s.symbol, s))
+        .collect();
+    Heuristics.run(ctx, &mut eligible)?;
+    let mut callers: HashMap<Sym, Vec<Sym>> = HashMap::new();
+    let mut recursive: HashSet<Sym> = HashSet::new();
+    for e in &graph.edges {
+        if e.from == e.to {
+            recursive.insert(e.from);
+        } else {
+            callers.entry(e.to).or_default().push(e.from);
+        }
+    }
+    loop {
+        let newly_dead = eligible
+            .keys()
+            .filter(|s| {
+                callers
+                    .get(s)
+                    .is_some_and(|c| c.iter().all(|c| dead.contains_key(c)))
+                    // Only recursive calls
+                    || !callers.contains_key(s) && recursive.contains(s)
+            })
+            .copied()
+            .collect_vec();
+        if newly_dead.is_empty() {
+            return Ok(());
+        }
+        debug!("{} transitively dead declarations", newly_dead.len());
+        for s in newly_dead {
+            dead.insert(s, eligible.remove(&s).unwrap());
+        }
+    }
+}
+
+/// Findings referencing each other, i.e. code that can be deleted together.
+#[derive(Serialize, Debug)]
+pub struct Cluster {
+    /// SCIP symbols of the findings.
+    pub symbols: Vec<String>,
+    /// Fingerprints of the findings.
+    pub fingerprints: Vec<String>,
+    /// Total number of lines of the definitions, if known.
+    pub lines: usize,
+}
+
+/// Group the findings into clusters, connected by references, sorted by decreasing size.
+pub fn clusters(index: &Index, findings: &[Finding]) -> Vec<Cluster> {
+    let graph = Graph::build(index);
+    let position: HashMap<&str, usize> = findings
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (f.symbol, i))
+        .collect();
+    // Union-find over the findings
+    let mut parents = (0..findings.len()).collect_vec();
+    fn root(parents: &mut [usize], i: usize) -> usize {
+        let mut r = i;
+        while parents[r] != r {
+            r = parents[r];
+        }
+        parents[i] = r;
+        r
+    }
+    for e in &graph.edges {
+        if let (Some(&a), Some(&b)) = (
+            position.get(index.str(e.from)),
+            position.get(index.str(e.to)),
+        ) {
+            let (a, b) = (root(&mut parents, a), root(&mut parents, b));
+            parents[a] = b;
+        }
+    }
+    let lines: HashMap<&str, usize> = index
+        .occurrences
+        .iter()
+        .filter(|o| o.is_definition())
+        .filter_map(|o| {
+            let span = o.enclosing_span()?;
+            Some((index.str(o.symbol), span.end_line - span.start_line + 1))
+        })
+        .collect();
+    let mut clusters: HashMap<usize, Cluster> = HashMap::new();
+    for (i, f) in findings.iter().enumerate() {
+        let cluster = clusters
+            .entry(root(&mut parents, i))
+            .or_insert_with(|| Cluster {
+                symbols: vec![],
+                fingerprints: vec![],
+                lines: 0,
+            });
+        cluster.symbols.push(f.symbol.to_string());
+        cluster.fingerprints.push(f.fingerprint());
+        cluster.lines += lines.get(f.symbol).copied().unwrap_or_default();
+    }
+    clusters
+        .into_values()
+        .sorted_by(|a, b| {
+            (b.fingerprints.len(), b.lines, &a.fingerprints).cmp(&(
+                a.fingerprints.len(),
+                a.lines,
+                &b.fingerprints,
+            ))
+        })
+        .collect()
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Graphviz.
+    Dot,
+    /// Object with the nodes and edges.
+    Json,
+    /// Edge list, with a header.
+    Csv,
+}
+
+impl Format {
+    /// Format matching the extension of a path, defaulting to Graphviz.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::Json,
+            Some("csv") => Self::Csv,
+            _ => Self::Dot,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    symbol: &'a str,
+    /// Rust path of the symbol.
+    path: Option<String>,
+    finding: bool,
+}
+
+#[derive(Serialize)]
+struct JsonEdge<'a> {
+    from: &'a str,
+    to: &'a str,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct JsonGraph<'a> {
+    nodes: Vec<JsonNode<'a>>,
+    edges: Vec<JsonEdge<'a>>,
+}
+
+/// Write the graph in the given format.
+pub fn write(
+    index: &Index,
+    graph: &Graph,
+    findings: &[Finding],
+    path: &Path,
+    format: Format,
+) -> anyhow::Result<()> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    match format {
+        Format::Dot => write_dot(index, graph, findings, &mut out)?,
+        Format::Json => {
+            let findings: HashSet<&str> = findings.iter().map(|f| f.symbol).collect();
+            let graph = JsonGraph {
+                nodes: nodes(index, graph, &findings)
+                    .map(|symbol| JsonNode {
+                        symbol,
+                        path: index::rust_path(symbol),
+                        finding: findings.contains(symbol),
+                    })
+                    .collect(),
+                edges: graph
+                    .edges
+                    .iter()
+                    .map(|e| JsonEdge {
+                        from: index.str(e.from),
+                        to: index.str(e.to),
+                        count: e.count,
+                    })
+                    .collect(),
+            };
+            serde_json::to_writer_pretty(&mut out, &graph)?;
+        }
+        Format::Csv => {
+            writeln!(out, "from,to,count")?;
+            for e in &graph.edges {
+                writeln!(
+                    out,
+                    "{},{},{}",
+                    csv_field(index.str(e.from)),
+                    csv_field(index.str(e.to)),
+                    e.count
+                )?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Symbols of the edges and findings, sorted.
+fn nodes<'a>(
+    index: &'a Index,
+    graph: &Graph,
+    findings: &HashSet<&'a str>,
+) -> impl Iterator<Item = &'a str> {
+    graph
+        .edges
+        .iter()
+        .flat_map(|e| [e.from, e.to])
+        .map(|s| index.str(s))
+        .chain(findings.iter().copied())
+        .unique()
+        .sorted()
+}
+
+/// Node name, as a quoted DOT identifier.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Write the graph in the Graphviz format, with the findings highlighted.
+fn write_dot(
+    index: &Index,
+    graph: &Graph,
+    findings: &[Finding],
+    out: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let findings: HashSet<&str> = findings.iter().map(|f| f.symbol).collect();
+    writeln!(out, "digraph references {{")?;
+    writeln!(out, "  node [shape=box, fontname=monospace];")?;
+    for symbol in nodes(index, graph, &findings) {
+        let label = index::rust_path(symbol).unwrap_or_else(|| symbol.to_string());
+        let style = if findings.contains(symbol) {
+            ", style=filled, fillcolor=lightcoral"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "  {} [label={}{}];",
+            quote(symbol),
+            quote(&label),
+            style
+        )?;
+    }
+    for e in &graph.edges {
+        writeln!(
+            out,
+            "  {} -> {}{};",
+            quote(index.str(e.from)),
+            quote(index.str(e.to)),
+            if e.count > 1 {
+                format!(" [label={}]", e.count)
+            } else {
+                String::new()
+            }
+        )?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{Finding, HistoryFlags, OutputFormat};
+
+/// Tables of the history database: the runs, and their findings per crate.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    git_commit TEXT,
+    dirty INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    total INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS crates (
+    run INTEGER NOT NULL REFERENCES runs (id),
+    name TEXT NOT NULL,
+    findings INTEGER NOT NULL,
+    PRIMARY KEY (run, name)
+);
+";
+
+/// Number of findings of a `check` run, as recorded in the history database.
+#[derive(Serialize)]
+struct Run {
+    /// Commit checked out in the workspace, if it is a git repository.
+    commit: Option<String>,
+    /// Whether the working tree had uncommitted changes.
+    dirty: bool,
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+    total: usize,
+    /// Findings per crate, for the crates with any.
+    crates: BTreeMap<String, usize>,
+}
+
+/// Add the per-crate counts of the findings to a SQLite history database, creating it if needed.
+pub fn record(workspace: &Path, path: &Path, findings: &[Finding]) -> anyhow::Result<()> {
+    let git = |args: &[&str]| {
+        duct::cmd("git", args)
+            .dir(workspace)
+            .stderr_null()
+            .read()
+            .ok()
+    };
+    let mut crates: BTreeMap<String, usize> = BTreeMap::new();
+    for f in findings {
+        *crates.entry(f.package().to_string()).or_default() += 1;
+    }
+    let run = Run {
+        commit: git(&["rev-parse", "HEAD"]).map(|c| c.trim().to_string()),
+        dirty: git(&["status", "--porcelain"]).is_some_and(|s| !s.trim().is_empty()),
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+        total: findings.len(),
+        crates,
+    };
+    let path = workspace.join(path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut db = rusqlite::Connection::open(&path)?;
+    db.execute_batch(SCHEMA)?;
+    let tx = db.transaction()?;
+    tx.execute(
+        "INSERT INTO runs (git_commit, dirty, timestamp, total) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![run.commit, run.dirty, run.timestamp, run.total],
+    )?;
+    let id = tx.last_insert_rowid();
+    for (name, n) in &run.crates {
+        tx.execute(
+            "INSERT INTO crates (run, name, findings) VALUES (?1, ?2, ?3)",
+            rusqlite::params![id, name, n],
+        )?;
+    }
+    tx.commit()?;
+    log::info!("Recorded {} findings in {:?}", findings.len(), path);
+    Ok(())
+}
+
+/// Recorded runs, oldest first.
+fn runs(path: &Path) -> anyhow::Result<Vec<Run>> {
+    anyhow::ensure!(path.exists(), "No history at {:?}", path);
+    let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY;
+    let db = rusqlite::Connection::open_with_flags(path, flags)
+        .map_err(|e| anyhow::anyhow!("Failed to open the history {:?}: {}", path, e))?;
+    let mut crates: BTreeMap<i64, BTreeMap<String, usize>> = BTreeMap::new();
+    let mut query = db.prepare("SELECT run, name, findings FROM crates")?;
+    for row in query.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))? {
+        let (run, name, n) = row?;
+        crates.entry(run).or_default().insert(name, n);
+    }
+    let mut query = db.prepare(
+        "SELECT id, git_commit, dirty, timestamp, total FROM runs ORDER BY timestamp, id",
+    )?;
+    let runs = query
+        .query_map([], |r| {
+            Ok(Run {
+                commit: r.get(1)?,
+                dirty: r.get(2)?,
+                timestamp: r.get(3)?,
+                total: r.get(4)?,
+                crates: crates.remove(&r.get(0)?).unwrap_or_default(),
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(runs)
+}
+
+/// Print the recorded runs, oldest first, with the number of findings per crate.
+pub fn history(args: &HistoryFlags) -> anyhow::Result<()> {
+    let runs = runs(&args.workspace.join(&args.path))?;
+
+    let mut out = std::io::stdout().lock();
+    match args.format {
+        OutputFormat::Json => serde_json::to_writer_pretty(&mut out, &runs)?,
+        OutputFormat::Text => print(&runs, &mut out)?,
+    }
+    Ok(())
+}
+
+fn print(runs: &[Run], out: &mut dyn Write) -> anyhow::Result<()> {
+    let crates: BTreeSet<&str> = runs
+        .iter()
+        .flat_map(|r| r.crates.keys().map(String::as_str))
+        .collect();
+    write!(
+        out,
+        "{:<10} {:<10} {:>6} {:>6}",
+        "Date", "Commit", "Total", "Delta"
+    )?;
+    for c in &crates {
+        write!(out, " {:>w$}", c, w = c.len().max(4))?;
+    }
+    writeln!(out)?;
+    let mut previous = None;
+    for r in runs {
+        let commit = match &r.commit {
+            Some(c) => format!("{}{}", &c[..c.len().min(8)], if r.dirty { "+" } else { "" }),
+            None => "-".into(),
+        };
+        let delta = match previous {
+            Some(p) => format!("{:+}", r.total as i64 - p as i64),
+            None => String::new(),
+        };
+        write!(
+            out,
+            "{:<10} {:<10} {:>6} {:>6}",
+            date(r.timestamp),
+            commit,
+            r.total,
+            delta
+        )?;
+        for c in &crates {
+            let n = r.crates.get(*c).copied().unwrap_or_default();
+            write!(out, " {:>w$}", n, w = c.len().max(4))?;
+        }
+        writeln!(out)?;
+        previous = Some(r.total);
+    }
+    Ok(())
+}
+
+/// UTC date of a Unix timestamp, as `YYYY-MM-DD`.
+pub(crate) fn date(timestamp: u64) -> String {
+    // Days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = (timestamp / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn date() {
+        assert_eq!(super::date(0), "1970-01-01");
+        assert_eq!(super::date(86399), "1970-01-01");
+        assert_eq!(super::date(86400), "1970-01-02");
+        // Leap days, including the one of a year divisible by 400
+        assert_eq!(super::date(951782400), "2000-02-29");
+        assert_eq!(super::date(1709251199), "2024-02-29");
+        assert_eq!(super::date(1709251200), "2024-03-01");
+        assert_eq!(super::date(1791988132), "2026-10-14");
+        assert_eq!(super::date(4102444800), "2100-01-01");
+    }
+}
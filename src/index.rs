@@ -0,0 +1,646 @@
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+
+use scip::types::{symbol_information::Kind, Document, Metadata, PositionEncoding, SymbolRole};
+use serde::{Deserialize, Serialize};
+
+/// Workspace-wide data extracted from a code index, independent of its format.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Index {
+    /// URI of the workspace root at indexing time.
+    pub project_root: String,
+    /// Paths of the documents, relative to the workspace root.
+    pub documents: Vec<String>,
+    /// Symbols declared in the documents, by symbol.
+    pub symbols: HashMap<Sym, Symbol>,
+    /// Occurrences of global symbols.
+    pub occurrences: Vec<Occurrence>,
+    /// Symbols and display names, which are repeated across symbols and occurrences.
+    pub strings: Interner,
+    /// Units of the columns of the documents whose ranges are not in UTF-8 bytes, by index into
+    /// [`Index::documents`], until [`Index::normalize_columns`] converts them.
+    #[serde(default)]
+    pub encodings: HashMap<usize, Encoding>,
+    /// Trait items implemented by each symbol, from the `is_implementation` relationships of the
+    /// indexers providing them.
+    #[serde(default)]
+    pub implementations: HashMap<Sym, Vec<Sym>>,
+}
+
+/// Unit of the columns in the ranges of a document.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Encoding {
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+impl Encoding {
+    fn units(self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+
+    /// Byte offset of a column in a line, clamped to the end of the line.
+    pub fn byte_offset(self, line: &str, column: usize) -> usize {
+        let mut units = 0;
+        for (i, c) in line.char_indices() {
+            if units >= column {
+                return i;
+            }
+            units += self.units(c);
+        }
+        line.len()
+    }
+
+    /// Column of a byte offset in a line, e.g. in characters for [`Encoding::Utf32`]. Offsets
+    /// past the end of the line or inside a character are returned as is.
+    pub fn column(self, line: &str, offset: usize) -> usize {
+        line.get(..offset)
+            .map_or(offset, |prefix| prefix.chars().map(|c| self.units(c)).sum())
+    }
+}
+
+/// Identifier of an interned string.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct Sym(u32);
+
+#[derive(Default, Deserialize)]
+#[serde(from = "Vec<Arc<str>>")]
+pub struct Interner {
+    strings: Vec<Arc<str>>,
+    ids: HashMap<Arc<str>, Sym>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, s: &str) -> Sym {
+        if let Some(id) = self.ids.get(s) {
+            return *id;
+        }
+        let id = Sym(self.strings.len() as u32);
+        let s: Arc<str> = s.into();
+        self.strings.push(s.clone());
+        self.ids.insert(s, id);
+        id
+    }
+
+    /// Identifier of a string, if it was interned.
+    pub fn get(&self, s: &str) -> Option<Sym> {
+        self.ids.get(s).copied()
+    }
+
+    pub fn resolve(&self, id: Sym) -> &str {
+        &self.strings[id.0 as usize]
+    }
+}
+
+impl From<Vec<Arc<str>>> for Interner {
+    fn from(strings: Vec<Arc<str>>) -> Self {
+        let ids = strings
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.clone(), Sym(i as u32)))
+            .collect();
+        Self { strings, ids }
+    }
+}
+
+/// Only the strings are serialized, the reverse mapping is rebuilt on deserialization.
+impl Serialize for Interner {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.strings.serialize(s)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Symbol {
+    pub symbol: Sym,
+    pub display_name: Sym,
+    #[serde(with = "kind_serde")]
+    pub kind: Kind,
+    /// Path of the signature documentation, if any.
+    pub signature_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Occurrence {
+    pub symbol: Sym,
+    /// Index into [`Index::documents`].
+    pub document: usize,
+    /// Either `[start line, start column, end line, end column]` or
+    /// `[line, start column, end column]`, as in SCIP.
+    pub range: Vec<i32>,
+    pub roles: i32,
+    /// Range of the whole definition, e.g. including the body of a function, in the same format.
+    /// Only kept for definitions, and empty if the indexer does not provide it.
+    pub enclosing_range: Vec<i32>,
+}
+
+/// Zero-based position of an occurrence.
+#[derive(Clone, Copy, Default, Debug, Serialize)]
+pub struct Span {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Occurrence {
+    pub fn is_definition(&self) -> bool {
+        (self.roles & SymbolRole::Definition as i32) > 0
+    }
+
+    pub fn span(&self) -> Span {
+        to_span(&self.range)
+    }
+
+    /// Span of the whole definition, if known.
+    pub fn enclosing_span(&self) -> Option<Span> {
+        (!self.enclosing_range.is_empty()).then(|| to_span(&self.enclosing_range))
+    }
+}
+
+impl Span {
+    /// Whether a position is inside the span.
+    pub fn contains(&self, line: usize, column: usize) -> bool {
+        (self.start_line, self.start_column) <= (line, column)
+            && (line, column) < (self.end_line, self.end_column)
+    }
+}
+
+/// Convert the columns of a range to UTF-8 bytes, with bound checks as the document may have
+/// changed since the indexing.
+fn convert_range(range: &mut [i32], lines: &[String], encoding: Encoding) {
+    let convert = |line: i32, column: i32| {
+        let Some(text) = usize::try_from(line).ok().and_then(|l| lines.get(l)) else {
+            return column;
+        };
+        encoding.byte_offset(text, column.max(0) as usize) as i32
+    };
+    match range {
+        [line, start, end] => {
+            *start = convert(*line, *start);
+            *end = convert(*line, *end);
+        }
+        [start_line, start, end_line, end, ..] => {
+            *start = convert(*start_line, *start);
+            *end = convert(*end_line, *end);
+        }
+        _ => {}
+    }
+}
+
+fn to_span(range: &[i32]) -> Span {
+    let range = range.iter().map(|&i| i as usize).collect::<Vec<_>>();
+    match range[..] {
+        [line, start_column, end_column] => Span {
+            start_line: line,
+            start_column,
+            end_line: line,
+            end_column,
+        },
+        [start_line, start_column, end_line, end_column, ..] => Span {
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+        },
+        _ => Span::default(),
+    }
+}
+
+impl Index {
+    /// Resolve an interned symbol or display name.
+    pub fn str(&self, id: Sym) -> &str {
+        self.strings.resolve(id)
+    }
+
+    /// Add the documents of another index, re-interning its strings. Documents that are already
+    /// present are skipped.
+    ///
+    /// If the other index was generated in another directory than this one's project root, e.g. a
+    /// subdirectory, its document paths are made relative to the project root.
+    pub fn merge(&mut self, other: Index) {
+        let prefix = relative_dir(&self.project_root, &other.project_root)
+            .map(|p| format!("{}/", p))
+            .unwrap_or_default();
+        let mut existing: HashSet<String> = self.documents.iter().cloned().collect();
+        let mut encodings = other.encodings;
+        let ids = other
+            .documents
+            .into_iter()
+            .map(|d| {
+                let d = format!("{}{}", prefix, d);
+                existing.insert(d.clone()).then(|| {
+                    self.documents.push(d);
+                    self.documents.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+        for (i, id) in ids.iter().enumerate() {
+            if let (Some(id), Some(encoding)) = (id, encodings.remove(&i)) {
+                self.encodings.insert(*id, encoding);
+            }
+        }
+        for o in other.occurrences {
+            let Some(document) = ids[o.document] else {
+                continue;
+            };
+            self.occurrences.push(Occurrence {
+                symbol: self.strings.intern(other.strings.resolve(o.symbol)),
+                document,
+                ..o
+            });
+        }
+        for s in other.symbols.into_values() {
+            let symbol = self.strings.intern(other.strings.resolve(s.symbol));
+            let symbol = Symbol {
+                symbol,
+                display_name: self.strings.intern(other.strings.resolve(s.display_name)),
+                ..s
+            };
+            self.symbols.entry(symbol.symbol).or_insert(symbol);
+        }
+        for (s, implemented) in other.implementations {
+            let s = self.strings.intern(other.strings.resolve(s));
+            let implemented = implemented
+                .into_iter()
+                .map(|i| self.strings.intern(other.strings.resolve(i)))
+                .collect();
+            self.implementations.entry(s).or_insert(implemented);
+        }
+    }
+
+    /// Convert the columns of the documents in other units than UTF-8 bytes, e.g. UTF-16 code
+    /// units as in LSP, using their current contents in the workspace. The columns of the
+    /// documents that cannot be read are left as is.
+    pub fn normalize_columns(&mut self, workspace: &Path) {
+        if self.encodings.is_empty() {
+            return;
+        }
+        let mut lines: HashMap<usize, Option<Vec<String>>> = HashMap::new();
+        for o in &mut self.occurrences {
+            let Some(encoding) = self.encodings.get(&o.document).copied() else {
+                continue;
+            };
+            let documents = &self.documents;
+            let Some(lines) = lines
+                .entry(o.document)
+                .or_insert_with(|| {
+                    let contents = crate::read_source(&workspace.join(&documents[o.document]));
+                    Some(contents.ok()?.lines().map(String::from).collect())
+                })
+                .as_ref()
+            else {
+                continue;
+            };
+            for range in [&mut o.range, &mut o.enclosing_range] {
+                convert_range(range, lines, encoding);
+            }
+        }
+        self.encodings.clear();
+    }
+
+    /// Rewrite the document paths under the `from` directory of a mapping to the `to` directory,
+    /// relative to the workspace root. `from` is matched against the absolute path at indexing
+    /// time if it is absolute, and against the relative path otherwise.
+    pub fn remap_paths(&mut self, maps: &[(String, String)]) {
+        let root = uri_path(&self.project_root);
+        let root = root.trim_end_matches('/');
+        for doc in &mut self.documents {
+            let absolute = format!("{}/{}", root, doc);
+            let remapped = maps.iter().find_map(|(from, to)| {
+                let from = from.replace('\\', "/");
+                let path = if is_absolute(&from) { &absolute } else { &*doc };
+                Some(join(to, strip_dir(path, &from)?))
+            });
+            if let Some(remapped) = remapped {
+                *doc = remapped;
+            }
+        }
+    }
+
+    /// Parse a SCIP index, streaming its documents so that they never all reside in memory.
+    pub fn read_scip(reader: &mut dyn BufRead) -> anyhow::Result<Self> {
+        let mut index = Self::default();
+        let mut is = protobuf::CodedInputStream::from_buf_read(reader);
+        // Fields of the top-level `scip.Index` message
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                // metadata = 1
+                10 => {
+                    let metadata: Metadata = is.read_message()?;
+                    index.project_root = metadata.project_root;
+                }
+                // documents = 2
+                18 => index.add_document(is.read_message()?),
+                // external_symbols = 3, which are not defined in the workspace
+                tag => protobuf::rt::skip_field_for_tag(tag, &mut is)?,
+            }
+        }
+        Ok(index)
+    }
+
+    fn add_document(&mut self, doc: Document) {
+        let document = self.documents.len();
+        self.documents.push(doc.relative_path);
+        // Unspecified in indices predating the field, in which rust-analyzer and scip-rust
+        // already used UTF-8 bytes
+        match doc.position_encoding.enum_value() {
+            Ok(PositionEncoding::UTF16CodeUnitOffsetFromLineStart) => {
+                self.encodings.insert(document, Encoding::Utf16);
+            }
+            Ok(PositionEncoding::UTF32CodeUnitOffsetFromLineStart) => {
+                self.encodings.insert(document, Encoding::Utf32);
+            }
+            _ => {}
+        }
+        // Local symbols are only unique within a document, and never part of an API.
+        for s in doc.symbols {
+            let Ok(kind) = s.kind.enum_value() else {
+                continue;
+            };
+            if scip::symbol::is_local_symbol(&s.symbol) {
+                continue;
+            }
+            let implemented = s
+                .relationships
+                .iter()
+                .filter(|r| r.is_implementation)
+                .map(|r| self.strings.intern(&r.symbol))
+                .collect::<Vec<_>>();
+            let symbol = Symbol {
+                symbol: self.strings.intern(&s.symbol),
+                display_name: self.strings.intern(&s.display_name),
+                kind,
+                signature_path: s
+                    .signature_documentation
+                    .into_option()
+                    .map(|d| d.relative_path),
+            };
+            if !implemented.is_empty() {
+                self.implementations.insert(symbol.symbol, implemented);
+            }
+            self.symbols.insert(symbol.symbol, symbol);
+        }
+        self.occurrences.extend(
+            doc.occurrences
+                .into_iter()
+                .filter(|o| !scip::symbol::is_local_symbol(&o.symbol))
+                .map(|o| Occurrence {
+                    symbol: self.strings.intern(&o.symbol),
+                    document,
+                    range: o.range,
+                    roles: o.symbol_roles,
+                    enclosing_range: if o.symbol_roles & SymbolRole::Definition as i32 > 0 {
+                        o.enclosing_range
+                    } else {
+                        vec![]
+                    },
+                }),
+        );
+    }
+}
+
+/// Rust path of a SCIP symbol, e.g. `a::S::unused_method` for
+/// `rust-analyzer cargo a 0.1.0 impl#[S]unused_method().`.
+///
+/// Methods are named after the implementing type, without the trait.
+pub fn rust_path(symbol: &str) -> Option<String> {
+    use scip::types::descriptor::Suffix;
+    let symbol = scip::symbol::parse_symbol(symbol).ok()?;
+    let mut path = vec![symbol.package.name.replace('-', "_")];
+    // The `impl` descriptor is followed by the type and the trait, as type parameters
+    let mut self_type = false;
+    for d in &symbol.descriptors {
+        match d.suffix.enum_value() {
+            Ok(Suffix::Type) if d.name == "impl" => self_type = true,
+            Ok(Suffix::TypeParameter) if self_type => {
+                path.push(d.name.clone());
+                self_type = false;
+            }
+            Ok(Suffix::TypeParameter | Suffix::Parameter | Suffix::Meta) => {}
+            // Root module
+            Ok(Suffix::Namespace) if d.name == "crate" => {}
+            _ => path.push(d.name.clone()),
+        }
+    }
+    Some(path.join("::"))
+}
+
+/// Path of the `to` project root relative to the `from` one, e.g. `../other` for sibling
+/// directories, if they differ.
+fn relative_dir(from: &str, to: &str) -> Option<String> {
+    let (from, to) = (uri_path(from), uri_path(to));
+    let (from, to) = (from.trim_end_matches('/'), to.trim_end_matches('/'));
+    if from.is_empty() || to.is_empty() || from == to {
+        return None;
+    }
+    let from: Vec<&str> = from.split('/').collect();
+    let to: Vec<&str> = to.split('/').collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let parts = std::iter::repeat_n("..", from.len() - common)
+        .chain(to[common..].iter().copied())
+        .collect::<Vec<_>>();
+    Some(parts.join("/"))
+}
+
+/// Path with `/` separators, as in the index documents, also on Windows.
+pub fn slash_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// `file://` URI of an absolute directory, e.g. `file:///C:/src` for a canonicalized `\\?\C:\src`.
+pub fn dir_uri(dir: &Path) -> String {
+    let path = slash_path(dir);
+    let path = path.strip_prefix("//?/").unwrap_or(&path);
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
+
+/// Path of a `file://` URI, e.g. `C:/src` for `file:///c%3A/src`, as written by Windows
+/// indexers.
+pub fn uri_path(uri: &str) -> String {
+    let path = uri.trim_start_matches("file://");
+    // Percent-decoding, which only concerns a few ASCII characters in practice
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let decoded = (b == b'%')
+            .then(|| std::str::from_utf8(tail.get(..2)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(d) => {
+                bytes.push(d);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    let path = String::from_utf8_lossy(&bytes).replace('\\', "/");
+    match path.strip_prefix('/') {
+        Some(rest) if has_drive(rest) => rest.to_string(),
+        _ => path,
+    }
+}
+
+/// Whether a path starts with a Windows drive letter, e.g. `C:/`.
+fn has_drive(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Whether a path with `/` separators is absolute, on Unix or Windows.
+fn is_absolute(path: &str) -> bool {
+    path.starts_with('/') || has_drive(path)
+}
+
+/// Path relative to a directory, if it is inside it.
+fn strip_dir<'a>(path: &'a str, dir: &str) -> Option<&'a str> {
+    let dir = dir.trim_end_matches('/');
+    match path.strip_prefix(dir)? {
+        "" => Some(""),
+        rest => rest.strip_prefix('/'),
+    }
+}
+
+fn join(dir: &str, path: &str) -> String {
+    let dir = dir.trim_start_matches("./").trim_end_matches('/');
+    match (dir, path) {
+        ("." | "", path) => path.to_string(),
+        (dir, "") => dir.to_string(),
+        (dir, path) => format!("{}/{}", dir, path),
+    }
+}
+
+/// Serialize SCIP kinds as their protobuf value.
+mod kind_serde {
+    use protobuf::Enum;
+    use scip::types::symbol_information::Kind;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(kind: &Kind, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_i32(kind.value())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Kind, D::Error> {
+        let value = i32::deserialize(d)?;
+        Kind::from_i32(value)
+            .ok_or_else(|| serde::de::Error::custom(format!("Unknown symbol kind {}", value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_offset() {
+        // `é` is 2 bytes and 1 UTF-16 unit, `𝕏` 4 bytes and 2 UTF-16 units
+        let line = "é𝕏 fn f";
+        assert_eq!(Encoding::Utf8.byte_offset(line, 2), 2);
+        assert_eq!(Encoding::Utf16.byte_offset(line, 1), 2);
+        assert_eq!(Encoding::Utf16.byte_offset(line, 3), 6);
+        assert_eq!(Encoding::Utf32.byte_offset(line, 2), 6);
+        assert_eq!(Encoding::Utf32.byte_offset(line, 7), 11);
+        // Clamped to the end of the line
+        assert_eq!(Encoding::Utf16.byte_offset(line, 100), line.len());
+    }
+
+    #[test]
+    fn column() {
+        let line = "é𝕏 fn f";
+        assert_eq!(Encoding::Utf8.column(line, 6), 6);
+        assert_eq!(Encoding::Utf16.column(line, 6), 3);
+        assert_eq!(Encoding::Utf32.column(line, 6), 2);
+        assert_eq!(Encoding::Utf32.column(line, 10), 6);
+        // Inside a character, or past the end of the line
+        assert_eq!(Encoding::Utf32.column(line, 1), 1);
+        assert_eq!(Encoding::Utf32.column(line, 100), 100);
+        for encoding in [Encoding::Utf8, Encoding::Utf16, Encoding::Utf32] {
+            for offset in [0, 2, 6, 7, line.len()] {
+                let column = encoding.column(line, offset);
+                assert_eq!(encoding.byte_offset(line, column), offset);
+            }
+        }
+    }
+
+    #[test]
+    fn convert_range() {
+        let lines = ["let é = 1;".to_string(), "/* 𝕏 */ fn f() {}".to_string()];
+        // The name `f` of the function
+        let mut range = vec![1, 12, 13];
+        super::convert_range(&mut range, &lines, Encoding::Utf16);
+        assert_eq!(range, [1, 14, 15]);
+        // From `é` to the end of the name, with a fifth element left as is
+        let mut range = vec![0, 4, 1, 12, 0];
+        super::convert_range(&mut range, &lines, Encoding::Utf32);
+        assert_eq!(range, [0, 4, 1, 15, 0]);
+        // Lines missing from the document are left as is
+        let mut range = vec![5, 1, 2];
+        super::convert_range(&mut range, &lines, Encoding::Utf16);
+        assert_eq!(range, [5, 1, 2]);
+    }
+
+    #[test]
+    fn uri_path() {
+        assert_eq!(super::uri_path("file:///home/user/src"), "/home/user/src");
+        assert_eq!(super::uri_path("file:///c%3A/src"), "c:/src");
+        assert_eq!(super::uri_path("file:///C:/src/a%20b"), "C:/src/a b");
+        assert_eq!(super::uri_path("file:///C:\\src\\lib"), "C:/src/lib");
+        // Invalid escapes are kept
+        assert_eq!(super::uri_path("file:///src/100%"), "/src/100%");
+        assert_eq!(super::uri_path("file:///src/%zz"), "/src/%zz");
+    }
+
+    #[test]
+    fn relative_dir() {
+        let dir = |from, to| super::relative_dir(from, to);
+        assert_eq!(dir("file:///src/ws", "file:///src/ws/"), None);
+        assert_eq!(dir("file:///src/ws", ""), None);
+        assert_eq!(
+            dir("file:///src/ws", "file:///src/ws/crates/a"),
+            Some("crates/a".into())
+        );
+        assert_eq!(
+            dir("file:///src/ws", "file:///src/other"),
+            Some("../other".into())
+        );
+        assert_eq!(dir("file:///src/ws/a", "file:///src"), Some("../..".into()));
+        assert_eq!(dir("file:///c%3A/ws", "file:///c:/ws/a"), Some("a".into()));
+    }
+
+    #[test]
+    fn strip_dir() {
+        assert_eq!(super::strip_dir("src/lib.rs", "src"), Some("lib.rs"));
+        assert_eq!(super::strip_dir("src/lib.rs", "src/"), Some("lib.rs"));
+        assert_eq!(super::strip_dir("src", "src"), Some(""));
+        // Only whole components
+        assert_eq!(super::strip_dir("srcs/lib.rs", "src"), None);
+        assert_eq!(super::strip_dir("lib.rs", "src"), None);
+    }
+
+    #[test]
+    fn join() {
+        assert_eq!(super::join("crates/a", "src/lib.rs"), "crates/a/src/lib.rs");
+        assert_eq!(
+            super::join("./crates/a/", "src/lib.rs"),
+            "crates/a/src/lib.rs"
+        );
+        assert_eq!(super::join(".", "src/lib.rs"), "src/lib.rs");
+        assert_eq!(super::join("", "src/lib.rs"), "src/lib.rs");
+        assert_eq!(super::join("crates/a", ""), "crates/a");
+    }
+}
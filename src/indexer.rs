@@ -0,0 +1,578 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use log::*;
+use protobuf::Message;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::metadata::{self, Member};
+use crate::{cache, timings, AnalysisFlags};
+
+/// Fingerprints of the workspace members at the time the index was generated.
+#[derive(Serialize, Deserialize, Default)]
+struct State {
+    scip: PathBuf,
+    members: HashMap<String, u64>,
+    /// Git commit checked out at the time.
+    #[serde(default)]
+    head: Option<String>,
+    /// Cargo feature flags passed to the indexer.
+    #[serde(default)]
+    features: Vec<String>,
+}
+
+fn state_path(workspace: &Path) -> PathBuf {
+    cache::dir(workspace).join("index-state.json")
+}
+
+/// Number of lines of the indexer output included in errors.
+const OUTPUT_TAIL: usize = 20;
+
+/// The indexer command, to which the directory and `--output <path>` are appended.
+fn indexer_cmd(args: &AnalysisFlags) -> anyhow::Result<Vec<String>> {
+    let cmd = match &args.indexer_cmd {
+        Some(cmd) => cmd.clone(),
+        None => Config::load(&args.workspace)?
+            .indexer_cmd
+            .unwrap_or_else(|| DEFAULT_INDEXER_CMD.into()),
+    };
+    let cmd = cmd.split_whitespace().map(String::from).collect_vec();
+    anyhow::ensure!(!cmd.is_empty(), "The indexer command is empty");
+    Ok(cmd)
+}
+
+const DEFAULT_INDEXER_CMD: &str = "rust-analyzer scip";
+
+/// Oldest supported rust-analyzer release, by date.
+const MIN_RUST_ANALYZER_DATE: &str = "2023-01-02";
+
+const INSTALL_HINT: &str =
+    "Install it with `rustup component add rust-analyzer`, or pass another indexer with --indexer-cmd.";
+
+/// Whether a program can be found, either as a path or in `PATH`.
+fn is_available(program: &str) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| {
+            dir.join(program).is_file() || dir.join(format!("{}.exe", program)).is_file()
+        })
+    })
+}
+
+/// Release date in the output of `rust-analyzer --version`, e.g.
+/// `rust-analyzer 1.83.0 (90b35a6 2024-11-26)`.
+fn release_date(version: &str) -> Option<&str> {
+    version
+        .split_whitespace()
+        .map(|t| t.trim_matches(|c| c == '(' || c == ')'))
+        .find(|t| {
+            t.len() == 10
+                && t.char_indices().all(|(i, c)| {
+                    if i == 4 || i == 7 {
+                        c == '-'
+                    } else {
+                        c.is_ascii_digit()
+                    }
+                })
+        })
+}
+
+/// Check that the indexer can be run before generating the index, and for rust-analyzer that it
+/// is recent enough and supports the `scip` subcommand, returning its version.
+pub(crate) fn preflight(args: &AnalysisFlags) -> anyhow::Result<Option<String>> {
+    let cmd = indexer_cmd(args)?;
+    let program = &cmd[0];
+    let is_rust_analyzer = Path::new(program)
+        .file_stem()
+        .is_some_and(|s| s == "rust-analyzer");
+    if !is_available(program) {
+        anyhow::bail!(
+            "The indexer {:?} was not found.{}",
+            program,
+            if is_rust_analyzer {
+                format!(" {}", INSTALL_HINT)
+            } else {
+                String::new()
+            }
+        );
+    }
+    if !is_rust_analyzer || cmd.get(1).is_none_or(|c| c != "scip") {
+        return Ok(None);
+    }
+    let version = duct::cmd!(program, "--version")
+        .stderr_to_stdout()
+        .read()
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to run `{} --version`: {}. {}",
+                program,
+                e,
+                INSTALL_HINT
+            )
+        })?;
+    debug!("Using {}", version.trim());
+    if let Some(date) = release_date(&version).filter(|d| *d < MIN_RUST_ANALYZER_DATE) {
+        anyhow::bail!(
+            "{} was released on {}, but a release from {} or later is required. Update it with `rustup update`, or pass another indexer with --indexer-cmd.",
+            version.trim(),
+            date,
+            MIN_RUST_ANALYZER_DATE
+        );
+    }
+    let help = duct::cmd!(program, "--help")
+        .stderr_to_stdout()
+        .unchecked()
+        .read()?;
+    anyhow::ensure!(
+        help.contains("rust-analyzer scip"),
+        "{} does not support the scip subcommand. {}",
+        version.trim(),
+        INSTALL_HINT
+    );
+    Ok(Some(version.trim().to_string()))
+}
+
+/// Cargo feature flags requested for the index generation, as passed to cargo.
+fn feature_flags(args: &AnalysisFlags) -> Vec<String> {
+    let mut flags = vec![];
+    if !args.features.is_empty() {
+        flags.push(format!("--features={}", args.features.join(",")));
+    }
+    if args.all_features {
+        flags.push("--all-features".into());
+    }
+    if args.no_default_features {
+        flags.push("--no-default-features".into());
+    }
+    flags
+}
+
+/// rust-analyzer configuration selecting the requested features, if any.
+fn indexer_config(args: &AnalysisFlags) -> Option<serde_json::Value> {
+    if feature_flags(args).is_empty() {
+        return None;
+    }
+    let features = if args.all_features {
+        serde_json::json!("all")
+    } else {
+        serde_json::json!(args.features)
+    };
+    Some(serde_json::json!({
+        "cargo": {
+            "features": features,
+            "noDefaultFeatures": args.no_default_features,
+        }
+    }))
+}
+
+/// Cargo feature flags with which the index was generated, if any.
+pub fn recorded_features(workspace: &Path, scip: &Path) -> Option<String> {
+    let features = load_state(workspace, scip)?.features;
+    (!features.is_empty()).then(|| features.join(" "))
+}
+
+fn run_indexer(args: &AnalysisFlags, dir: &Path, output: &Path) -> anyhow::Result<()> {
+    let cmd_line = indexer_cmd(args)?;
+    let mut cmd_args: Vec<std::ffi::OsString> = cmd_line[1..].iter().map(Into::into).collect();
+    cmd_args.extend([dir.into(), "--output".into(), output.into()]);
+    // The features are set through the configuration file, named after the output to allow
+    // concurrent invocations.
+    let config_path = output.with_extension("json");
+    if let Some(config) = indexer_config(args) {
+        std::fs::write(&config_path, config.to_string())?;
+        cmd_args.extend(["--config-path".into(), config_path.clone().into()]);
+    }
+    let res = run_cmd(args, &cmd_line, cmd_args, dir);
+    let _ = std::fs::remove_file(&config_path);
+    res
+}
+
+fn run_cmd(
+    args: &AnalysisFlags,
+    cmd_line: &[String],
+    cmd_args: Vec<std::ffi::OsString>,
+    dir: &Path,
+) -> anyhow::Result<()> {
+    let mut cmd = duct::cmd(&cmd_line[0], cmd_args).dir(dir);
+    if let Some(jobs) = args.jobs {
+        // rust-analyzer has no flag for its thread pools, but runs `cargo check` for build
+        // scripts and proc macros.
+        cmd = cmd
+            .env("CARGO_BUILD_JOBS", jobs.to_string())
+            .env("RAYON_NUM_THREADS", jobs.to_string());
+    }
+    // Progress is reported on stderr, and the last lines are kept for the error message.
+    let reader = cmd.stderr_to_stdout().unchecked().reader()?;
+    let mut tail = std::collections::VecDeque::with_capacity(OUTPUT_TAIL);
+    for line in std::io::BufReader::new(&reader).lines() {
+        let line = line?;
+        if line.starts_with("rust-analyzer: ") || line.starts_with("Generating SCIP") {
+            info!("[{}] {}", dir.display(), line);
+        } else {
+            debug!("[{}] {}", dir.display(), line);
+        }
+        if tail.len() == OUTPUT_TAIL {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+    let status = reader.try_wait()?.map(|o| o.status);
+    if let Some(status) = status.filter(|s| !s.success()) {
+        anyhow::bail!(
+            "{} failed to index {:?} ({}):\n{}",
+            cmd_line.join(" "),
+            dir,
+            status,
+            tail.iter().join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Hash of the paths, sizes and modification times of the files of each member.
+fn fingerprints(workspace: &Path, members: &[Member]) -> HashMap<String, u64> {
+    let mut hashers: HashMap<&str, xxhash_rust::xxh3::Xxh3> = members
+        .iter()
+        .map(|m| (m.name.as_str(), Default::default()))
+        .collect();
+    for f in walkdir::WalkDir::new(workspace)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git" && !e.path().join("CACHEDIR.TAG").exists())
+        .filter_map(|e| e.ok())
+        .filter(|f| f.file_type().is_file())
+    {
+        let Ok(path) = f.path().strip_prefix(workspace) else {
+            continue;
+        };
+        let (Some(member), Ok(metadata)) = (metadata::owner(members, path), f.metadata()) else {
+            continue;
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        let hasher = hashers.get_mut(member.name.as_str()).unwrap();
+        hasher.update(path.as_os_str().as_encoded_bytes());
+        hasher.update(&metadata.len().to_le_bytes());
+        hasher.update(&modified.as_nanos().to_le_bytes());
+    }
+    hashers
+        .into_iter()
+        .map(|(name, h)| (name.to_string(), h.digest()))
+        .collect()
+}
+
+fn git_head(workspace: &Path) -> Option<String> {
+    let head = duct::cmd!("git", "rev-parse", "HEAD")
+        .dir(workspace)
+        .stderr_null()
+        .read()
+        .ok()?;
+    Some(head.trim().to_string())
+}
+
+fn load_state(workspace: &Path, scip: &Path) -> Option<State> {
+    std::fs::read_to_string(state_path(workspace))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .filter(|s: &State| s.scip == scip)
+}
+
+/// Reason for which the index may be stale: a Rust file modified after it, or a different git
+/// commit checked out than when it was generated.
+pub fn staleness(workspace: &Path, scip: &Path) -> Option<String> {
+    let generated = std::fs::metadata(scip).and_then(|m| m.modified()).ok()?;
+    let newer = walkdir::WalkDir::new(workspace)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git" && !e.path().join("CACHEDIR.TAG").exists())
+        .filter_map(|e| e.ok())
+        .filter(|f| f.file_type().is_file() && f.path().extension().is_some_and(|e| e == "rs"))
+        .find(|f| {
+            f.metadata()
+                .ok()
+                .and_then(|m| m.modified().ok())
+                .is_some_and(|m| m > generated)
+        });
+    if let Some(f) = newer {
+        return Some(format!(
+            "{:?} was modified after the index was generated",
+            f.path().strip_prefix(workspace).unwrap_or(f.path())
+        ));
+    }
+    let recorded = load_state(workspace, scip)?.head?;
+    let head = git_head(workspace)?;
+    (recorded != head).then(|| {
+        format!(
+            "the index was generated at commit {}, but {} is checked out",
+            recorded, head
+        )
+    })
+}
+
+fn save_state(args: &AnalysisFlags, scip: &Path, members: &[Member]) -> anyhow::Result<()> {
+    let workspace = &args.workspace;
+    let state = State {
+        scip: scip.to_path_buf(),
+        members: fingerprints(workspace, members),
+        head: git_head(workspace),
+        features: feature_flags(args),
+    };
+    let path = state_path(workspace);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(path, serde_json::to_string(&state)?)?;
+    Ok(())
+}
+
+fn read_scip(path: &Path) -> anyhow::Result<scip::types::Index> {
+    Ok(scip::types::Index::parse_from_reader(&mut cache::open(
+        path,
+    )?)?)
+}
+
+/// Write an index, compressed with gzip or zstd if its extension is `.gz` or `.zst`, as read by
+/// [`cache::open`].
+fn write_scip(index: &scip::types::Index, path: &Path) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let file = std::fs::File::create(&tmp)
+        .map_err(|e| anyhow::anyhow!("Failed to write {:?}: {}", path, e))?;
+    let mut file = std::io::BufWriter::new(file);
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => {
+            let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            index.write_to_writer(&mut encoder)?;
+            encoder.finish()?.flush()?;
+        }
+        Some("zst") => {
+            let mut encoder = zstd::Encoder::new(file, 0)?;
+            index.write_to_writer(&mut encoder)?;
+            encoder.finish()?.flush()?;
+        }
+        _ => {
+            index.write_to_writer(&mut file)?;
+            file.flush()?;
+        }
+    }
+    std::fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Whether an index is written compressed by [`write_scip`].
+fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "gz" || e == "zst")
+}
+
+/// Index the directory of a member, with the document paths made relative to the workspace
+/// root, keeping only the documents owned by the members named in `keep`: the index of a
+/// directory also covers the members nested in it.
+fn index_dir(
+    args: &AnalysisFlags,
+    dir: &Path,
+    members: &[Member],
+    keep: &[&str],
+) -> anyhow::Result<scip::types::Index> {
+    let cache = cache::dir(&args.workspace);
+    std::fs::create_dir_all(&cache)?;
+    let prefix = crate::index::slash_path(dir);
+    let output = cache.join(format!("index-{}.scip", prefix.replace('/', "-")));
+    run_indexer(args, &args.workspace.join(dir), &output)?;
+    let mut index = read_scip(&output)?;
+    std::fs::remove_file(&output)?;
+    // The member was indexed as a project on its own, with paths relative to its directory
+    for doc in &mut index.documents {
+        doc.relative_path = format!("{}/{}", prefix, doc.relative_path);
+    }
+    index.documents.retain(|d| {
+        metadata::owner(members, Path::new(&d.relative_path))
+            .is_some_and(|m| keep.contains(&m.name.as_str()))
+    });
+    Ok(index)
+}
+
+/// Members to index: with `--index-selected`, the selected packages and their dependents, which
+/// hold the usages of their items.
+pub fn select(args: &AnalysisFlags, members: Vec<Member>) -> Vec<Member> {
+    if !args.index_selected || args.packages.is_empty() {
+        return members;
+    }
+    let packages = args
+        .packages
+        .iter()
+        .map(|p| p.replace('-', "_"))
+        .collect_vec();
+    let names = members
+        .iter()
+        .filter(|m| packages.contains(&m.name.replace('-', "_")))
+        .map(|m| m.name.clone())
+        .collect_vec();
+    let selected = metadata::with_dependents(&members, &names);
+    members
+        .into_iter()
+        .filter(|m| selected.contains(&m.name))
+        .collect()
+}
+
+/// Generate a SCIP index of the whole workspace.
+///
+/// With `--index-selected`, the directories of the selected members are indexed concurrently and
+/// the indices merged. Otherwise, the workspace is indexed in a single run: rust-analyzer loads
+/// the whole workspace whatever the directory, so that indexing each member takes about as long
+/// as indexing all of them.
+pub fn generate(args: &AnalysisFlags, scip: &Path) -> anyhow::Result<()> {
+    let _t = timings::phase("index generation");
+    preflight(args)?;
+    let workspace = &args.workspace;
+    if let Some(parent) = scip.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Without a Cargo workspace, e.g. with Bazel, the indexer discovers the project itself
+    let all_members = if workspace.join("Cargo.toml").exists() {
+        metadata::members(workspace, &metadata::load(workspace)?)
+    } else {
+        vec![]
+    };
+    let n_members = all_members.len();
+    let members = select(args, all_members.clone());
+    if members.len() < n_members {
+        info!(
+            "Indexing the selected packages and their dependents: {}",
+            members.iter().map(|m| &m.name).join(", ")
+        );
+    }
+    if members.len() == n_members || members.iter().any(|m| m.dir.as_os_str().is_empty()) {
+        if is_compressed(scip) {
+            // The indexer writes uncompressed protobuf
+            let output = scip.with_extension("uncompressed");
+            run_indexer(args, workspace, &output)?;
+            write_scip(&read_scip(&output)?, scip)?;
+            std::fs::remove_file(&output)?;
+        } else {
+            run_indexer(args, workspace, scip)?;
+        }
+        return save_state(args, scip, &members);
+    }
+
+    let keep = members.iter().map(|m| m.name.as_str()).collect_vec();
+    let indices = metadata::member_dirs(&members)
+        .par_iter()
+        .map(|d| index_dir(args, d, &all_members, &keep))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut index = scip::types::Index::default();
+    let mut documents = HashSet::<String>::default();
+    let mut external_symbols = HashSet::<String>::default();
+    for member_index in indices {
+        if index.metadata.is_none() {
+            index.metadata = member_index.metadata;
+        }
+        index.documents.extend(
+            member_index
+                .documents
+                .into_iter()
+                .filter(|d| documents.insert(d.relative_path.clone())),
+        );
+        // Dependencies shared by several members are reported by each of them
+        index.external_symbols.extend(
+            member_index
+                .external_symbols
+                .into_iter()
+                .filter(|s| external_symbols.insert(s.symbol.clone())),
+        );
+    }
+    if let Some(metadata) = index.metadata.as_mut() {
+        let root = workspace
+            .canonicalize()
+            .unwrap_or_else(|_| workspace.clone());
+        metadata.project_root = crate::index::dir_uri(&root);
+    }
+    write_scip(&index, scip)?;
+    save_state(args, scip, &members)
+}
+
+/// Re-index only the members that changed since the index was generated, replacing their
+/// documents in the existing index.
+pub fn reindex(args: &AnalysisFlags, scip: &Path) -> anyhow::Result<()> {
+    let workspace = &args.workspace;
+    if !workspace.join("Cargo.toml").exists() {
+        info!("Re-indexing the whole workspace, whose members are unknown without Cargo");
+        return generate(args, scip);
+    }
+    let Some(state) = load_state(workspace, scip) else {
+        warn!(
+            "No record of the generation of {:?}, re-indexing the whole workspace",
+            scip
+        );
+        return generate(args, scip);
+    };
+
+    let members = select(
+        args,
+        metadata::members(workspace, &metadata::load(workspace)?),
+    );
+    let current = fingerprints(workspace, &members);
+    let changed = members
+        .iter()
+        .filter(|m| state.members.get(&m.name) != current.get(&m.name))
+        .collect_vec();
+    if state.features != feature_flags(args) {
+        info!("Re-indexing the whole workspace with different features");
+        return generate(args, scip);
+    }
+    if changed.is_empty() && state.members.len() == members.len() {
+        info!("The index is up-to-date");
+        return Ok(());
+    }
+    // Members at the workspace root would re-index everything, and added or removed members
+    // change the crate graph.
+    if state.members.len() != members.len() || changed.iter().any(|m| m.dir.as_os_str().is_empty())
+    {
+        info!("Re-indexing the whole workspace");
+        return generate(args, scip);
+    }
+    info!(
+        "Re-indexing the changed members: {}",
+        changed.iter().map(|m| &m.name).join(", ")
+    );
+
+    let _t = timings::phase("index generation");
+    preflight(args)?;
+    let mut index = read_scip(scip)?;
+    for member in changed {
+        let member_index = index_dir(args, &member.dir, &members, &[&member.name])?;
+        index.documents.retain(|d| {
+            metadata::owner(&members, Path::new(&d.relative_path))
+                .is_none_or(|m| m.name != member.name)
+        });
+        index.documents.extend(member_index.documents);
+    }
+    write_scip(&index, scip)?;
+    save_state(args, scip, &members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_date() {
+        let version = "rust-analyzer 1.83.0 (90b35a6 2024-11-26)";
+        assert_eq!(super::release_date(version), Some("2024-11-26"));
+        let version = "rust-analyzer 0.3.1325-standalone (b4ba2bd2b 2022-12-26)";
+        assert_eq!(super::release_date(version), Some("2022-12-26"));
+        assert_eq!(super::release_date("rust-analyzer 1.83.0"), None);
+        assert_eq!(super::release_date("rust-analyzer (2024-1-26)"), None);
+        // Compared as strings to the oldest supported release
+        assert!(super::release_date(version).unwrap() < MIN_RUST_ANALYZER_DATE);
+        assert!("2023-01-02" >= MIN_RUST_ANALYZER_DATE);
+        assert!("2024-11-26" > MIN_RUST_ANALYZER_DATE);
+    }
+}
@@ -0,0 +1,2783 @@
+//! Detection of unused pub items in a Cargo workspace, as run by the `cargo workspace-unused-pub`
+//! binary.
+
+// TODO:
+// - Reduce the number of potential false positives by skipping non-pub methods.
+
+mod api;
+mod bindings;
+mod blame;
+mod cache;
+mod completions;
+mod components;
+pub mod config;
+#[cfg(unix)]
+mod daemon;
+mod doctor;
+mod editor;
+mod explain;
+mod fix;
+pub mod graph;
+mod history;
+pub mod index;
+mod indexer;
+mod logging;
+mod lsif;
+mod lsp;
+mod metadata;
+pub mod metrics;
+mod owners;
+pub mod passes;
+mod routes;
+mod rustc;
+mod rustdoc;
+mod search;
+mod size;
+mod snippet;
+mod stats;
+mod symbols;
+mod timings;
+mod tui;
+mod who_uses;
+mod why;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use colored::Colorize;
+use itertools::Itertools;
+use log::*;
+use scip::types::symbol_information::Kind;
+
+use config::Config;
+use index::{Index, Occurrence, Span, Sym};
+
+#[derive(Parser)]
+#[command(name = "cargo")]
+#[command(bin_name = "cargo")]
+pub enum MainFlags {
+    WorkspaceUnusedPub(Flags),
+}
+
+/// Detect unused pub methods in a workspace.
+#[derive(clap::Args)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Flags {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Arguments of the default `check` command.
+    #[clap(flatten)]
+    check: CheckFlags,
+    /// Format of the logs printed to stderr.
+    #[clap(long, value_enum, global = true, default_value_t = logging::LogFormat::Text)]
+    log_format: logging::LogFormat,
+    /// When to color the output and the logs. With `auto`, they are colored when printed to a
+    /// terminal, unless the `NO_COLOR` environment variable is set.
+    #[clap(long, value_enum, global = true, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color, or `None` to detect it for each output.
+    fn resolve(self) -> Option<bool> {
+        match self {
+            // See https://no-color.org
+            Self::Auto if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) => {
+                Some(false)
+            }
+            Self::Auto => None,
+            Self::Always => Some(true),
+            Self::Never => Some(false),
+        }
+    }
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Report possibly unused pub functions (default).
+    Check(CheckFlags),
+    /// Remove the `pub` qualifier from possibly unused functions.
+    Fix(FixFlags),
+    /// Print the references of a symbol, e.g. `my_crate::MyStruct::method`.
+    WhoUses(WhoUsesFlags),
+    /// List the declared functions and methods, with their visibility, number of references and
+    /// whether they are reported.
+    Symbols(SymbolsFlags),
+    /// Print the number of pub functions and methods per crate, by the crates referencing them.
+    Stats(StatsFlags),
+    /// Run a language server over the standard input and output, publishing the findings as
+    /// diagnostics.
+    ServeLsp(ServeLspFlags),
+    /// Print the number of findings of the runs recorded with `--record-history`.
+    History(HistoryFlags),
+    /// Print the completion script of a shell, to be sourced in its configuration, e.g.
+    /// `source <(cargo workspace-unused-pub completions bash)`.
+    Completions(CompletionsFlags),
+    /// Check the environment (cargo, the indexer, the permissions of the target directory and the
+    /// index of the workspace), and analyze a small bundled workspace end-to-end.
+    Doctor(DoctorFlags),
+}
+
+/// Flags shared by all commands running the analysis.
+#[derive(clap::Args, Clone)]
+pub struct AnalysisFlags {
+    /// Workspace root, or a directory inside the workspace, whose root is then found like other
+    /// cargo subcommands.
+    #[clap(default_value_os_t = std::env::current_dir().unwrap())]
+    pub workspace: PathBuf,
+    /// Other workspaces analyzed along with this one, e.g. calling into it through path
+    /// dependencies. Their indices are merged, and their files searched.
+    #[clap(long, value_name = "DIR")]
+    pub with_workspace: Vec<PathBuf>,
+    /// Format of the code index.
+    #[clap(long, value_enum, default_value_t = Input::Scip)]
+    pub input: Input,
+    /// SCIP files to analyze, or directories containing them, which are merged. Defaults to
+    /// `index.scip` in `target/workspace-unused-pub`. Files compressed with gzip or zstd are
+    /// supported, and `-` reads the standard input.
+    #[clap(long)]
+    pub scip: Vec<PathBuf>,
+    /// Path of the LSIF dump, with `--input lsif`.
+    #[clap(long, default_value = "dump.lsif")]
+    pub lsif: PathBuf,
+    /// Rewrite the document paths of the index under a directory, e.g. `/build/src=.` for an
+    /// index generated in a container where the workspace was at `/build/src`. The target is
+    /// relative to the workspace root.
+    #[clap(long, value_name = "FROM=TO", value_parser = parse_path_map)]
+    pub path_map: Vec<(String, String)>,
+    #[clap(long, value_delimiter = ',', default_value = "rs,html")]
+    pub extensions: Vec<String>,
+    /// Skip larger files in the search pass, in bytes (with an optional K, M or G suffix).
+    #[clap(long, default_value = "4M", value_parser = parse_size)]
+    pub max_file_size: u64,
+    /// Follow the symbolic links in the search pass, which are skipped by default as they may
+    /// lead outside of the workspace. Cycles are detected and skipped.
+    #[clap(long)]
+    pub follow_symlinks: bool,
+    /// Number of parallel jobs, for the search pass and index generation. Defaults to the number
+    /// of CPUs.
+    #[clap(short, long)]
+    pub jobs: Option<usize>,
+    /// Do not use or update the caches in the target directory.
+    #[clap(long)]
+    pub no_cache: bool,
+    /// Command generating the SCIP index, to which the directory to index and
+    /// `--output <path>` are appended. Defaults to `rust-analyzer scip`.
+    #[clap(long)]
+    pub indexer_cmd: Option<String>,
+    /// Regenerate the whole index, even if it exists.
+    #[clap(long, conflicts_with_all = ["reindex", "refresh_stale"])]
+    pub regenerate: bool,
+    /// Re-index the workspace members that changed since the index was generated.
+    #[clap(long)]
+    pub reindex: bool,
+    /// Re-index the changed workspace members if the index looks stale.
+    #[clap(long)]
+    pub refresh_stale: bool,
+    /// Cargo features to activate when generating the index.
+    #[clap(long, value_delimiter = ',')]
+    pub features: Vec<String>,
+    /// Activate all the cargo features when generating the index.
+    #[clap(long)]
+    pub all_features: bool,
+    /// Do not activate the default cargo features when generating the index.
+    #[clap(long)]
+    pub no_default_features: bool,
+    /// Print the duration and memory usage of each phase to stderr.
+    #[clap(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "text")]
+    pub timings: Option<timings::Format>,
+    /// Only report items from these packages.
+    #[clap(short, long = "package", add = clap_complete::ArgValueCompleter::new(completions::packages))]
+    pub packages: Vec<String>,
+    /// Only index the packages selected with --package, and the workspace members depending on
+    /// them, which are needed to find their usages.
+    #[clap(long, requires = "packages")]
+    pub index_selected: bool,
+    /// Only search the files listed in this file, one per line relative to the workspace root
+    /// (e.g. the output of `git ls-files`), or in the standard input with `-`, and only report
+    /// the items defined in them. The references of the index still count, from any file.
+    #[clap(long, value_name = "PATH", value_parser = parse_file_list)]
+    pub files_from: Option<FileList>,
+    /// Ignore the usages in the directories listed in `workspace.exclude`, whose definitions are
+    /// never reported.
+    #[clap(long)]
+    pub exclude_usages: bool,
+    /// Do not report the items reachable from the root of the publishable library crates,
+    /// which crates outside of the workspace may use. All the items of the members with
+    /// `publish = false` are still analyzed.
+    #[clap(long)]
+    pub keep_public_api: bool,
+    /// Members with consumers outside of the workspace, whose items reachable from the crate
+    /// root are not reported, like with --keep-public-api. Their other items, and all the items
+    /// of the other members, are still analyzed.
+    #[clap(long, value_name = "CRATE,...", value_delimiter = ',')]
+    pub external_consumers: Vec<String>,
+    /// Do not report the functions and methods exported by the publishable library crates,
+    /// according to their rustdoc JSON output in `target/doc`, but list them separately.
+    #[clap(long)]
+    pub respect_public_api: bool,
+    /// Kinds of items to report.
+    #[clap(long, value_delimiter = ',', default_value = "functions,methods")]
+    pub kinds: Vec<ItemKind>,
+    /// Also report the items with at most this number of references, along with their
+    /// references, e.g. to find the ones only used in a test.
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    pub max_uses: usize,
+    /// Only report the items whose definition spans at least this number of lines, including
+    /// their doc comment and attributes, e.g. to skip trivial getters. Items of unknown size are
+    /// kept.
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    pub min_lines: usize,
+    /// Run `cargo check --message-format json` and mark the findings that rustc already reports
+    /// with its `dead_code` lint, so that the ones the compiler cannot see, e.g. the items only
+    /// unused across crates, stand out.
+    #[clap(long)]
+    pub rustc_dead_code: bool,
+    /// Read the JSON messages of `cargo check` from this file rather than running it. Implies
+    /// `--rustc-dead-code`.
+    #[clap(long, value_name = "PATH")]
+    pub rustc_messages: Option<PathBuf>,
+    /// Do not report the findings that rustc already reports with its `dead_code` lint. Implies
+    /// `--rustc-dead-code`.
+    #[clap(long)]
+    pub hide_rustc_dead_code: bool,
+    /// How to report the functions and methods generated by macro invocations, e.g. builder
+    /// methods, recognized by a name in the invocation rather than after `fn`.
+    #[clap(long, value_enum, default_value_t = MacroGenerated::Include)]
+    pub macro_generated: MacroGenerated,
+    /// Also report the functions and methods only referenced by unused ones, and group the
+    /// findings referencing each other into clusters.
+    #[clap(long)]
+    pub transitive: bool,
+    /// Also report the default methods of traits that are never called nor overridden, rather
+    /// than keeping all trait methods.
+    #[clap(long)]
+    pub trait_defaults: bool,
+    /// Include the references of every function and method in the JSON report, not only of the
+    /// unused ones.
+    #[clap(long)]
+    pub references: bool,
+    /// Also report the items re-exported under several public paths, the items of glob
+    /// re-exports shadowed by an explicit item with the same name, and the glob re-exports of
+    /// modules whose items are all unused.
+    #[clap(long)]
+    pub reexports: bool,
+    /// Also list the items only referenced from the example targets, which are not findings as
+    /// they may only exist for demos.
+    #[clap(long)]
+    pub example_only: bool,
+    /// Also list the items only referenced from the tests of their own file, which are not
+    /// findings as their unit tests keep them alive.
+    #[clap(long)]
+    pub self_tested: bool,
+    /// Also list the functions and methods declared with a visibility in `#[cfg(test)]` modules
+    /// and only referenced within their module, which can be made private.
+    #[clap(long)]
+    pub needless_pub: bool,
+    /// CODEOWNERS file assigning owners to the findings, relative to the workspace root.
+    /// Defaults to `.github/CODEOWNERS`, `CODEOWNERS` or `docs/CODEOWNERS` in the repository.
+    #[clap(long, value_name = "PATH")]
+    pub codeowners: Option<PathBuf>,
+    /// Group the findings of the text report by file, or by owner according to CODEOWNERS.
+    #[clap(long, value_enum, default_value_t = GroupBy::File)]
+    pub group_by: GroupBy,
+    /// Annotate the findings with the author and date of the last change of their definition
+    /// line, according to `git blame`.
+    #[clap(long)]
+    pub blame: bool,
+    /// List the passes that each finding went through, with the evidence for keeping it, e.g.
+    /// its number of references and of lines containing its name.
+    #[clap(long)]
+    pub explain: bool,
+    /// Print the fingerprint of each finding in the text and snippets reports, as passed to
+    /// `fix --only` or listed in the `ignore` configuration. The JSON report always has them.
+    #[clap(long)]
+    pub show_fingerprints: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum MacroGenerated {
+    /// Report them like the other findings.
+    Include,
+    /// Do not report them.
+    Exclude,
+    /// List them separately from the findings.
+    Separate,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    File,
+    /// Findings of each owner, grouped by file. Findings with several owners are listed under
+    /// each of them.
+    Owner,
+    /// Undocumented findings first, which are usually easy to delete, and then the ones with a
+    /// doc comment, which may be an intentional API, grouped by file.
+    Documentation,
+}
+
+#[derive(clap::Args)]
+pub struct CheckFlags {
+    #[clap(flatten)]
+    analysis: AnalysisFlags,
+    /// Triage the findings in an interactive terminal interface.
+    #[clap(long)]
+    tui: bool,
+    /// Keep the parsed index in memory and answer the `check` invocations in this workspace
+    /// over a local socket.
+    #[clap(long, conflicts_with = "tui")]
+    daemon: bool,
+    /// Output format of the report.
+    #[clap(long, value_enum, default_value_t = ReportFormat::Text, conflicts_with = "tui")]
+    format: ReportFormat,
+    /// Print the JSON Schema of the report in the JSON format, and exit.
+    #[clap(long)]
+    print_schema: bool,
+    /// Write the reference graph between the symbols, with the findings highlighted.
+    #[clap(long, value_name = "PATH")]
+    emit_graph: Option<PathBuf>,
+    /// Format of the graph. Defaults to JSON or CSV for paths with these extensions, and to
+    /// Graphviz otherwise.
+    #[clap(long, value_enum, requires = "emit_graph")]
+    graph_format: Option<graph::Format>,
+    /// Only include the findings and the symbols referencing them or referenced by them in the
+    /// graph.
+    #[clap(long, requires = "emit_graph")]
+    graph_findings: bool,
+    /// Open the n-th finding of the report (the first by default) in `$VISUAL` or `$EDITOR`,
+    /// after printing the report.
+    #[clap(long, value_name = "N", num_args = 0..=1, default_missing_value = "1", conflicts_with_all = ["tui", "daemon"])]
+    open: Option<usize>,
+    /// Record the number of findings per crate, with the current git commit and time, in this
+    /// SQLite database, relative to the workspace root. The trend is printed by the `history`
+    /// command.
+    #[clap(long, value_name = "PATH")]
+    record_history: Option<PathBuf>,
+    /// Write aggregate metrics of the run (number of findings per crate and kind, duration, index
+    /// age) to this JSON file, e.g. for dashboards.
+    #[clap(long, value_name = "PATH")]
+    emit_metrics: Option<PathBuf>,
+    /// Write a shields.io endpoint badge with the number of findings to this JSON file.
+    #[clap(long, value_name = "PATH")]
+    emit_badge: Option<PathBuf>,
+    /// Maximal numbers of findings for which the badge is green, yellow and orange, above
+    /// which it is red.
+    #[clap(
+        long,
+        value_name = "N,...",
+        value_delimiter = ',',
+        default_value = "0,10,50",
+        requires = "emit_badge"
+    )]
+    badge_thresholds: Vec<usize>,
+    /// Print how the analysis decided whether to report a symbol instead of the report: whether
+    /// it was collected, the pass removing it, and the references and lines counted as usages.
+    /// The symbol is given as for `who-uses`.
+    #[clap(long, value_name = "SYMBOL", conflicts_with = "tui")]
+    why: Option<String>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Findings grouped by file, with the line of the definition.
+    Text,
+    /// Findings rendered like rustc diagnostics, with a caret under the name and a note on each
+    /// pass that kept them.
+    Snippets,
+    /// Report as a JSON object, as described by `--print-schema`.
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    Text,
+    Json,
+}
+
+#[derive(clap::Args)]
+pub struct FixFlags {
+    #[clap(flatten)]
+    analysis: AnalysisFlags,
+    /// Only fix the finding with this fingerprint.
+    #[clap(long)]
+    only: Option<String>,
+    /// Fix files even if they have uncommitted changes.
+    #[clap(long)]
+    allow_dirty: bool,
+}
+
+#[derive(clap::Args)]
+pub struct WhoUsesFlags {
+    /// Path of the symbol, or a suffix of it, e.g. `MyStruct::method`. SCIP symbols are also
+    /// accepted.
+    symbol: String,
+    #[clap(flatten)]
+    analysis: AnalysisFlags,
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+pub struct SymbolsFlags {
+    #[clap(flatten)]
+    analysis: AnalysisFlags,
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = symbols::Format::Text)]
+    format: symbols::Format,
+}
+
+#[derive(clap::Args)]
+pub struct StatsFlags {
+    #[clap(flatten)]
+    analysis: AnalysisFlags,
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+pub struct HistoryFlags {
+    /// Workspace root, or a directory inside the workspace.
+    #[clap(default_value_os_t = std::env::current_dir().unwrap())]
+    workspace: PathBuf,
+    /// History database, relative to the workspace root.
+    #[clap(long, default_value = ".unused-pub/history.sqlite")]
+    path: PathBuf,
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(clap::Args)]
+pub struct CompletionsFlags {
+    #[clap(value_enum)]
+    shell: clap_complete::Shell,
+}
+
+#[derive(clap::Args)]
+pub struct DoctorFlags {
+    #[clap(flatten)]
+    analysis: AnalysisFlags,
+}
+
+#[derive(clap::Args)]
+pub struct ServeLspFlags {
+    #[clap(flatten)]
+    analysis: AnalysisFlags,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// SCIP index, generated with rust-analyzer if missing.
+    Scip,
+    /// rustdoc JSON output of the workspace members, in `target/doc`.
+    RustdocJson,
+    /// LSIF dump, in the JSON lines format.
+    Lsif,
+}
+
+#[derive(
+    clap::ValueEnum, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    #[value(name = "functions")]
+    Function,
+    #[value(name = "methods")]
+    Method,
+}
+impl std::fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Function => write!(f, "function"),
+            Self::Method => write!(f, "method"),
+        }
+    }
+}
+impl ItemKind {
+    fn from_scip(kind: Kind) -> Option<Self> {
+        match kind {
+            Kind::Function => Some(Self::Function),
+            Kind::Method => Some(Self::Method),
+            _ => None,
+        }
+    }
+}
+
+/// A possibly unused item.
+pub struct Finding<'a> {
+    /// Path relative to the workspace root.
+    pub path: &'a str,
+    pub symbol: &'a str,
+    pub display_name: &'a str,
+    pub kind: ItemKind,
+    /// Position of the name in the definition.
+    pub span: Span,
+    /// References, with `--max-uses`.
+    pub references: Vec<Location>,
+    /// Whether it is declared in a crate without a library target, where it can be made private
+    /// rather than removed from an API.
+    pub bin_only: bool,
+    /// Whether it is declared in a proc-macro crate, where only the references within the crate
+    /// are counted, with a lower confidence.
+    pub proc_macro: bool,
+    /// Whether rustc already reports it with its `dead_code` lint, with `--rustc-dead-code`.
+    pub rustc_dead_code: bool,
+}
+impl<'a> Finding<'a> {
+    pub fn package(&self) -> &'a str {
+        self.symbol.split(' ').nth(2).unwrap_or_default()
+    }
+
+    /// Stable identifier, independent of the package version.
+    pub fn fingerprint(&self) -> String {
+        // Symbols have the form `<scheme> <manager> <name> <version> <descriptors>`.
+        let mut parts = self.symbol.splitn(5, ' ');
+        let name = parts.nth(2).unwrap_or_default();
+        let descriptors = parts.nth(1).unwrap_or_default();
+        // FNV-1a, which is stable across Rust versions, unlike `DefaultHasher`.
+        let hash = format!("{} {}", name, descriptors)
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |h, b| {
+                (h ^ b as u64).wrapping_mul(0x100000001b3)
+            });
+        format!("{:016x}", hash)
+    }
+}
+
+fn parse_size(s: &str) -> Result<u64, String> {
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&s[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&s[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    digits
+        .parse::<u64>()
+        .map(|n| n * multiplier)
+        .map_err(|e| format!("Invalid size {:?}: {}", s, e))
+}
+
+fn parse_path_map(s: &str) -> Result<(String, String), String> {
+    let (from, to) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid path mapping {:?}, expected FROM=TO", s))?;
+    Ok((from.to_string(), to.to_string()))
+}
+
+/// Files given with `--files-from`, read on first use rather than while parsing the arguments.
+#[derive(Clone)]
+pub struct FileList {
+    path: PathBuf,
+    /// Paths relative to the workspace root, or absolute, or the error reading them.
+    files: std::sync::Arc<std::sync::OnceLock<Result<HashSet<PathBuf>, String>>>,
+}
+
+impl FileList {
+    /// Whether the list is read from the standard input.
+    pub fn is_stdin(&self) -> bool {
+        cache::is_stdin(&self.path)
+    }
+
+    /// Listed paths, read once.
+    pub fn read(&self) -> anyhow::Result<&HashSet<PathBuf>> {
+        let files = self.files.get_or_init(|| {
+            let contents = if self.is_stdin() {
+                std::io::read_to_string(std::io::stdin())
+            } else {
+                std::fs::read_to_string(&self.path)
+            }
+            .map_err(|e| format!("Failed to read the file list {:?}: {}", self.path, e))?;
+            Ok(contents
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(|l| PathBuf::from(l.strip_prefix("./").unwrap_or(l)))
+                .collect())
+        });
+        files.as_ref().map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    /// Whether a path, relative to the workspace root or absolute, is listed. Nothing is listed
+    /// if the list cannot be read, which [`load_index`] reports.
+    pub fn contains(&self, workspace: &Path, path: &Path) -> bool {
+        let Ok(files) = self.read() else {
+            return false;
+        };
+        let relative = path.strip_prefix(workspace).unwrap_or(path);
+        files.contains(relative) || files.contains(&workspace.join(relative))
+    }
+}
+
+fn parse_file_list(s: &str) -> Result<FileList, String> {
+    Ok(FileList {
+        path: s.into(),
+        files: Default::default(),
+    })
+}
+
+/// Package name of a symbol, normalized with underscores.
+fn symbol_package(symbol: &str) -> Option<String> {
+    symbol.split(' ').nth(2).map(|p| p.replace('-', "_"))
+}
+
+/// Read a source file, replacing its invalid UTF-8 sequences, e.g. in latin-1 fixtures.
+pub(crate) fn read_source(path: &std::path::Path) -> std::io::Result<String> {
+    let bytes = std::fs::read(path)?;
+    Ok(match String::from_utf8(bytes) {
+        Ok(contents) => contents,
+        Err(e) => {
+            debug!("{:?} is not valid UTF-8", path);
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+    })
+}
+
+/// SCIP files to analyze, with directories expanded to the `.scip` files they contain.
+fn scip_paths(args: &AnalysisFlags) -> anyhow::Result<Vec<PathBuf>> {
+    if args.scip.is_empty() {
+        // Indices generated by previous versions at the workspace root are still used
+        let legacy = args.workspace.join("index.scip");
+        if legacy.exists() {
+            debug!("Using the index at the workspace root {:?}", legacy);
+            return Ok(vec![legacy]);
+        }
+        return Ok(vec![cache::dir(&args.workspace).join("index.scip")]);
+    }
+    let mut paths = vec![];
+    for path in &args.scip {
+        if !path.is_dir() {
+            paths.push(path.clone());
+            continue;
+        }
+        let files = std::fs::read_dir(path)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                let name = p.file_name().unwrap_or_default().to_string_lossy();
+                [".scip", ".scip.gz", ".scip.zst"]
+                    .iter()
+                    .any(|e| name.ends_with(e))
+            })
+            .sorted()
+            .collect_vec();
+        anyhow::ensure!(!files.is_empty(), "No SCIP files in {:?}", path);
+        paths.extend(files);
+    }
+    Ok(paths)
+}
+
+/// Generate or refresh a single SCIP file as requested.
+fn prepare_scip(args: &AnalysisFlags, scip: &std::path::Path) -> anyhow::Result<()> {
+    if args.regenerate {
+        info!("Regenerating the SCIP file at {:?}", scip);
+        indexer::generate(args, scip)?;
+    } else if !scip.exists() {
+        warn!(
+            "SCIP file not found at {:?}. Generating with rust-analyzer. This may take a while for large workspaces.",
+            scip
+        );
+        indexer::generate(args, scip)?;
+    } else if args.reindex {
+        indexer::reindex(args, scip)?;
+    } else if let Some(reason) = indexer::staleness(&args.workspace, scip) {
+        if args.refresh_stale {
+            info!("Refreshing the stale SCIP index: {}", reason);
+            indexer::reindex(args, scip)?;
+        } else {
+            warn!(
+                "{}",
+                format!(
+                    "The SCIP index may be stale: {}. Results may be wrong; run with --reindex or --refresh-stale.",
+                    reason
+                )
+                .bold()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Workspaces analyzed along with the main one, from `--with-workspace` and the configuration.
+pub(crate) fn other_workspaces(args: &AnalysisFlags, config: &Config) -> Vec<PathBuf> {
+    args.with_workspace
+        .iter()
+        .cloned()
+        .chain(config.workspaces.iter().map(|w| args.workspace.join(w)))
+        .unique()
+        .collect()
+}
+
+/// Use the root of the workspace containing the given directory, e.g. when running from a member
+/// or one of its subdirectories, like other cargo subcommands.
+///
+/// Directories with a configuration file are kept as is.
+fn discover_workspace(workspace: &mut PathBuf) {
+    if Config::path(workspace).exists() {
+        return;
+    }
+    let Some(root) = metadata::workspace_root(workspace) else {
+        return;
+    };
+    let canonical = |p: &std::path::Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    if canonical(&root) != canonical(workspace) {
+        info!("Using the workspace root {:?}", root);
+        *workspace = root;
+    }
+}
+
+pub fn load_index(args: &AnalysisFlags) -> anyhow::Result<Index> {
+    if let Some(files) = &args.files_from {
+        files.read()?;
+    }
+    let config = Config::load(&args.workspace)?;
+    let is_cargo = args.workspace.join("Cargo.toml").exists();
+    if !is_cargo && config.source_roots.is_empty() {
+        anyhow::bail!(
+            "{:?} does not contain a Cargo.toml file, nor a configuration with source-roots",
+            args.workspace
+        );
+    }
+    let mut index = read_index(args)?;
+    index.remap_paths(&args.path_map);
+    for workspace in other_workspaces(args, &config) {
+        info!("Merging the index of the workspace {:?}", workspace);
+        let mut other = args.clone();
+        other.workspace = workspace;
+        other.scip = vec![];
+        other.path_map = vec![];
+        other.index_selected = false;
+        index.merge(read_index(&other)?);
+    }
+    resolve_documents(&args.workspace, &config.source_roots, &mut index);
+    index.normalize_columns(&args.workspace);
+    if args.exclude_usages {
+        let excluded = metadata::excluded_dirs(&args.workspace);
+        let n_occurrences = index.occurrences.len();
+        let documents = &index.documents;
+        index.occurrences.retain(|o| {
+            let path = std::path::Path::new(&documents[o.document]);
+            !excluded.iter().any(|e| path.starts_with(e))
+        });
+        debug!(
+            "Dropped {} occurrences in excluded directories",
+            n_occurrences - index.occurrences.len()
+        );
+    }
+    if is_cargo {
+        check_coverage(args, &index);
+    }
+    Ok(index)
+}
+
+/// Make the document paths that do not exist relative to the workspace root relative to it,
+/// from the first source root under which they exist.
+fn resolve_documents(workspace: &std::path::Path, roots: &[PathBuf], index: &mut Index) {
+    for doc in &mut index.documents {
+        if workspace.join(&*doc).exists() {
+            continue;
+        }
+        if let Some(root) = roots
+            .iter()
+            .find(|r| workspace.join(r).join(&*doc).exists())
+        {
+            *doc = index::slash_path(&root.join(&*doc));
+        }
+    }
+}
+
+/// Warn about the workspace members without any document in the index, whose items would
+/// falsely appear unused elsewhere.
+fn check_coverage(args: &AnalysisFlags, index: &Index) {
+    let workspace = &args.workspace;
+    let members = match metadata::load(workspace) {
+        Ok(metadata) => indexer::select(args, metadata::members(workspace, &metadata)),
+        Err(e) => {
+            warn!("Could not check the index coverage: {}", e);
+            return;
+        }
+    };
+    let covered: HashSet<&str> = index
+        .documents
+        .iter()
+        .filter_map(|d| metadata::owner(&members, std::path::Path::new(d)))
+        .map(|m| m.name.as_str())
+        .collect();
+    let missing = members
+        .iter()
+        .filter(|m| !covered.contains(m.name.as_str()))
+        .map(|m| m.name.as_str())
+        .collect_vec();
+    if !missing.is_empty() {
+        warn!(
+            "{}",
+            format!(
+                "The index has no documents for the members {}, which may have failed to build. Their items may be falsely reported as unused.",
+                missing.join(", ")
+            )
+            .bold()
+        );
+    }
+}
+
+fn read_index(args: &AnalysisFlags) -> anyhow::Result<Index> {
+    match args.input {
+        Input::Scip => {}
+        Input::RustdocJson => {
+            let _t = timings::phase("parsing");
+            return rustdoc::load_index(&args.workspace);
+        }
+        Input::Lsif => {
+            let lsif = args.workspace.join(&args.lsif);
+            anyhow::ensure!(lsif.exists(), "LSIF dump not found at {:?}", lsif);
+            info!("Running on {:?} with LSIF {:?}", args.workspace, lsif);
+            let _t = timings::phase("parsing");
+            return cache::load_index(&args.workspace, &lsif, lsif::read, !args.no_cache);
+        }
+    }
+    let scips = scip_paths(args)?;
+    anyhow::ensure!(
+        !(args.files_from.as_ref().is_some_and(FileList::is_stdin)
+            && scips.iter().any(|s| cache::is_stdin(s))),
+        "The file list and the SCIP index cannot both be read from the standard input"
+    );
+    if let [scip] = scips.as_slice() {
+        if !cache::is_stdin(scip) {
+            prepare_scip(args, scip)?;
+        }
+        info!(
+            workspace:% = args.workspace.display(), scip:% = scip.display();
+            "Running on {:?} with SCIP {:?}", args.workspace, scip
+        );
+    } else {
+        anyhow::ensure!(
+            !args.regenerate && !args.reindex && !args.refresh_stale,
+            "Indices can only be generated for a single SCIP file"
+        );
+        if let Some(missing) = scips.iter().find(|s| !s.exists() && !cache::is_stdin(s)) {
+            anyhow::bail!("SCIP file not found at {:?}", missing);
+        }
+        info!("Running on {:?} with SCIP {:?}", args.workspace, scips);
+    }
+
+    // Parse SCIP
+    let _t = timings::phase("parsing");
+    let load = |scip| cache::load_index(&args.workspace, scip, Index::read_scip, !args.no_cache);
+    let index = if let [scip] = scips.as_slice() {
+        load(scip)?
+    } else {
+        // Indices generated in subdirectories, e.g. for each member, are merged relative to the
+        // workspace root.
+        let root = args.workspace.canonicalize()?;
+        let mut index = Index {
+            project_root: index::dir_uri(&root),
+            ..Default::default()
+        };
+        for scip in &scips {
+            index.merge(load(scip)?);
+        }
+        index
+    };
+    debug!("Opened SCIP file with {} documents", index.documents.len());
+    Ok(index)
+}
+
+/// Options of [`analyze`], which are also the flags of the `check` command.
+pub type Options = AnalysisFlags;
+
+impl AnalysisFlags {
+    /// Default options for a workspace.
+    pub fn new(workspace: impl Into<PathBuf>) -> Self {
+        let cmd = <Self as clap::Args>::augment_args(clap::Command::new("workspace-unused-pub"));
+        let matches = cmd.get_matches_from(["workspace-unused-pub"]);
+        let mut options = <Self as clap::FromArgMatches>::from_arg_matches(&matches).unwrap();
+        options.workspace = workspace.into();
+        options
+    }
+
+    /// Whether to compare the findings with rustc's `dead_code` lint.
+    pub fn rustc_dead_code(&self) -> bool {
+        self.rustc_dead_code || self.rustc_messages.is_some() || self.hide_rustc_dead_code
+    }
+}
+
+/// Version of the [`Report`] format, incremented on incompatible changes.
+pub const REPORT_VERSION: u32 = 1;
+
+/// JSON Schema of the serialized [`Report`].
+pub const REPORT_SCHEMA: &str = include_str!("../schema/report.schema.json");
+
+/// Result of [`analyze`].
+#[derive(serde::Serialize, Debug)]
+pub struct Report {
+    /// See [`REPORT_VERSION`].
+    pub version: u32,
+    /// Findings, sorted by path and line.
+    pub findings: Vec<UnusedItem>,
+    /// Number of candidates after each pass.
+    pub passes: Vec<PassStats>,
+    /// Total number of lines of the definitions of the findings, where known.
+    pub deletable_lines: usize,
+    /// References of every function and method, with `--references`, from the least used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub references: Option<Vec<SymbolReferences>>,
+    /// Findings referencing each other, with `--transitive`, from the largest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clusters: Option<Vec<graph::Cluster>>,
+    /// Duplicate and shadowed re-exports, with `--reexports`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reexports: Option<Vec<api::Reexport>>,
+    /// Items only referenced from the examples, with `--example-only` and these references. See
+    /// [`example_only`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub example_only: Vec<UnusedItem>,
+    /// Items only referenced from the tests of their own file, with `--self-tested` and these
+    /// references. See [`self_tested`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub self_tested: Vec<UnusedItem>,
+    /// Items declared with a visibility in `#[cfg(test)]` modules, which can be private, with
+    /// `--needless-pub`. See [`needless_pub`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub needless_pub: Vec<UnusedItem>,
+    /// Items only named in the string literals, e.g. looked up at runtime, which are not findings
+    /// but reported with a low confidence.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub string_only: Vec<UnusedItem>,
+    /// Items only mentioned in the doc comments and Markdown files, which are not findings.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub documentation_only: Vec<UnusedItem>,
+    /// Unused inherent methods named like the method of a trait that their type does not
+    /// implement, e.g. left over after the removal of the implementation.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub orphaned_trait_methods: Vec<UnusedItem>,
+    /// Items generated by macro invocations, with `--macro-generated separate`, which are not
+    /// findings.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub macro_generated: Vec<UnusedItem>,
+    /// `#[wasm_bindgen]` exports only used from the configured JavaScript sources, which are not
+    /// findings.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub javascript_only: Vec<UnusedItem>,
+    /// Exports of PyO3 modules only used from the configured Python sources, which are not
+    /// findings.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub python_only: Vec<UnusedItem>,
+    /// `#[no_mangle]` functions only used from the configured C and C++ sources, which are not
+    /// findings.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub c_only: Vec<UnusedItem>,
+    /// With `--respect-public-api`, items exported by the publishable crates that are unused
+    /// within the workspace, which are not findings.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub externally_visible: Vec<UnusedItem>,
+}
+
+/// A possibly unused item, as returned by [`analyze`].
+#[derive(serde::Serialize, Debug)]
+pub struct UnusedItem {
+    /// Path relative to the workspace root.
+    pub path: String,
+    /// Position of the name in the definition.
+    pub span: Span,
+    pub kind: ItemKind,
+    pub symbol: String,
+    pub display_name: String,
+    pub fingerprint: String,
+    /// References, with `--max-uses`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<Location>,
+    /// Whether the crate only has binary targets, so that the item can be made private.
+    pub bin_only: bool,
+    /// Whether the crate is a proc-macro one, in which case the finding is less certain.
+    pub proc_macro: bool,
+    /// Whether rustc already reports it with its `dead_code` lint, with `--rustc-dead-code`.
+    pub rustc_dead_code: bool,
+    /// Owners of the file according to CODEOWNERS, if any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<String>,
+    /// Last commit changing the definition line, with `--blame`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blame: Option<blame::Blame>,
+    /// Evidence for keeping the item in each pass, with `--explain`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub explanation: Vec<explain::Explanation>,
+    /// Number of lines of the definition, including its doc comment and attributes, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines: Option<usize>,
+    /// Whether the definition has a doc comment, with `--group-by documentation`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documented: Option<bool>,
+}
+
+impl From<&Finding<'_>> for UnusedItem {
+    fn from(f: &Finding) -> Self {
+        Self {
+            path: f.path.into(),
+            span: f.span,
+            kind: f.kind,
+            symbol: f.symbol.into(),
+            display_name: f.display_name.into(),
+            fingerprint: f.fingerprint(),
+            references: f.references.clone(),
+            bin_only: f.bin_only,
+            proc_macro: f.proc_macro,
+            rustc_dead_code: f.rustc_dead_code,
+            owners: vec![],
+            blame: None,
+            explanation: vec![],
+            lines: None,
+            documented: None,
+        }
+    }
+}
+
+impl Report {
+    pub fn new(
+        index: &Index,
+        args: &AnalysisFlags,
+        config: &Config,
+        metadata: &metadata::Workspace,
+        findings: &[Finding],
+        passes: Vec<PassStats>,
+        listed: &Listed,
+    ) -> Self {
+        let explanations = if args.explain {
+            explain::explain(index, args, metadata, findings, &passes).unwrap_or_else(|e| {
+                warn!("Could not explain the findings: {}", e);
+                HashMap::new()
+            })
+        } else {
+            HashMap::new()
+        };
+        let lines = size::lines(index, &args.workspace, findings);
+        let mut report = Self {
+            version: REPORT_VERSION,
+            findings: findings
+                .iter()
+                .map(|f| UnusedItem {
+                    lines: lines.get(f.symbol).copied(),
+                    ..f.into()
+                })
+                .collect(),
+            deletable_lines: lines.values().sum(),
+            passes,
+            references: args.references.then(|| references(index)),
+            clusters: args.transitive.then(|| graph::clusters(index, findings)),
+            reexports: args
+                .reexports
+                .then(|| api::reexports(index, &args.workspace)),
+            example_only: if args.example_only {
+                example_only(index, args, config, metadata)
+                    .iter()
+                    .map(UnusedItem::from)
+                    .collect()
+            } else {
+                vec![]
+            },
+            self_tested: if args.self_tested {
+                self_tested(index, args, config)
+                    .iter()
+                    .map(UnusedItem::from)
+                    .collect()
+            } else {
+                vec![]
+            },
+            needless_pub: if args.needless_pub {
+                needless_pub(index, args, config)
+                    .iter()
+                    .map(UnusedItem::from)
+                    .collect()
+            } else {
+                vec![]
+            },
+            string_only: listed.string_only.iter().map(UnusedItem::from).collect(),
+            documentation_only: listed
+                .documentation_only
+                .iter()
+                .map(UnusedItem::from)
+                .collect(),
+            orphaned_trait_methods: listed
+                .orphaned_trait_methods
+                .iter()
+                .map(UnusedItem::from)
+                .collect(),
+            macro_generated: listed
+                .macro_generated
+                .iter()
+                .map(UnusedItem::from)
+                .collect(),
+            javascript_only: listed
+                .javascript_only
+                .iter()
+                .map(UnusedItem::from)
+                .collect(),
+            python_only: listed.python_only.iter().map(UnusedItem::from).collect(),
+            c_only: listed.c_only.iter().map(UnusedItem::from).collect(),
+            externally_visible: listed
+                .externally_visible
+                .iter()
+                .map(UnusedItem::from)
+                .collect(),
+        };
+        match owners::CodeOwners::load(&args.workspace, args.codeowners.as_deref()) {
+            Ok(Some(owners)) => {
+                for item in report.items_mut() {
+                    item.owners = owners.owners(&item.path).to_vec();
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Could not assign the owners of the findings: {}", e),
+        }
+        if args.group_by == GroupBy::Documentation {
+            let mut sources = symbols::Sources::new(&args.workspace);
+            for item in report.items_mut() {
+                item.documented = Some(symbols::is_documented(
+                    &mut sources,
+                    &item.path,
+                    item.span.start_line,
+                ));
+            }
+        }
+        if args.blame {
+            let mut blames = blame::blame(&args.workspace, &findings.iter().collect_vec());
+            for item in &mut report.findings {
+                item.blame = blames.remove(&(item.path.clone(), item.span.start_line));
+            }
+        }
+        for item in &mut report.findings {
+            item.explanation = explanations
+                .get(item.symbol.as_str())
+                .cloned()
+                .unwrap_or_default();
+        }
+        report
+    }
+
+    /// Findings, and the items listed separately.
+    fn items_mut(&mut self) -> impl Iterator<Item = &mut UnusedItem> {
+        let items = self.findings.iter_mut().chain(&mut self.example_only);
+        let items = items.chain(&mut self.self_tested);
+        let items = items.chain(&mut self.needless_pub);
+        let items = items.chain(&mut self.string_only);
+        let items = items.chain(&mut self.documentation_only);
+        let items = items.chain(&mut self.orphaned_trait_methods);
+        let items = items.chain(&mut self.macro_generated);
+        let items = items.chain(&mut self.javascript_only);
+        let items = items.chain(&mut self.python_only);
+        let items = items.chain(&mut self.c_only);
+        items.chain(&mut self.externally_visible)
+    }
+}
+
+/// Position in a document.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Location {
+    /// Path relative to the workspace root.
+    pub path: String,
+    pub span: Span,
+}
+
+/// References to a function or method declared in the workspace.
+#[derive(serde::Serialize, Debug)]
+pub struct SymbolReferences {
+    pub symbol: String,
+    pub display_name: String,
+    pub kind: ItemKind,
+    pub definition: Option<Location>,
+    /// Number of references.
+    pub count: usize,
+    pub references: Vec<Location>,
+}
+
+/// Definition and references of the selected symbols, in the order of the index.
+pub(crate) fn locations(
+    index: &Index,
+    selected: impl Fn(Sym) -> bool,
+) -> HashMap<Sym, (Option<Location>, Vec<Location>)> {
+    let mut locations: HashMap<Sym, (Option<Location>, Vec<Location>)> = HashMap::new();
+    for o in index.occurrences.iter().filter(|o| selected(o.symbol)) {
+        let (definition, references) = locations.entry(o.symbol).or_default();
+        let location = Location {
+            path: index.documents[o.document].clone(),
+            span: o.span(),
+        };
+        if o.is_definition() {
+            definition.get_or_insert(location);
+        } else {
+            references.push(location);
+        }
+    }
+    locations
+}
+
+/// References of every function and method declared in the index, sorted by increasing number
+/// of references.
+///
+/// Unlike the findings, these are only the references recorded in the index, without the
+/// filtering of the passes.
+pub fn references(index: &Index) -> Vec<SymbolReferences> {
+    let kinds: HashMap<Sym, ItemKind> = index
+        .symbols
+        .values()
+        .filter_map(|s| Some((s.symbol, ItemKind::from_scip(s.kind)?)))
+        .collect();
+    let mut locations = locations(index, |s| kinds.contains_key(&s));
+    let mut symbols = kinds
+        .into_iter()
+        .map(|(symbol, kind)| {
+            let (definition, references) = locations.remove(&symbol).unwrap_or_default();
+            SymbolReferences {
+                symbol: index.str(symbol).into(),
+                display_name: index.str(index.symbols[&symbol].display_name).into(),
+                kind,
+                definition,
+                count: references.len(),
+                references,
+            }
+        })
+        .collect_vec();
+    symbols.sort_by(|a, b| (a.count, &a.symbol).cmp(&(b.count, &b.symbol)));
+    symbols
+}
+
+/// Number of candidates remaining after a pass.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct PassStats {
+    pub name: String,
+    /// Candidates before the pass.
+    pub input: usize,
+    pub candidates: usize,
+    /// Number of removed candidates by reason, for the passes combining several criteria, e.g.
+    /// the heuristics.
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub reasons: std::collections::BTreeMap<String, usize>,
+}
+
+/// Run the analysis on a workspace like `check`, loading or generating the index, and return
+/// the findings instead of printing them.
+pub fn analyze(options: Options) -> anyhow::Result<Report> {
+    let mut options = options;
+    discover_workspace(&mut options.workspace);
+    let index = load_index(&options)?;
+    let config = Config::load(&options.workspace)?;
+    let metadata = metadata::Workspace::load(&options.workspace);
+    let (findings, passes, listed) = find_unused_listed(&index, &options, &config, &metadata)?;
+    Ok(Report::new(
+        &index, &options, &config, &metadata, &findings, passes, &listed,
+    ))
+}
+
+/// Run the detection passes on an index, returning the findings sorted by path and line, and the
+/// number of candidates after each pass.
+pub fn find_unused<'a>(
+    index: &'a Index,
+    args: &AnalysisFlags,
+    config: &Config,
+) -> anyhow::Result<(Vec<Finding<'a>>, Vec<PassStats>)> {
+    find_unused_with(index, args, config, passes::builtin(), &mut ())
+}
+
+/// Like [`find_unused`], with additional passes, which run after the built-in ones unless ordered
+/// otherwise by the `passes` configuration, and hooks invoked on the candidates.
+pub fn find_unused_with<'a>(
+    index: &'a Index,
+    args: &AnalysisFlags,
+    config: &Config,
+    available: Vec<Box<dyn passes::Pass>>,
+    hooks: &mut dyn passes::Hooks,
+) -> anyhow::Result<(Vec<Finding<'a>>, Vec<PassStats>)> {
+    let metadata = metadata::Workspace::load(&args.workspace);
+    run_passes(index, args, config, &metadata, available, hooks)
+}
+
+/// Like [`find_unused_with`], with the metadata of the workspace already loaded.
+pub(crate) fn run_passes<'a>(
+    index: &'a Index,
+    args: &AnalysisFlags,
+    config: &Config,
+    metadata: &metadata::Workspace,
+    available: Vec<Box<dyn passes::Pass>>,
+    hooks: &mut dyn passes::Hooks,
+) -> anyhow::Result<(Vec<Finding<'a>>, Vec<PassStats>)> {
+    let mut available = available;
+    available.extend(
+        config
+            .filters
+            .iter()
+            .map(|f| Box::new(passes::External::from(f)) as Box<dyn passes::Pass>),
+    );
+    let pipeline = passes::select(config, available)?;
+    let mut passes: Vec<PassStats> = vec![];
+    let mut reasons = HashMap::new();
+    let mut record = |name: &str, candidates| {
+        passes.push(PassStats {
+            name: name.to_string(),
+            input: passes.last().map_or(candidates, |p| p.candidates),
+            candidates,
+            reasons: Default::default(),
+        })
+    };
+    // Record method/function declarations
+    let t = timings::phase("declarations");
+    let mut declarations: passes::Candidates = index
+        .symbols
+        .values()
+        .filter(|s| s.kind == Kind::Method || s.kind == Kind::Function)
+        .filter(|s| hooks.on_candidate(index, s))
+        .map(|s| (s.symbol, s))
+        .collect();
+    debug!("Found {} declarations", declarations.len());
+    drop(t);
+    record("declarations", declarations.len());
+
+    // Definitions in the directories excluded from the workspace are not candidates
+    let excluded = metadata::excluded_dirs(&args.workspace);
+    if !excluded.is_empty() {
+        let in_excluded = defined_in(index, &excluded);
+        passes::filter(index, "workspace.exclude", &mut declarations, hooks, |c| {
+            c.retain(|s, _| !in_excluded.contains(s));
+            Ok(())
+        })?;
+        record("workspace.exclude", declarations.len());
+    }
+    // Nor are the ones of the examples, which are entry points
+    let examples = metadata::examples(&metadata.members);
+    if !examples.is_empty() {
+        let in_examples = defined_in(index, &examples);
+        passes::filter(index, "examples", &mut declarations, hooks, |c| {
+            c.retain(|s, _| !in_examples.contains(s));
+            Ok(())
+        })?;
+        record("examples", declarations.len());
+    }
+    // Nor the macros of the proc-macro crates, which the compiler calls
+    let proc_macro = metadata::proc_macro_crates(&metadata.members);
+    if !proc_macro.is_empty() {
+        let entry_points = proc_macro_entry_points(index, &args.workspace, &proc_macro);
+        passes::filter(
+            index,
+            "proc-macro entry points",
+            &mut declarations,
+            hooks,
+            |c| {
+                c.retain(|s, _| !entry_points.contains(s));
+                Ok(())
+            },
+        )?;
+        record("proc-macro entry points", declarations.len());
+    }
+    let mut exporting: HashSet<String> = args
+        .external_consumers
+        .iter()
+        .map(|c| c.replace('-', "_"))
+        .collect();
+    for c in &exporting {
+        if !index
+            .symbols
+            .values()
+            .any(|s| symbol_package(index.str(s.symbol)).as_ref() == Some(c))
+        {
+            warn!(
+                "No item of the external consumers' crate {} in the index",
+                c
+            );
+        }
+    }
+    if args.keep_public_api {
+        exporting.extend(
+            metadata::publishable_libraries(&metadata.members)
+                .into_iter()
+                .map(|m| m.name.replace('-', "_")),
+        );
+    }
+    if !exporting.is_empty() {
+        let t = timings::phase("public API");
+        let reachable = api::reachable(index, &args.workspace, &exporting);
+        passes::filter(index, "public API", &mut declarations, hooks, |c| {
+            c.retain(|s, _| !reachable.contains(s));
+            Ok(())
+        })?;
+        drop(t);
+        record("public API", declarations.len());
+    }
+
+    let ctx = passes::Context {
+        index,
+        args,
+        config,
+        metadata,
+    };
+    for (i, pass) in pipeline.iter().filter(|p| p.enabled(&ctx)).enumerate() {
+        let t = timings::phase(pass.name().to_string());
+        let removed = passes::filter(index, pass.name(), &mut declarations, hooks, |c| {
+            pass.run(&ctx, c)
+        })?;
+        reasons.insert(pass.name().to_string(), pass.reasons(&ctx, &removed));
+        drop(t);
+        debug!(
+            "Pass {} ({}): {} candidates",
+            i + 1,
+            pass.name(),
+            declarations.len()
+        );
+        record(pass.name(), declarations.len());
+    }
+    // After the other passes, so that the remaining items are unused within the workspace
+    if args.respect_public_api {
+        let t = timings::phase("rustdoc API");
+        let exported = exported_candidates(index, &args.workspace, metadata, &declarations)?;
+        passes::filter(index, api::RUSTDOC_PASS, &mut declarations, hooks, |c| {
+            c.retain(|s, _| !exported.contains(s));
+            Ok(())
+        })?;
+        drop(t);
+        record(api::RUSTDOC_PASS, declarations.len());
+    }
+    if args.transitive {
+        let t = timings::phase("transitive");
+        graph::extend_dead(&ctx, &mut declarations)?;
+        drop(t);
+        debug!("Transitive: {} candidates", declarations.len());
+        record("transitive", declarations.len());
+    }
+
+    // Scope to the requested packages and kinds
+    passes::filter(index, "packages, kinds", &mut declarations, hooks, |c| {
+        c.retain(|_, d| is_selected(index, args, d));
+        Ok(())
+    })?;
+    debug!(
+        "Scoping (packages, kinds): {} candidates",
+        declarations.len()
+    );
+    record("packages, kinds", declarations.len());
+
+    let _t = timings::phase("definition lookup");
+    let mut findings = definitions(index, args, metadata, &mut declarations);
+    let n_findings = findings.len();
+    findings.retain(|f| !config.ignore.contains(&f.fingerprint()));
+    debug!("Ignored {} findings", n_findings - findings.len());
+    record("ignored", findings.len());
+    if let Some(files) = &args.files_from {
+        findings.retain(|f| files.contains(&args.workspace, Path::new(f.path)));
+        record("files from", findings.len());
+    }
+    if args.macro_generated != MacroGenerated::Include {
+        let mut sources = symbols::Sources::new(&args.workspace);
+        let generated: HashSet<&str> = findings
+            .iter()
+            .filter(|f| {
+                sources
+                    .line(f.path, f.span.start_line)
+                    .is_some_and(|l| symbols::is_macro_generated(l, f.span.start_column))
+            })
+            .map(|f| f.symbol)
+            .collect();
+        for s in &generated {
+            if let Some(s) = index.strings.get(s).and_then(|s| index.symbols.get(&s)) {
+                hooks.on_filtered(index, MACRO_GENERATED, s);
+            }
+        }
+        findings.retain(|f| !generated.contains(f.symbol));
+        debug!("Skipped {} macro-generated findings", generated.len());
+        record(MACRO_GENERATED, findings.len());
+    }
+    if args.min_lines > 0 {
+        let lines = size::lines(index, &args.workspace, &findings);
+        let symbols: HashSet<&str> = findings
+            .iter()
+            .filter(|f| lines.get(f.symbol).is_some_and(|n| *n < args.min_lines))
+            .map(|f| f.symbol)
+            .collect();
+        findings.retain(|f| !symbols.contains(f.symbol));
+        debug!(
+            "Skipped {} findings shorter than {} lines",
+            symbols.len(),
+            args.min_lines
+        );
+        record("min lines", findings.len());
+    }
+    if args.rustc_dead_code() {
+        let t = timings::phase("rustc dead code");
+        let dead = rustc::dead_code(args)?;
+        for f in &mut findings {
+            f.rustc_dead_code = dead.contains(&(f.path.to_string(), f.span.start_line));
+        }
+        if args.hide_rustc_dead_code {
+            findings.retain(|f| !f.rustc_dead_code);
+            record("rustc dead code", findings.len());
+        }
+        drop(t);
+    }
+    findings.retain(|f| hooks.on_finding(f));
+    findings.sort_by(|a, b| (a.path, a.span.start_line).cmp(&(b.path, b.span.start_line)));
+    drop(_t);
+    for p in &mut passes {
+        p.reasons = reasons.remove(&p.name).unwrap_or_default();
+        debug!(
+            pass = p.name.as_str(), input = p.input, candidates = p.candidates;
+            "{}: {} -> {} candidates{}",
+            p.name,
+            p.input,
+            p.candidates,
+            p.reasons
+                .iter()
+                .map(|(reason, n)| format!(", {} removed as {}", n, reason))
+                .join("")
+        );
+    }
+    info!(findings = findings.len(); "Found {} possibly unused functions", findings.len());
+    Ok((findings, passes))
+}
+
+/// Whether a symbol is in the requested packages and of the requested kinds.
+fn is_selected(index: &Index, args: &AnalysisFlags, symbol: &index::Symbol) -> bool {
+    let package = || symbol_package(index.str(symbol.symbol));
+    (args.packages.is_empty()
+        || package().is_some_and(|p| args.packages.iter().any(|q| q.replace('-', "_") == p)))
+        && ItemKind::from_scip(symbol.kind).is_some_and(|k| args.kinds.contains(&k))
+}
+
+/// Name of the step removing the macro-generated findings, with `--macro-generated`.
+pub(crate) const MACRO_GENERATED: &str = "macro-generated";
+
+/// Items removed by a pass but listed separately from the findings, as they are otherwise unused
+/// within the workspace.
+#[derive(Default)]
+pub struct Listed<'a> {
+    /// Part of the public API documented by rustdoc, with `--respect-public-api`.
+    pub externally_visible: Vec<Finding<'a>>,
+    /// Only named in string literals, see [`passes::Strings`].
+    pub string_only: Vec<Finding<'a>>,
+    /// Only mentioned in the documentation, see [`passes::Documentation`].
+    pub documentation_only: Vec<Finding<'a>>,
+    /// Inherent methods named like the method of a trait that their type does not implement,
+    /// see [`passes::Heuristics`].
+    pub orphaned_trait_methods: Vec<Finding<'a>>,
+    /// Generated by macro invocations, with `--macro-generated separate`.
+    pub macro_generated: Vec<Finding<'a>>,
+    /// Only used from the JavaScript sources, see [`passes::Bindings`].
+    pub javascript_only: Vec<Finding<'a>>,
+    /// Only used from the Python sources.
+    pub python_only: Vec<Finding<'a>>,
+    /// Only used from the C and C++ sources.
+    pub c_only: Vec<Finding<'a>>,
+}
+
+/// Records the candidates removed by the passes whose items are [`Listed`].
+struct ListedHooks {
+    externally_visible: api::ExternallyVisible,
+    string_only: passes::RemovedBy,
+    documentation_only: passes::RemovedBy,
+    /// Removed by the heuristics, among which the orphaned trait methods.
+    heuristics: passes::RemovedBy,
+    macro_generated: passes::RemovedBy,
+    javascript_only: passes::RemovedBy,
+    python_only: passes::RemovedBy,
+    c_only: passes::RemovedBy,
+}
+
+impl ListedHooks {
+    fn new() -> Self {
+        let removed_by = |pass| passes::RemovedBy(pass, HashSet::new());
+        Self {
+            externally_visible: api::ExternallyVisible::default(),
+            string_only: removed_by(passes::STRINGS_PASS),
+            documentation_only: removed_by(passes::DOCUMENTATION_PASS),
+            heuristics: removed_by("heuristics"),
+            macro_generated: removed_by(MACRO_GENERATED),
+            javascript_only: removed_by(bindings::JAVASCRIPT.pass),
+            python_only: removed_by(bindings::PYTHON.pass),
+            c_only: removed_by(bindings::C.pass),
+        }
+    }
+}
+
+impl passes::Hooks for ListedHooks {
+    fn on_filtered(&mut self, index: &Index, pass: &str, candidate: &index::Symbol) {
+        self.externally_visible.on_filtered(index, pass, candidate);
+        for hook in [
+            &mut self.string_only,
+            &mut self.documentation_only,
+            &mut self.heuristics,
+            &mut self.macro_generated,
+            &mut self.javascript_only,
+            &mut self.python_only,
+            &mut self.c_only,
+        ] {
+            hook.on_filtered(index, pass, candidate);
+        }
+    }
+}
+
+/// Like [`find_unused`], also returning the items that would be reported without
+/// `--respect-public-api`, as they are part of the public API documented by rustdoc, the ones
+/// only named in string literals or in the documentation, the orphaned trait methods, the
+/// macro-generated ones with `--macro-generated separate`, and the ones only used from other
+/// languages.
+pub(crate) fn find_unused_listed<'a>(
+    index: &'a Index,
+    args: &AnalysisFlags,
+    config: &Config,
+    metadata: &metadata::Workspace,
+) -> anyhow::Result<(Vec<Finding<'a>>, Vec<PassStats>, Listed<'a>)> {
+    let mut hooks = ListedHooks::new();
+    let (findings, passes) =
+        run_passes(index, args, config, metadata, passes::builtin(), &mut hooks)?;
+    let listed = |symbols: &HashSet<Sym>| {
+        let mut candidates: passes::Candidates = symbols
+            .iter()
+            .filter_map(|s| Some((*s, index.symbols.get(s)?)))
+            .filter(|(_, s)| is_selected(index, args, s))
+            .collect();
+        let mut items = definitions(index, args, metadata, &mut candidates);
+        retain_listed(&mut items, args, config);
+        items.sort_by(|a, b| (a.path, a.span.start_line).cmp(&(b.path, b.span.start_line)));
+        items
+    };
+    let listed = Listed {
+        externally_visible: listed(&hooks.externally_visible.0),
+        string_only: listed(&hooks.string_only.1),
+        documentation_only: listed(&hooks.documentation_only.1),
+        orphaned_trait_methods: listed(&passes::Heuristics::orphaned(
+            &passes::Context {
+                index,
+                args,
+                config,
+                metadata,
+            },
+            &hooks.heuristics.1,
+        )),
+        macro_generated: if args.macro_generated == MacroGenerated::Separate {
+            listed(&hooks.macro_generated.1)
+        } else {
+            vec![]
+        },
+        javascript_only: listed(&hooks.javascript_only.1),
+        python_only: listed(&hooks.python_only.1),
+        c_only: listed(&hooks.c_only.1),
+    };
+    Ok((findings, passes, listed))
+}
+
+/// Drop the listed items ignored in the configuration, or outside of `--files-from`.
+fn retain_listed(items: &mut Vec<Finding>, args: &AnalysisFlags, config: &Config) {
+    items.retain(|f| !config.ignore.contains(&f.fingerprint()));
+    if let Some(files) = &args.files_from {
+        items.retain(|f| files.contains(&args.workspace, Path::new(f.path)));
+    }
+}
+
+/// Candidates documented by rustdoc as exported, matched by name and position.
+fn exported_candidates(
+    index: &Index,
+    workspace: &std::path::Path,
+    metadata: &metadata::Workspace,
+    candidates: &passes::Candidates,
+) -> anyhow::Result<HashSet<Sym>> {
+    let mut exported: HashMap<(&str, &str), Vec<(usize, usize)>> = HashMap::new();
+    let items = rustdoc::exported(workspace, &metadata.members)?;
+    for e in &items {
+        exported
+            .entry((e.path.as_str(), e.name.as_str()))
+            .or_default()
+            .push(e.lines);
+    }
+    Ok(index
+        .occurrences
+        .iter()
+        .filter(|o| o.is_definition())
+        .filter_map(|o| {
+            let candidate = candidates.get(&o.symbol)?;
+            let key = (
+                index.documents[o.document].as_str(),
+                index.str(candidate.display_name),
+            );
+            let line = o.span().start_line;
+            exported
+                .get(&key)?
+                .iter()
+                .any(|(begin, end)| (*begin..=*end).contains(&line))
+                .then_some(o.symbol)
+        })
+        .collect())
+}
+
+/// Findings of the candidates, with their references if `--max-uses` is set, in the order of
+/// the index. The candidates without definition are left.
+fn definitions<'a>(
+    index: &'a Index,
+    args: &AnalysisFlags,
+    metadata: &metadata::Workspace,
+    declarations: &mut passes::Candidates,
+) -> Vec<Finding<'a>> {
+    // Find occurrence with definition to get the position in the file
+    // TODO: Doing that earlier woud allow detecting the #[test], #[main], etc.
+    let mut findings = vec![];
+    // Symbols of the findings, in the same order
+    let mut symbols = vec![];
+    let bin_only: HashSet<String> = metadata::bin_only_crates(&metadata.members)
+        .into_iter()
+        .map(|m| m.name.replace('-', "_"))
+        .collect();
+    let proc_macro: HashSet<String> = metadata::proc_macro_crates(&metadata.members)
+        .into_iter()
+        .map(|m| m.name.replace('-', "_"))
+        .collect();
+    for o in &index.occurrences {
+        if !o.is_definition() {
+            continue;
+        }
+        if let Some(s) = declarations.remove(&o.symbol) {
+            symbols.push(s.symbol);
+            findings.push(Finding {
+                path: &index.documents[o.document],
+                symbol: index.str(s.symbol),
+                display_name: index.str(s.display_name),
+                kind: ItemKind::from_scip(s.kind).unwrap(),
+                span: o.span(),
+                references: vec![],
+                bin_only: symbol_package(index.str(s.symbol))
+                    .is_some_and(|p| bin_only.contains(&p)),
+                proc_macro: symbol_package(index.str(s.symbol))
+                    .is_some_and(|p| proc_macro.contains(&p)),
+                rustc_dead_code: false,
+            });
+        }
+    }
+    if args.max_uses > 0 {
+        let selected: HashSet<Sym> = symbols.iter().copied().collect();
+        let mut locations = locations(index, |s| selected.contains(&s));
+        for (f, s) in findings.iter_mut().zip(symbols) {
+            if let Some((_, references)) = locations.remove(&s) {
+                f.references = references;
+            }
+        }
+    }
+    findings
+}
+
+/// Whether a document is inside one of the given files or directories.
+fn is_in(paths: &[PathBuf], document: &str) -> bool {
+    let document = std::path::Path::new(document);
+    paths.iter().any(|p| document.starts_with(p))
+}
+
+/// Symbols defined in the given files or directories, relative to the workspace root.
+fn defined_in(index: &Index, paths: &[PathBuf]) -> HashSet<Sym> {
+    index
+        .occurrences
+        .iter()
+        .filter(|o| o.is_definition() && is_in(paths, &index.documents[o.document]))
+        .map(|o| o.symbol)
+        .collect()
+}
+
+/// Functions of proc-macro crates defining a macro, i.e. with a `#[proc_macro]`,
+/// `#[proc_macro_derive]` or `#[proc_macro_attribute]` attribute, which are only used by the
+/// compiler.
+fn proc_macro_entry_points(
+    index: &Index,
+    workspace: &Path,
+    proc_macro: &[&metadata::Member],
+) -> HashSet<Sym> {
+    let packages: HashSet<String> = proc_macro
+        .iter()
+        .map(|m| m.name.replace('-', "_"))
+        .collect();
+    let mut sources = symbols::Sources::new(workspace);
+    index
+        .occurrences
+        .iter()
+        .filter(|o| o.is_definition())
+        .filter(|o| {
+            index.symbols.get(&o.symbol).is_some_and(|s| {
+                s.kind == Kind::Function
+                    && symbol_package(index.str(s.symbol)).is_some_and(|p| packages.contains(&p))
+            })
+        })
+        .filter(|o| {
+            has_attribute(&mut sources, &index.documents[o.document], o, |name| {
+                matches!(
+                    name,
+                    "proc_macro" | "proc_macro_derive" | "proc_macro_attribute"
+                )
+            })
+        })
+        .map(|o| o.symbol)
+        .collect()
+}
+
+/// Functions and methods that are only referenced from the examples, e.g. helpers for demos,
+/// sorted by path and line. Unlike the findings, they are still considered used.
+///
+/// The [`passes::Heuristics`] are applied to them, and their references are the ones from the
+/// examples.
+pub fn example_only<'a>(
+    index: &'a Index,
+    args: &AnalysisFlags,
+    config: &Config,
+    metadata: &metadata::Workspace,
+) -> Vec<Finding<'a>> {
+    let examples = metadata::examples(&metadata.members);
+    if examples.is_empty() {
+        return vec![];
+    }
+    only_used_from(index, args, config, |definition, references| {
+        !is_in(&examples, &index.documents[definition.document])
+            && references
+                .iter()
+                .all(|r| is_in(&examples, &index.documents[r.document]))
+    })
+}
+
+/// Functions and methods that are only referenced from the `#[test]` functions of their own file,
+/// e.g. production code kept alive by its unit tests, sorted by path and line. Unlike the
+/// findings, they are still considered used.
+///
+/// References are attributed to the innermost definition enclosing them, which requires a SCIP
+/// index from a recent rust-analyzer, and test functions are recognized by an attribute named
+/// like `test`, e.g. `#[test]`, `#[tokio::test]` or `#[rstest]`. The [`passes::Heuristics`] are
+/// applied to them.
+pub fn self_tested<'a>(
+    index: &'a Index,
+    args: &AnalysisFlags,
+    config: &Config,
+) -> Vec<Finding<'a>> {
+    let mut definitions: HashMap<usize, Vec<(Span, &Occurrence)>> = HashMap::new();
+    for o in &index.occurrences {
+        if let (true, Some(span)) = (o.is_definition(), o.enclosing_span()) {
+            definitions.entry(o.document).or_default().push((span, o));
+        }
+    }
+    let mut sources = symbols::Sources::new(&args.workspace);
+    let mut tests: HashMap<Sym, bool> = HashMap::new();
+    only_used_from(index, args, config, |definition, references| {
+        references.iter().all(|r| {
+            let span = r.span();
+            let Some((_, enclosing)) = (r.document == definition.document)
+                .then(|| definitions.get(&r.document))
+                .flatten()
+                .into_iter()
+                .flatten()
+                .filter(|(d, _)| d.contains(span.start_line, span.start_column))
+                .max_by_key(|(d, _)| (d.start_line, d.start_column))
+            else {
+                return false;
+            };
+            *tests.entry(enclosing.symbol).or_insert_with(|| {
+                index
+                    .symbols
+                    .get(&enclosing.symbol)
+                    .is_some_and(|s| s.kind == Kind::Function)
+                    && has_attribute(
+                        &mut sources,
+                        &index.documents[enclosing.document],
+                        enclosing,
+                        |name| name.contains("test"),
+                    )
+            })
+        })
+    })
+}
+
+/// Whether a definition has an attribute whose name, without its path, satisfies a predicate, on
+/// the lines above it.
+fn has_attribute(
+    sources: &mut symbols::Sources,
+    path: &str,
+    definition: &Occurrence,
+    predicate: impl Fn(&str) -> bool,
+) -> bool {
+    attributes(sources, path, definition).iter().any(|a| {
+        let name = a.split(['(', ']', ' ']).next().unwrap_or_default();
+        name.rsplit("::").next().is_some_and(&predicate)
+    })
+}
+
+/// Attributes on the lines above an occurrence, without their `#[`, e.g. `cfg(test)]`.
+fn attributes(sources: &mut symbols::Sources, path: &str, occurrence: &Occurrence) -> Vec<String> {
+    attributes_above(sources, path, occurrence.span().start_line)
+}
+
+/// Attributes on the lines above a line, without their `#[`.
+pub(crate) fn attributes_above(
+    sources: &mut symbols::Sources,
+    path: &str,
+    mut line: usize,
+) -> Vec<String> {
+    let mut attributes = vec![];
+    while line > 0 {
+        line -= 1;
+        let Some(l) = sources.line(path, line).map(str::trim_start) else {
+            break;
+        };
+        if let Some(attribute) = l.strip_prefix("#[") {
+            attributes.push(attribute.to_string());
+        } else if !l.starts_with("//") {
+            break;
+        }
+    }
+    attributes
+}
+
+/// Functions and methods declared with a visibility in modules gated by `#[cfg(test)]`, and only
+/// referenced within their module, sorted by path and line. Their visibility is needless, as
+/// the module is only compiled with the tests, and they can be made private. Unlike the
+/// findings, they may be used, and the "test" heuristic does not apply to them.
+///
+/// The gated modules are recognized by a `#[cfg(test)]` attribute above their `mod` item, and
+/// the modules nested in them are gated as well.
+pub fn needless_pub<'a>(
+    index: &'a Index,
+    args: &AnalysisFlags,
+    config: &Config,
+) -> Vec<Finding<'a>> {
+    let mut sources = symbols::Sources::new(&args.workspace);
+    let mut gated: HashSet<&str> = HashSet::new();
+    // Document of each module, and range for the inline ones
+    let mut scopes: HashMap<&str, (usize, Option<Span>)> = HashMap::new();
+    let mut definitions: HashMap<Sym, &Occurrence> = HashMap::new();
+    let mut references: HashMap<Sym, Vec<&Occurrence>> = HashMap::new();
+    for o in &index.occurrences {
+        if o.is_definition() {
+            definitions.entry(o.symbol).or_insert(o);
+        } else {
+            references.entry(o.symbol).or_default().push(o);
+        }
+        let Some(module) = index
+            .symbols
+            .get(&o.symbol)
+            .filter(|s| s.kind == Kind::Module)
+        else {
+            continue;
+        };
+        let path = &index.documents[o.document];
+        let declaration = format!("mod {}", index.str(module.display_name));
+        if !sources
+            .line(path, o.span().start_line)
+            .is_some_and(|l| l.contains(&declaration))
+        {
+            if o.is_definition() {
+                // Module in its own file
+                scopes
+                    .entry(index.str(o.symbol))
+                    .or_insert((o.document, None));
+            }
+            continue;
+        }
+        if o.is_definition() {
+            scopes.insert(index.str(o.symbol), (o.document, o.enclosing_span()));
+        }
+        if attributes(&mut sources, path, o)
+            .iter()
+            .any(|a| a.replace(' ', "").starts_with("cfg(test)"))
+        {
+            gated.insert(index.str(o.symbol));
+        }
+    }
+    if gated.is_empty() {
+        return vec![];
+    }
+    let mut findings = index
+        .symbols
+        .values()
+        .filter(|s| is_selected(index, args, s))
+        .filter_map(|s| {
+            let definition = definitions.get(&s.symbol)?;
+            let symbol = index.str(s.symbol);
+            // Innermost module, and whether it is nested in a gated one
+            let mut module = None;
+            let mut parent = api::parent(symbol);
+            let mut in_gated = false;
+            while let Some(p) = parent {
+                if module.is_none() {
+                    module = scopes.get_key_value(p.as_str()).map(|(m, s)| (*m, *s));
+                }
+                in_gated |= gated.contains(p.as_str());
+                parent = api::parent(&p);
+            }
+            let (_, (document, span)) = module.filter(|_| in_gated)?;
+            let path = &index.documents[definition.document];
+            let line = sources.line(path, definition.span().start_line)?;
+            if symbols::visibility(line, definition.span().start_column) == "private" {
+                return None;
+            }
+            let references = references
+                .get(&s.symbol)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let within = references.iter().all(|r| {
+                let position = r.span();
+                r.document == document
+                    && span.is_none_or(|m| m.contains(position.start_line, position.start_column))
+            });
+            within.then(|| Finding {
+                path,
+                symbol,
+                display_name: index.str(s.display_name),
+                kind: ItemKind::from_scip(s.kind).unwrap(),
+                span: definition.span(),
+                references: references
+                    .iter()
+                    .map(|r| Location {
+                        path: index.documents[r.document].clone(),
+                        span: r.span(),
+                    })
+                    .collect(),
+                bin_only: false,
+                proc_macro: false,
+                rustc_dead_code: false,
+            })
+        })
+        .collect_vec();
+    retain_listed(&mut findings, args, config);
+    findings.sort_by(|a, b| (a.path, a.span.start_line).cmp(&(b.path, b.span.start_line)));
+    findings
+}
+
+/// Referenced functions and methods passing the [`passes::Heuristics`], whose definition and
+/// references satisfy a predicate, sorted by path and line. The references are kept.
+fn only_used_from<'a>(
+    index: &'a Index,
+    args: &AnalysisFlags,
+    config: &Config,
+    mut predicate: impl FnMut(&Occurrence, &[&Occurrence]) -> bool,
+) -> Vec<Finding<'a>> {
+    let mut candidates: passes::Candidates = index
+        .symbols
+        .values()
+        .filter(|s| is_selected(index, args, s))
+        .map(|s| (s.symbol, s))
+        .collect();
+    // The heuristics do not depend on the metadata
+    let metadata = metadata::Workspace::default();
+    let ctx = passes::Context {
+        index,
+        args,
+        config,
+        metadata: &metadata,
+    };
+    if passes::Pass::run(&passes::Heuristics, &ctx, &mut candidates).is_err() {
+        return vec![];
+    }
+    let mut definitions = HashMap::new();
+    let mut references: HashMap<Sym, Vec<&Occurrence>> = HashMap::new();
+    for o in &index.occurrences {
+        if !candidates.contains_key(&o.symbol) {
+            continue;
+        }
+        if o.is_definition() {
+            definitions.entry(o.symbol).or_insert(o);
+        } else {
+            references.entry(o.symbol).or_default().push(o);
+        }
+    }
+    let mut findings = references
+        .into_iter()
+        .filter_map(|(symbol, references)| {
+            let definition = definitions.get(&symbol)?;
+            if !predicate(definition, &references) {
+                return None;
+            }
+            let s = candidates[&symbol];
+            Some(Finding {
+                path: &index.documents[definition.document],
+                symbol: index.str(s.symbol),
+                display_name: index.str(s.display_name),
+                kind: ItemKind::from_scip(s.kind)?,
+                span: definition.span(),
+                references: references
+                    .iter()
+                    .map(|r| Location {
+                        path: index.documents[r.document].clone(),
+                        span: r.span(),
+                    })
+                    .collect(),
+                bin_only: false,
+                proc_macro: false,
+                rustc_dead_code: false,
+            })
+        })
+        .collect_vec();
+    retain_listed(&mut findings, args, config);
+    findings.sort_by(|a, b| (a.path, a.span.start_line).cmp(&(b.path, b.span.start_line)));
+    findings
+}
+
+/// Cargo feature flags with which a single generated SCIP index was generated.
+fn generation_features(args: &AnalysisFlags) -> Option<String> {
+    if args.input != Input::Scip {
+        return None;
+    }
+    match scip_paths(args).ok()?.as_slice() {
+        [scip] => indexer::recorded_features(&args.workspace, scip),
+        _ => None,
+    }
+}
+
+/// Print the findings, grouped by file, with the number of lines of their definitions if known.
+pub fn report(
+    args: &AnalysisFlags,
+    findings: &[Finding],
+    explanations: &HashMap<&str, Vec<explain::Explanation>>,
+    lines: &HashMap<&str, usize>,
+    out: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    let workspace = &args.workspace;
+    // Results depend on the features the index was generated with
+    if let (false, Some(features)) = (findings.is_empty(), generation_features(args)) {
+        writeln!(
+            out,
+            "{}",
+            format!("Index generated with {}", features).dimmed()
+        )?;
+        writeln!(out)?;
+    }
+    if !findings.is_empty() && metadata::is_standalone(workspace) {
+        writeln!(
+            out,
+            "{}",
+            "Standalone crate: only its own targets (library, binaries, tests, examples) are searched for usages"
+                .dimmed()
+        )?;
+        writeln!(out)?;
+    }
+    let annotations = Annotations {
+        blames: if args.blame {
+            blame::blame(workspace, &findings.iter().collect_vec())
+        } else {
+            HashMap::new()
+        },
+        explanations,
+        lines,
+        fingerprints: args.show_fingerprints,
+    };
+    if args.group_by == GroupBy::Owner {
+        let owners = owners::CodeOwners::load(workspace, args.codeowners.as_deref())?
+            .ok_or_else(|| anyhow::anyhow!("No CODEOWNERS file found, pass --codeowners"))?;
+        let mut by_owner: BTreeMap<Option<&str>, Vec<&Finding>> = BTreeMap::new();
+        for f in findings {
+            match owners.owners(f.path) {
+                [] => by_owner.entry(None).or_default().push(f),
+                o => o
+                    .iter()
+                    .for_each(|o| by_owner.entry(Some(o)).or_default().push(f)),
+            }
+        }
+        // Unowned findings last
+        let unowned = by_owner.remove(&None);
+        let groups = by_owner.into_iter().chain(unowned.map(|f| (None, f)));
+        for (owner, findings) in groups {
+            writeln!(
+                out,
+                "{} {}",
+                owner.unwrap_or("(no owner)").bold(),
+                match findings.len() {
+                    1 => "(1 finding)".to_string(),
+                    n => format!("({} findings)", n),
+                }
+                .dimmed()
+            )?;
+            writeln!(out)?;
+            report_files(workspace, &findings, &annotations, out)?;
+        }
+        return Ok(());
+    }
+    if args.group_by == GroupBy::Documentation {
+        let documented = documented(workspace, findings);
+        let (documented, undocumented): (Vec<&Finding>, Vec<&Finding>) =
+            findings.iter().partition(|f| documented.contains(f.symbol));
+        for (title, findings) in [("Undocumented", undocumented), ("Documented", documented)] {
+            if findings.is_empty() {
+                continue;
+            }
+            writeln!(
+                out,
+                "{} {}",
+                title.bold(),
+                match findings.len() {
+                    1 => "(1 finding)".to_string(),
+                    n => format!("({} findings)", n),
+                }
+                .dimmed()
+            )?;
+            writeln!(out)?;
+            report_files(workspace, &findings, &annotations, out)?;
+        }
+        return Ok(());
+    }
+    report_files(workspace, &findings.iter().collect_vec(), &annotations, out)
+}
+
+/// Symbols of the findings whose definition has a doc comment.
+fn documented<'a>(workspace: &Path, findings: &[Finding<'a>]) -> HashSet<&'a str> {
+    let mut sources = symbols::Sources::new(workspace);
+    findings
+        .iter()
+        .filter(|f| symbols::is_documented(&mut sources, f.path, f.span.start_line))
+        .map(|f| f.symbol)
+        .collect()
+}
+
+/// Optional information printed under the findings.
+struct Annotations<'a> {
+    /// By path and line.
+    blames: HashMap<(String, usize), blame::Blame>,
+    /// By symbol.
+    explanations: &'a HashMap<&'a str, Vec<explain::Explanation>>,
+    /// Number of lines of the definitions, by symbol.
+    lines: &'a HashMap<&'a str, usize>,
+    /// With `--show-fingerprints`.
+    fingerprints: bool,
+}
+
+/// Print the findings grouped by file.
+fn report_files(
+    workspace: &std::path::Path,
+    findings: &[&Finding],
+    annotations: &Annotations,
+    out: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    for (path, findings) in &findings.iter().group_by(|f| f.path) {
+        let full_path = workspace.join(path);
+        if !full_path.exists() {
+            warn!("{} not found, is the SCIP file up-to-date?", path);
+            continue;
+        }
+        let lines = read_source(&full_path)?;
+        let lines: Vec<&str> = lines.lines().collect();
+        let findings = findings.collect_vec();
+        if findings.iter().all(|f| f.bin_only) {
+            writeln!(
+                out,
+                "{} {}",
+                path.yellow(),
+                "(binary crate: make private rather than remove from the API)".dimmed()
+            )?;
+        } else if findings.iter().all(|f| f.proc_macro) {
+            writeln!(
+                out,
+                "{} {}",
+                path.yellow(),
+                "(proc-macro crate: lower confidence, as only the references within the crate count)"
+                    .dimmed()
+            )?;
+        } else {
+            writeln!(out, "{}", path.yellow())?;
+        }
+        for f in findings {
+            let size = match annotations.lines.get(f.symbol) {
+                Some(1) => " (1 line)".to_string(),
+                Some(n) => format!(" ({} lines)", n),
+                None => String::new(),
+            };
+            let rustc = if f.rustc_dead_code {
+                " (also reported by rustc)"
+            } else {
+                ""
+            };
+            let fingerprint = if annotations.fingerprints {
+                format!(" {}", f.fingerprint())
+            } else {
+                String::new()
+            };
+            writeln!(
+                out,
+                "{:<4} {}{}{}{}",
+                (f.span.start_line + 1).to_string().blue(),
+                highlight(lines.get(f.span.start_line).copied().unwrap_or_default(), f),
+                fingerprint.dimmed(),
+                size.dimmed(),
+                rustc.dimmed()
+            )?;
+            if let Some(b) = annotations
+                .blames
+                .get(&(f.path.to_string(), f.span.start_line))
+            {
+                writeln!(
+                    out,
+                    "     {}",
+                    format!("{}, {} ({})", b.author, b.date(), &b.commit[..8]).dimmed()
+                )?;
+            }
+            for e in annotations.explanations.get(f.symbol).into_iter().flatten() {
+                writeln!(
+                    out,
+                    "     {} {}",
+                    format!("{}:", e.pass).dimmed(),
+                    e.evidence
+                )?;
+            }
+            for r in &f.references {
+                writeln!(
+                    out,
+                    "     {} {}:{}:{}",
+                    "used at".dimmed(),
+                    r.path,
+                    r.span.start_line + 1,
+                    r.span.start_column + 1
+                )?;
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Line of a finding with its name underlined, if it is still at the position of the index.
+fn highlight(line: &str, f: &Finding) -> String {
+    let (start, end) = (f.span.start_column, f.span.end_column);
+    match line.get(start..end) {
+        Some(name) if f.span.start_line == f.span.end_line && name == f.display_name => {
+            format!("{}{}{}", &line[..start], name.underline(), &line[end..])
+        }
+        _ => line.to_string(),
+    }
+}
+
+/// Print informational items, which are not findings, under a title.
+fn report_list(title: &str, items: &[Finding], out: &mut dyn std::io::Write) -> anyhow::Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "{}", title.yellow())?;
+    for f in items {
+        let uses = match f.references.len() {
+            0 => String::new(),
+            n => format!(" ({} uses)", n),
+        };
+        writeln!(
+            out,
+            "{}:{} {}{}",
+            f.path,
+            (f.span.start_line + 1).to_string().blue(),
+            index::rust_path(f.symbol).unwrap_or_else(|| f.display_name.into()),
+            uses.dimmed()
+        )?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Print the clusters of several findings.
+fn report_clusters(
+    clusters: &[graph::Cluster],
+    out: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    let clusters = clusters
+        .iter()
+        .filter(|c| c.symbols.len() > 1)
+        .collect_vec();
+    if clusters.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "{}",
+        "Clusters of findings referencing each other".yellow()
+    )?;
+    for c in clusters {
+        let names = c
+            .symbols
+            .iter()
+            .map(|s| index::rust_path(s).unwrap_or_else(|| s.clone()))
+            .join(", ");
+        writeln!(out, "{:>6} {}", format!("{}L", c.lines).blue(), names)?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Print the duplicate and shadowed re-exports.
+fn report_reexports(
+    reexports: &[api::Reexport],
+    out: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    if reexports.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "{}", "Duplicate and shadowed re-exports".yellow())?;
+    let position = |l: &Location| format!("{}:{}", l.path, l.span.start_line + 1);
+    for r in reexports {
+        let path = index::rust_path(&r.symbol).unwrap_or_else(|| r.symbol.clone());
+        match r.kind {
+            api::ReexportKind::Duplicate => writeln!(
+                out,
+                "{:>9} {} re-exported at {}",
+                "duplicate".blue(),
+                path,
+                r.locations.iter().map(position).join(", ")
+            )?,
+            api::ReexportKind::Shadowed => writeln!(
+                out,
+                "{:>9} {} from the glob at {}, by {}",
+                "shadowed".blue(),
+                path,
+                position(&r.locations[0]),
+                position(&r.locations[1])
+            )?,
+            api::ReexportKind::Unused => writeln!(
+                out,
+                "{:>9} {}::* at {}, none of its items is used",
+                "unused".blue(),
+                path,
+                position(&r.locations[0])
+            )?,
+        }
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Print the report in the requested format.
+fn write_report(
+    ctx: &passes::Context,
+    format: ReportFormat,
+    findings: &[Finding],
+    passes: Vec<PassStats>,
+    listed: &Listed,
+    out: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    let passes::Context {
+        index,
+        args,
+        config,
+        metadata,
+    } = *ctx;
+    match format {
+        ReportFormat::Text | ReportFormat::Snippets => {
+            let explanations = if args.explain {
+                explain::explain(index, args, metadata, findings, &passes)?
+            } else {
+                HashMap::new()
+            };
+            let lines = size::lines(index, &args.workspace, findings);
+            if format == ReportFormat::Snippets {
+                snippet::report(args, findings, &passes, &explanations, &lines, out)?;
+            } else {
+                report(args, findings, &explanations, &lines, out)?;
+            }
+            if !lines.is_empty() {
+                writeln!(
+                    out,
+                    "{}",
+                    format!(
+                        "≈{} deletable lines in {} findings",
+                        size::separated(lines.values().sum()),
+                        findings.len()
+                    )
+                    .bold()
+                )?;
+                writeln!(out)?;
+            }
+            if args.rustc_dead_code() && !args.hide_rustc_dead_code {
+                let rustc = findings.iter().filter(|f| f.rustc_dead_code).count();
+                writeln!(
+                    out,
+                    "{}",
+                    format!(
+                        "{} of the {} findings also reported by rustc's dead_code lint, {} not seen by the compiler",
+                        rustc,
+                        findings.len(),
+                        findings.len() - rustc
+                    )
+                    .bold()
+                )?;
+                writeln!(out)?;
+            }
+            if args.example_only {
+                report_list(
+                    "Only used in examples",
+                    &example_only(index, args, config, metadata),
+                    out,
+                )?;
+            }
+            if args.self_tested {
+                report_list(
+                    "Only used in their own tests",
+                    &self_tested(index, args, config),
+                    out,
+                )?;
+            }
+            if args.needless_pub {
+                report_list(
+                    "Needless pub in test modules (can be private)",
+                    &needless_pub(index, args, config),
+                    out,
+                )?;
+            }
+            report_list(
+                "Only named in string literals (low confidence)",
+                &listed.string_only,
+                out,
+            )?;
+            report_list(
+                "Only used in the documentation",
+                &listed.documentation_only,
+                out,
+            )?;
+            report_list(
+                "Methods of traits no longer implemented",
+                &listed.orphaned_trait_methods,
+                out,
+            )?;
+            report_list("Generated by macros", &listed.macro_generated, out)?;
+            report_list(
+                &format!("Only used from {}", bindings::JAVASCRIPT.label),
+                &listed.javascript_only,
+                out,
+            )?;
+            report_list(
+                &format!("Only used from {}", bindings::PYTHON.label),
+                &listed.python_only,
+                out,
+            )?;
+            report_list(
+                &format!("Only used from {}", bindings::C.label),
+                &listed.c_only,
+                out,
+            )?;
+            report_list(
+                "Externally visible, unused internally",
+                &listed.externally_visible,
+                out,
+            )?;
+            if args.transitive {
+                report_clusters(&graph::clusters(index, findings), out)?;
+            }
+            if args.reexports {
+                report_reexports(&api::reexports(index, &args.workspace), out)?;
+            }
+            Ok(())
+        }
+        ReportFormat::Json => {
+            let report = Report::new(index, args, config, metadata, findings, passes, listed);
+            serde_json::to_writer_pretty(&mut *out, &report)?;
+            writeln!(out)?;
+            Ok(())
+        }
+    }
+}
+
+fn check(args: CheckFlags) -> anyhow::Result<()> {
+    let CheckFlags {
+        analysis: args,
+        tui,
+        daemon,
+        format,
+        print_schema,
+        emit_graph,
+        graph_format,
+        graph_findings,
+        open,
+        record_history,
+        emit_metrics,
+        emit_badge,
+        badge_thresholds,
+        why,
+    } = args;
+    let start = std::time::Instant::now();
+    if print_schema {
+        print!("{}", REPORT_SCHEMA);
+        return Ok(());
+    }
+    // The daemon only returns the report, the other outputs are produced locally
+    #[cfg(unix)]
+    let local = tui
+        || open.is_some()
+        || record_history.is_some()
+        || emit_metrics.is_some()
+        || emit_badge.is_some()
+        || why.is_some();
+    #[cfg(unix)]
+    if daemon {
+        return daemon::serve(args);
+    } else if !local {
+        if let Some(res) = daemon::forward(&args) {
+            return res;
+        }
+    }
+    #[cfg(not(unix))]
+    anyhow::ensure!(!daemon, "--daemon is only supported on Unix");
+
+    let index = load_index(&args)?;
+    let config = Config::load(&args.workspace)?;
+    if let Some(query) = &why {
+        return why::why(&index, &args, &config, query, &mut std::io::stdout().lock());
+    }
+    let metadata = metadata::Workspace::load(&args.workspace);
+    let (findings, passes, listed) = find_unused_listed(&index, &args, &config, &metadata)?;
+    if let Some(path) = &emit_graph {
+        let _t = timings::phase("graph");
+        let mut graph = graph::Graph::build(&index);
+        if graph_findings {
+            graph.neighborhood(&index, &findings);
+        }
+        let format = graph_format.unwrap_or_else(|| graph::Format::from_path(path));
+        graph::write(&index, &graph, &findings, path, format)?;
+        info!("Wrote the reference graph to {:?}", path);
+    }
+    if let Some(path) = &record_history {
+        history::record(&args.workspace, path, &findings)?;
+    }
+    if let Some(path) = &emit_metrics {
+        metrics::write(&args, &metadata, &findings, start.elapsed(), path)?;
+    }
+    if let Some(path) = &emit_badge {
+        metrics::write_badge(findings.len(), &badge_thresholds, path)?;
+    }
+    if tui {
+        return tui::run(&args.workspace, findings);
+    }
+    let t = timings::phase("reporting");
+    let ctx = passes::Context {
+        index: &index,
+        args: &args,
+        config: &config,
+        metadata: &metadata,
+    };
+    write_report(
+        &ctx,
+        format,
+        &findings,
+        passes,
+        &listed,
+        &mut std::io::stdout().lock(),
+    )?;
+    drop(t);
+    if let Some(n) = open {
+        let f = n
+            .checked_sub(1)
+            .and_then(|i| findings.get(i))
+            .ok_or_else(|| anyhow::anyhow!("No finding {}, out of {}", n, findings.len()))?;
+        editor::open(
+            &args.workspace.join(f.path),
+            f.span.start_line + 1,
+            f.span.start_column + 1,
+        )?;
+    }
+    anyhow::ensure!(
+        findings.is_empty(),
+        "Found {} possibly unused functions",
+        findings.len()
+    );
+    Ok(())
+}
+
+/// Run a command, as parsed from the command line.
+pub fn run(args: MainFlags) -> anyhow::Result<()> {
+    let MainFlags::WorkspaceUnusedPub(args) = args;
+    let color = args.color.resolve();
+    if let Some(color) = color {
+        colored::control::set_override(color);
+    }
+    logging::init(args.log_format, color);
+    // Older Windows consoles only interpret ANSI colors once enabled
+    #[cfg(windows)]
+    let _ = colored::control::set_virtual_terminal(true);
+
+    let mut command = args.command.unwrap_or(Command::Check(args.check));
+    // The commands without an analysis
+    match &mut command {
+        Command::History(args) => {
+            discover_workspace(&mut args.workspace);
+            return history::history(args);
+        }
+        Command::Completions(args) => return completions::completions(args),
+        _ => {}
+    }
+    let analysis = match &mut command {
+        Command::Check(args) => &mut args.analysis,
+        Command::Fix(args) => &mut args.analysis,
+        Command::WhoUses(args) => &mut args.analysis,
+        Command::Symbols(args) => &mut args.analysis,
+        Command::Stats(args) => &mut args.analysis,
+        Command::ServeLsp(args) => &mut args.analysis,
+        Command::Doctor(args) => &mut args.analysis,
+        Command::History(_) | Command::Completions(_) => unreachable!(),
+    };
+    discover_workspace(&mut analysis.workspace);
+    if let Some(jobs) = analysis.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
+
+    let timings = analysis.timings;
+
+    let res = match command {
+        Command::Check(args) => check(args),
+        Command::Fix(args) => fix::fix(args),
+        Command::WhoUses(args) => who_uses::who_uses(args),
+        Command::Symbols(args) => symbols::symbols(args),
+        Command::Stats(args) => stats::stats(args),
+        Command::ServeLsp(args) => lsp::serve(args.analysis),
+        Command::Doctor(args) => doctor::doctor(args),
+        Command::History(_) | Command::Completions(_) => unreachable!(),
+    };
+    if let Some(format) = timings {
+        timings::print(format);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    #[test]
+    fn parse_size() {
+        assert_eq!(super::parse_size("512"), Ok(512));
+        assert_eq!(super::parse_size("4k"), Ok(4 << 10));
+        assert_eq!(super::parse_size("64M"), Ok(64 << 20));
+        assert_eq!(super::parse_size("2G"), Ok(2 << 30));
+        assert!(super::parse_size("").is_err());
+        assert!(super::parse_size("M").is_err());
+        assert!(super::parse_size("1.5M").is_err());
+        assert!(super::parse_size("10T").is_err());
+    }
+
+    #[test]
+    fn parse_path_map() {
+        assert_eq!(
+            super::parse_path_map("/build/src=."),
+            Ok(("/build/src".into(), ".".into()))
+        );
+        // Split at the first `=`
+        assert_eq!(
+            super::parse_path_map("a=b=c"),
+            Ok(("a".into(), "b=c".into()))
+        );
+        assert!(super::parse_path_map("/build/src").is_err());
+    }
+
+    #[test]
+    fn file_list() {
+        let path = std::env::temp_dir().join(format!("files-{}.txt", std::process::id()));
+        // Not read while parsing the arguments
+        let files = super::parse_file_list(path.to_str().unwrap()).unwrap();
+        std::fs::write(&path, "./src/lib.rs\n\n  src/main.rs \n/abs/other.rs\n").unwrap();
+        let workspace = Path::new("/ws");
+        assert!(files.contains(workspace, Path::new("src/lib.rs")));
+        assert!(files.contains(workspace, Path::new("/ws/src/main.rs")));
+        assert!(files.contains(workspace, Path::new("/abs/other.rs")));
+        assert!(!files.contains(workspace, Path::new("src/other.rs")));
+        // Read once
+        std::fs::remove_file(&path).unwrap();
+        assert!(files.read().is_ok());
+        let missing = super::parse_file_list(path.to_str().unwrap()).unwrap();
+        assert!(missing.read().is_err());
+        assert!(!missing.contains(workspace, Path::new("src/lib.rs")));
+    }
+}
@@ -0,0 +1,75 @@
+use std::io::Write;
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Colored lines, with the level and the module.
+    Text,
+    /// One JSON object per line, with the timestamp, level, target, message and the structured
+    /// fields of the event, e.g. `path`, `phase`, `seconds` or `findings`.
+    Json,
+}
+
+/// Log to stderr, at the level set by `RUST_LOG` (`info` by default), with colors if `color` is
+/// set, and otherwise if stderr is a terminal.
+pub fn init(format: LogFormat, color: Option<bool>) {
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    builder.write_style(match color {
+        Some(true) => env_logger::WriteStyle::Always,
+        Some(false) => env_logger::WriteStyle::Never,
+        None => env_logger::WriteStyle::Auto,
+    });
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let mut event = serde_json::Map::new();
+            event.insert("timestamp".into(), buf.timestamp().to_string().into());
+            event.insert("level".into(), record.level().as_str().into());
+            event.insert("target".into(), record.target().into());
+            let message = strip_ansi(&record.args().to_string());
+            event.insert("message".into(), message.into());
+            let _ = record.key_values().visit(&mut Fields(&mut event));
+            writeln!(buf, "{}", serde_json::Value::Object(event))
+        });
+    }
+    builder.init();
+}
+
+/// Remove the escape sequences of the colored messages.
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Control sequences end with a letter, e.g. `\x1b[1m`
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Collects the structured fields of an event, keeping numbers and booleans as such.
+struct Fields<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        let value = if let Some(v) = value.to_u64() {
+            v.into()
+        } else if let Some(v) = value.to_i64() {
+            v.into()
+        } else if let Some(v) = value.to_f64() {
+            v.into()
+        } else if let Some(v) = value.to_bool() {
+            v.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
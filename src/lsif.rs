@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use scip::types::{symbol_information::Kind, SymbolRole};
+use serde_json::Value;
+
+use crate::index::{Encoding, Index, Occurrence, Symbol};
+
+/// Vertices and edges of an LSIF dump that are used to build the index.
+#[derive(Default)]
+struct Graph {
+    project_root: String,
+    documents: HashMap<u64, String>,
+    ranges: HashMap<u64, [i32; 3]>,
+    /// Scheme, identifier, and whether the symbol is defined in the dump.
+    monikers: HashMap<u64, (String, String, bool)>,
+    /// Manager, name and version.
+    packages: HashMap<u64, [String; 3]>,
+    hovers: HashMap<u64, String>,
+    /// Targets of the `moniker`, `packageInformation`, `textDocument/hover` and
+    /// `textDocument/references` edges, by source.
+    moniker_edges: HashMap<u64, u64>,
+    package_edges: HashMap<u64, u64>,
+    hover_edges: HashMap<u64, u64>,
+    reference_edges: HashMap<u64, u64>,
+    /// Ranges of the definitions and references of each reference result.
+    items: HashMap<u64, Vec<(u64, Vec<u64>, bool)>>,
+}
+
+fn field(value: &Value, name: &str) -> String {
+    value
+        .get(name)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+impl Graph {
+    fn add(&mut self, value: &Value) {
+        let id = value.get("id").and_then(|v| v.as_u64()).unwrap_or_default();
+        let out_v = value.get("outV").and_then(|v| v.as_u64());
+        let in_v = value.get("inV").and_then(|v| v.as_u64());
+        let label = value
+            .get("label")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        let is_vertex = value.get("type").and_then(|v| v.as_str()) == Some("vertex");
+        match (label, is_vertex) {
+            ("metaData", true) => self.project_root = field(value, "projectRoot"),
+            ("document", true) => {
+                self.documents.insert(id, field(value, "uri"));
+            }
+            ("range", true) => {
+                let position = |p: &str| {
+                    value
+                        .pointer(p)
+                        .and_then(|v| v.as_i64())
+                        .unwrap_or_default() as i32
+                };
+                let range = [
+                    position("/start/line"),
+                    position("/start/character"),
+                    position("/end/character"),
+                ];
+                self.ranges.insert(id, range);
+            }
+            ("moniker", true) => {
+                let export = field(value, "kind") == "export";
+                self.monikers.insert(
+                    id,
+                    (field(value, "scheme"), field(value, "identifier"), export),
+                );
+            }
+            ("packageInformation", true) => {
+                let package = [
+                    field(value, "manager"),
+                    field(value, "name"),
+                    field(value, "version"),
+                ];
+                self.packages.insert(id, package);
+            }
+            ("hoverResult", true) => {
+                let contents = value
+                    .pointer("/result/contents/value")
+                    .or_else(|| value.pointer("/result/contents"));
+                let contents = contents.and_then(|v| v.as_str()).unwrap_or_default();
+                self.hovers.insert(id, contents.to_string());
+            }
+            (
+                "moniker" | "packageInformation" | "textDocument/hover" | "textDocument/references",
+                false,
+            ) => {
+                let edges = match label {
+                    "moniker" => &mut self.moniker_edges,
+                    "packageInformation" => &mut self.package_edges,
+                    "textDocument/hover" => &mut self.hover_edges,
+                    _ => &mut self.reference_edges,
+                };
+                if let (Some(out_v), Some(in_v)) = (out_v, in_v) {
+                    edges.insert(out_v, in_v);
+                }
+            }
+            ("item", false) => {
+                let document = value
+                    .get("document")
+                    .or_else(|| value.get("shard"))
+                    .and_then(|v| v.as_u64());
+                let (Some(out_v), Some(document)) = (out_v, document) else {
+                    return;
+                };
+                let ranges = value
+                    .get("inVs")
+                    .and_then(|v| v.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|v| v.as_u64())
+                    .collect();
+                let definitions = field(value, "property") == "definitions";
+                self.items
+                    .entry(out_v)
+                    .or_default()
+                    .push((document, ranges, definitions));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Kind of a symbol, from the signature in its hover text.
+fn kind(hover: &str, identifier: &str, traits: &[&str]) -> Option<Kind> {
+    // The hover starts with code blocks containing the path of the container and the signature.
+    let signature = hover
+        .split("```")
+        .filter(|b| b.starts_with("rust"))
+        .nth(1)?
+        .lines()
+        .rfind(|l| !l.trim().is_empty())?;
+    let tokens = signature.split_whitespace().collect::<Vec<_>>();
+    if tokens.contains(&"trait") {
+        return Some(Kind::Trait);
+    }
+    if !tokens.contains(&"fn") {
+        return None;
+    }
+    let (parent, _) = identifier.rsplit_once("::").unwrap_or_default();
+    let is_method = identifier.contains("::impl::") || traits.contains(&parent);
+    Some(if is_method {
+        Kind::Method
+    } else {
+        Kind::Function
+    })
+}
+
+/// Parse an LSIF dump in the JSON lines format.
+pub fn read(reader: &mut dyn BufRead) -> anyhow::Result<Index> {
+    let mut graph = Graph::default();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        graph.add(&serde_json::from_str(&line)?);
+    }
+
+    let root = format!("{}/", graph.project_root.trim_end_matches('/'));
+    let mut index = Index {
+        project_root: graph.project_root.clone(),
+        ..Default::default()
+    };
+    // Documents outside the project, e.g. of the standard library, are skipped.
+    let mut documents = HashMap::<u64, usize>::default();
+    for (id, uri) in &graph.documents {
+        if let Some(path) = uri.strip_prefix(&root) {
+            documents.insert(*id, index.documents.len());
+            // LSP positions are in UTF-16 code units
+            index
+                .encodings
+                .insert(index.documents.len(), Encoding::Utf16);
+            index.documents.push(path.to_string());
+        }
+    }
+
+    // Result sets with a moniker are global symbols
+    let mut symbols = vec![];
+    for (&result_set, &moniker) in &graph.moniker_edges {
+        let Some((scheme, identifier, export)) = graph.monikers.get(&moniker) else {
+            continue;
+        };
+        let Some([manager, name, version]) = graph
+            .package_edges
+            .get(&moniker)
+            .and_then(|p| graph.packages.get(p))
+        else {
+            continue;
+        };
+        let hover = graph
+            .hover_edges
+            .get(&result_set)
+            .and_then(|h| graph.hovers.get(h))
+            .map(|h| h.as_str())
+            .unwrap_or_default();
+        // Identifiers are paths from the crate, as in `a::impl::S::method`.
+        let descriptors = identifier
+            .split_once("::")
+            .map_or(identifier.as_str(), |(_, d)| d);
+        let symbol = format!(
+            "{} {} {} {} {}",
+            scheme, manager, name, version, descriptors
+        );
+        symbols.push((result_set, symbol, identifier.as_str(), hover, *export));
+    }
+    let traits = symbols
+        .iter()
+        .filter(|s| kind(s.3, s.2, &[]) == Some(Kind::Trait))
+        .map(|s| s.2)
+        .collect::<Vec<_>>();
+
+    for (result_set, symbol, identifier, hover, export) in &symbols {
+        let sym = index.strings.intern(symbol);
+        if let (true, Some(kind)) = (*export, kind(hover, identifier, &traits)) {
+            let display_name = identifier.rsplit("::").next().unwrap_or(identifier);
+            index.symbols.insert(
+                sym,
+                Symbol {
+                    symbol: sym,
+                    display_name: index.strings.intern(display_name),
+                    kind,
+                    signature_path: None,
+                },
+            );
+        }
+        let Some(results) = graph
+            .reference_edges
+            .get(result_set)
+            .and_then(|r| graph.items.get(r))
+        else {
+            continue;
+        };
+        for (document, ranges, definitions) in results {
+            let Some(&document) = documents.get(document) else {
+                continue;
+            };
+            for range in ranges.iter().filter_map(|r| graph.ranges.get(r)) {
+                index.occurrences.push(Occurrence {
+                    symbol: sym,
+                    document,
+                    range: range.to_vec(),
+                    roles: if *definitions {
+                        SymbolRole::Definition as i32
+                    } else {
+                        0
+                    },
+                    enclosing_range: vec![],
+                });
+            }
+        }
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+
+    #[test]
+    fn read() {
+        let hover = "```rust\na\n```\n\n```rust\npub fn f()\n```";
+        let dump = [
+            r#"{"id":1,"type":"vertex","label":"metaData","projectRoot":"file:///ws/"}"#,
+            r#"{"id":2,"type":"vertex","label":"document","uri":"file:///ws/src/lib.rs"}"#,
+            r#"{"id":3,"type":"vertex","label":"document","uri":"file:///rustlib/std.rs"}"#,
+            r#"{"id":4,"type":"vertex","label":"range","start":{"line":0,"character":7},"end":{"line":0,"character":8}}"#,
+            r#"{"id":5,"type":"vertex","label":"range","start":{"line":3,"character":4},"end":{"line":3,"character":5}}"#,
+            r#"{"id":6,"type":"vertex","label":"range","start":{"line":9,"character":0},"end":{"line":9,"character":1}}"#,
+            r#"{"id":7,"type":"vertex","label":"resultSet"}"#,
+            r#"{"id":8,"type":"vertex","label":"moniker","scheme":"rust-analyzer","identifier":"a::f","kind":"export"}"#,
+            r#"{"id":9,"type":"vertex","label":"packageInformation","manager":"cargo","name":"a","version":"0.1.0"}"#,
+            &serde_json::json!({"id": 10, "type": "vertex", "label": "hoverResult", "result": {"contents": {"kind": "markdown", "value": hover}}}).to_string(),
+            r#"{"id":11,"type":"vertex","label":"referenceResult"}"#,
+            r#"{"id":12,"type":"edge","label":"moniker","outV":7,"inV":8}"#,
+            r#"{"id":13,"type":"edge","label":"packageInformation","outV":8,"inV":9}"#,
+            r#"{"id":14,"type":"edge","label":"textDocument/hover","outV":7,"inV":10}"#,
+            r#"{"id":15,"type":"edge","label":"textDocument/references","outV":7,"inV":11}"#,
+            r#"{"id":16,"type":"edge","label":"item","outV":11,"inVs":[4],"document":2,"property":"definitions"}"#,
+            r#"{"id":17,"type":"edge","label":"item","outV":11,"inVs":[5],"shard":2,"property":"references"}"#,
+            r#"{"id":18,"type":"edge","label":"item","outV":11,"inVs":[6],"document":3,"property":"references"}"#,
+            "",
+        ]
+        .join("\n");
+        let index = super::read(&mut dump.as_bytes()).unwrap();
+        // The documents outside of the project root are skipped
+        assert_eq!(index.documents, ["src/lib.rs"]);
+        assert_eq!(index.encodings.get(&0), Some(&Encoding::Utf16));
+        let [symbol] = index.symbols.values().collect::<Vec<_>>()[..] else {
+            panic!("Expected a single symbol");
+        };
+        assert_eq!(index.str(symbol.symbol), "rust-analyzer cargo a 0.1.0 f");
+        assert_eq!(index.str(symbol.display_name), "f");
+        assert_eq!(symbol.kind, Kind::Function);
+        let occurrences = index
+            .occurrences
+            .iter()
+            .map(|o| (o.range.clone(), o.is_definition()))
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(occurrences, [(vec![0, 7, 8], true), (vec![3, 4, 5], false)]);
+    }
+
+    #[test]
+    fn kind() {
+        let hover = |signature: &str| format!("```rust\na::S\n```\n\n```rust\n{}\n```", signature);
+        let kind = |signature: &str, identifier: &str| {
+            super::kind(&hover(signature), identifier, &["a::T"])
+        };
+        assert_eq!(kind("pub fn f()", "a::f"), Some(Kind::Function));
+        assert_eq!(kind("pub fn m(&self)", "a::impl::S::m"), Some(Kind::Method));
+        assert_eq!(kind("fn m(&self)", "a::T::m"), Some(Kind::Method));
+        assert_eq!(kind("pub trait T", "a::T"), Some(Kind::Trait));
+        assert_eq!(kind("pub struct S", "a::S"), None);
+        assert_eq!(super::kind("no code", "a::f", &[]), None);
+    }
+}
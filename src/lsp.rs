@@ -0,0 +1,432 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use log::*;
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::index::{self, Encoding, Span};
+use crate::{find_unused, fix, load_index, AnalysisFlags, Finding};
+
+/// A published diagnostic, with the edit of its code action.
+struct Diagnostic {
+    /// LSP range of the name, in UTF-16 code units.
+    range: Value,
+    message: String,
+    fingerprint: String,
+    /// LSP range of the visibility qualifier, if it was found.
+    qualifier: Option<Value>,
+}
+
+struct Server {
+    args: AnalysisFlags,
+    /// Workspace root, as a path with `/` separators.
+    root: String,
+    /// Published diagnostics, by path relative to the workspace root.
+    diagnostics: HashMap<String, Vec<Diagnostic>>,
+    /// Open documents edited since they were last saved, whose diagnostics have stale ranges.
+    modified: HashSet<String>,
+}
+
+/// Time without messages from the client after which a pending analysis is run, so that the
+/// notifications of a burst of changes, e.g. a checkout, trigger a single analysis.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Language server publishing the findings as diagnostics, over the standard input and output.
+///
+/// The analysis is run when the client is initialized, and again whenever a file is saved or a
+/// watched file (Rust sources and SCIP indices) changes. The notifications received until the
+/// client is idle for [`DEBOUNCE`], including during the previous analysis, are coalesced into
+/// a single analysis.
+pub fn serve(args: AnalysisFlags) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        !args.files_from.as_ref().is_some_and(|f| f.is_stdin()),
+        "--files-from cannot read the standard input, which carries the protocol"
+    );
+    let root = args
+        .workspace
+        .canonicalize()
+        .unwrap_or_else(|_| args.workspace.clone());
+    let mut server = Server {
+        root: index::uri_path(&index::dir_uri(&root)),
+        args,
+        diagnostics: HashMap::new(),
+        modified: HashSet::new(),
+    };
+    let messages = read_messages();
+    let mut stdout = std::io::stdout().lock();
+    // Whether an analysis is due once the client is idle
+    let mut pending = false;
+    loop {
+        let message = if pending {
+            match messages.recv_timeout(DEBOUNCE) {
+                Ok(message) => message,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    pending = false;
+                    server.analyze(&mut stdout)?;
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match messages.recv() {
+                Ok(message) => message,
+                Err(mpsc::RecvError) => break,
+            }
+        };
+        let message = message?;
+        let method = message["method"].as_str().unwrap_or_default();
+        let id = message.get("id").cloned();
+        debug!("Received {}", method);
+        match method {
+            "initialize" => {
+                let watch = message["params"]["capabilities"]["workspace"]["didChangeWatchedFiles"]
+                    ["dynamicRegistration"]
+                    .as_bool()
+                    .unwrap_or_default();
+                respond(
+                    &mut stdout,
+                    id,
+                    json!({
+                        "capabilities": {
+                            // Incremental changes, only to know which documents were edited
+                            "textDocumentSync": {"openClose": true, "change": 2, "save": true},
+                            "codeActionProvider": {"codeActionKinds": ["quickfix"]},
+                        },
+                        "serverInfo": {
+                            "name": "workspace-unused-pub",
+                            "version": env!("CARGO_PKG_VERSION"),
+                        },
+                    }),
+                )?;
+                if watch {
+                    write_message(
+                        &mut stdout,
+                        &json!({
+                            "jsonrpc": "2.0",
+                            "id": "watch",
+                            "method": "client/registerCapability",
+                            "params": {"registrations": [{
+                                "id": "watch",
+                                "method": "workspace/didChangeWatchedFiles",
+                                "registerOptions": {"watchers": [
+                                    {"globPattern": "**/*.rs"},
+                                    {"globPattern": "**/*.scip"},
+                                ]},
+                            }]},
+                        }),
+                    )?;
+                }
+            }
+            "textDocument/didChange" => {
+                if let Some(path) = server.relative(&message["params"]["textDocument"]["uri"]) {
+                    server.modified.insert(path);
+                }
+            }
+            "textDocument/didSave" | "textDocument/didClose" => {
+                if let Some(path) = server.relative(&message["params"]["textDocument"]["uri"]) {
+                    server.modified.remove(&path);
+                }
+                pending |= method == "textDocument/didSave";
+            }
+            "initialized" | "workspace/didChangeWatchedFiles" => pending = true,
+            "textDocument/codeAction" => {
+                let actions = server.code_actions(&message["params"]);
+                respond(&mut stdout, id, actions)?;
+            }
+            "shutdown" => respond(&mut stdout, id, Value::Null)?,
+            "exit" => return Ok(()),
+            // Unsupported requests, as opposed to notifications and responses to our requests
+            _ if id.is_some() && message.get("method").is_some() => write_message(
+                &mut stdout,
+                &json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": format!("Unsupported method {}", method)},
+                }),
+            )?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl Server {
+    /// Run the analysis and publish the diagnostics, clearing those of the files without
+    /// findings anymore.
+    fn analyze(&mut self, out: &mut dyn Write) -> anyhow::Result<()> {
+        let diagnostics = match self.diagnostics() {
+            Ok(diagnostics) => diagnostics,
+            Err(e) => {
+                warn!("Analysis failed: {}", e);
+                return write_message(
+                    out,
+                    &json!({
+                        "jsonrpc": "2.0",
+                        "method": "window/showMessage",
+                        "params": {"type": 1, "message": format!("workspace-unused-pub: {}", e)},
+                    }),
+                );
+            }
+        };
+        let previous = std::mem::replace(&mut self.diagnostics, diagnostics);
+        for path in previous
+            .keys()
+            .filter(|p| !self.diagnostics.contains_key(*p))
+        {
+            self.publish(out, path, &[])?;
+        }
+        for (path, diagnostics) in &self.diagnostics {
+            self.publish(out, path, diagnostics)?;
+        }
+        Ok(())
+    }
+
+    fn diagnostics(&self) -> anyhow::Result<HashMap<String, Vec<Diagnostic>>> {
+        let index = load_index(&self.args)?;
+        let config = Config::load(&self.args.workspace)?;
+        let (findings, _) = find_unused(&index, &self.args, &config)?;
+        info!("Publishing {} findings", findings.len());
+        let mut diagnostics: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+        for f in &findings {
+            let line = crate::read_source(&self.args.workspace.join(f.path))
+                .ok()
+                .and_then(|c| c.lines().nth(f.span.start_line).map(String::from))
+                .unwrap_or_default();
+            diagnostics
+                .entry(f.path.to_string())
+                .or_default()
+                .push(diagnostic(f, &line));
+        }
+        Ok(diagnostics)
+    }
+
+    fn publish(
+        &self,
+        out: &mut dyn Write,
+        path: &str,
+        diagnostics: &[Diagnostic],
+    ) -> anyhow::Result<()> {
+        let diagnostics: Vec<Value> = diagnostics
+            .iter()
+            .map(|d| {
+                json!({
+                    "range": d.range,
+                    "severity": 2,
+                    "code": d.fingerprint,
+                    "source": "workspace-unused-pub",
+                    "message": d.message,
+                    // Rendered faded out by most clients
+                    "tags": [1],
+                })
+            })
+            .collect();
+        write_message(
+            out,
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {"uri": self.uri(path), "diagnostics": diagnostics},
+            }),
+        )
+    }
+
+    /// Path relative to the workspace root of a document URI, if it is in the workspace.
+    fn relative(&self, uri: &Value) -> Option<String> {
+        let path = index::uri_path(uri.as_str()?);
+        let path = path.strip_prefix(&self.root)?.trim_start_matches('/');
+        Some(path.to_string())
+    }
+
+    /// Quick fixes removing the visibility qualifier of the diagnostics intersecting the
+    /// requested range, unless the document was edited since they were published, in which case
+    /// their ranges may not match anymore.
+    fn code_actions(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(diagnostics) = self
+            .relative(&params["textDocument"]["uri"])
+            .filter(|p| !self.modified.contains(p))
+            .and_then(|p| self.diagnostics.get(&p))
+        else {
+            return json!([]);
+        };
+        let line = |position: &Value| position["line"].as_u64().unwrap_or_default();
+        let (start, end) = (
+            line(&params["range"]["start"]),
+            line(&params["range"]["end"]),
+        );
+        let actions: Vec<Value> = diagnostics
+            .iter()
+            .filter(|d| (start..=end).contains(&line(&d.range["start"])))
+            .filter_map(|d| {
+                Some(json!({
+                    "title": "Remove the visibility qualifier",
+                    "kind": "quickfix",
+                    "isPreferred": true,
+                    "edit": {"changes": {uri: [{"range": d.qualifier.as_ref()?, "newText": ""}]}},
+                }))
+            })
+            .collect();
+        json!(actions)
+    }
+
+    fn uri(&self, path: &str) -> String {
+        let path = format!("{}/{}", self.root.trim_end_matches('/'), path);
+        let encoded: String = path
+            .bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{:02X}", b),
+            })
+            .collect();
+        if encoded.starts_with('/') {
+            format!("file://{}", encoded)
+        } else {
+            format!("file:///{}", encoded)
+        }
+    }
+}
+
+fn diagnostic(f: &Finding, line: &str) -> Diagnostic {
+    let message = if f.bin_only {
+        format!(
+            "Possibly unused pub {} `{}`, in a binary crate: make it private",
+            f.kind, f.display_name
+        )
+    } else if f.proc_macro {
+        format!(
+            "Possibly unused pub {} `{}`, in a proc-macro crate: lower confidence",
+            f.kind, f.display_name
+        )
+    } else {
+        format!("Possibly unused pub {} `{}`", f.kind, f.display_name)
+    };
+    Diagnostic {
+        range: range(line, &f.span),
+        message,
+        fingerprint: f.fingerprint(),
+        qualifier: fix::visibility_range(line, f.display_name).map(|r| {
+            range(
+                line,
+                &Span {
+                    start_line: f.span.start_line,
+                    start_column: r.start,
+                    end_line: f.span.start_line,
+                    end_column: r.end,
+                },
+            )
+        }),
+    }
+}
+
+/// LSP range of a single-line span, whose columns are converted from bytes to UTF-16 code
+/// units.
+fn range(line: &str, span: &Span) -> Value {
+    let character = |column: usize| Encoding::Utf16.column(line, column);
+    json!({
+        "start": {"line": span.start_line, "character": character(span.start_column)},
+        "end": {"line": span.end_line, "character": character(span.end_column)},
+    })
+}
+
+/// Read the messages of the standard input on a separate thread, so that the ones received
+/// during an analysis are queued. The channel ends after the input or the first error.
+fn read_messages() -> mpsc::Receiver<anyhow::Result<Value>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin().lock();
+        while let Some(message) = read_message(&mut stdin).transpose() {
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Read a message with its `Content-Length` header, returning `None` at the end of the input.
+fn read_message(reader: &mut dyn BufRead) -> anyhow::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let length = length.ok_or_else(|| anyhow::anyhow!("Message without Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(out: &mut dyn Write, message: &Value) -> anyhow::Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn respond(out: &mut dyn Write, id: Option<Value>, result: Value) -> anyhow::Result<()> {
+    write_message(out, &json!({"jsonrpc": "2.0", "id": id, "result": result}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range() {
+        // `𝕏` is 4 bytes and 2 UTF-16 code units
+        let span = Span {
+            start_line: 3,
+            start_column: 12,
+            end_line: 3,
+            end_column: 13,
+        };
+        assert_eq!(
+            super::range("/* 𝕏 */ fn f() {}", &span),
+            json!({"start": {"line": 3, "character": 10}, "end": {"line": 3, "character": 11}})
+        );
+        assert_eq!(
+            super::range(
+                "fn f() {}",
+                &Span {
+                    start_column: 3,
+                    end_column: 4,
+                    ..span
+                }
+            ),
+            json!({"start": {"line": 3, "character": 3}, "end": {"line": 3, "character": 4}})
+        );
+    }
+
+    #[test]
+    fn read_message() {
+        let body = r#"{"jsonrpc":"2.0","method":"initialized"}"#;
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut reader = std::io::Cursor::new(input.repeat(2));
+        for _ in 0..2 {
+            let message = super::read_message(&mut reader).unwrap().unwrap();
+            assert_eq!(message["method"], "initialized");
+        }
+        assert!(super::read_message(&mut reader).unwrap().is_none());
+        let mut reader = std::io::Cursor::new("Content-Type: text\r\n\r\n{}");
+        assert!(super::read_message(&mut reader).is_err());
+    }
+}
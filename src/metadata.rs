@@ -0,0 +1,339 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use cargo_metadata::{Metadata, MetadataCommand};
+
+/// Metadata of the workspace members, without their dependencies.
+pub fn load(workspace: &Path) -> anyhow::Result<Metadata> {
+    MetadataCommand::new()
+        .manifest_path(workspace.join("Cargo.toml"))
+        .no_deps()
+        .exec()
+        .map_err(|e| anyhow::anyhow!("Failed to run cargo metadata in {:?}: {}", workspace, e))
+}
+
+/// Root of the workspace containing a directory, possibly in one of its parents, as found by
+/// `cargo locate-project`.
+pub fn workspace_root(dir: &Path) -> Option<PathBuf> {
+    let manifest = duct::cmd!(
+        "cargo",
+        "locate-project",
+        "--workspace",
+        "--message-format",
+        "plain"
+    )
+    .dir(dir)
+    .stderr_null()
+    .read()
+    .ok()?;
+    Some(Path::new(manifest.trim()).parent()?.to_path_buf())
+}
+
+/// Whether the manifest at the root of a workspace has no package, only a `[workspace]` section.
+pub fn is_virtual(workspace: &Path) -> bool {
+    std::fs::read_to_string(workspace.join("Cargo.toml"))
+        .ok()
+        .and_then(|m| m.parse::<toml::Table>().ok())
+        .is_some_and(|m| m.contains_key("workspace") && !m.contains_key("package"))
+}
+
+/// Directories excluded from the workspace with `workspace.exclude`, relative to its root.
+pub fn excluded_dirs(workspace: &Path) -> Vec<PathBuf> {
+    let Some(manifest) = std::fs::read_to_string(workspace.join("Cargo.toml"))
+        .ok()
+        .and_then(|m| m.parse::<toml::Table>().ok())
+    else {
+        return vec![];
+    };
+    manifest
+        .get("workspace")
+        .and_then(|w| w.get("exclude"))
+        .and_then(|e| e.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.as_str())
+        .map(|e| PathBuf::from(e.trim_start_matches("./")))
+        .collect()
+}
+
+/// Whether the manifest at the root is a standalone crate, outside of any workspace.
+pub fn is_standalone(workspace: &Path) -> bool {
+    std::fs::read_to_string(workspace.join("Cargo.toml"))
+        .ok()
+        .and_then(|m| m.parse::<toml::Table>().ok())
+        .is_some_and(|m| m.contains_key("package") && !m.contains_key("workspace"))
+}
+
+/// A workspace member.
+#[derive(Clone)]
+pub struct Member {
+    pub name: String,
+    pub version: String,
+    /// Directory of the manifest, relative to the workspace root.
+    pub dir: PathBuf,
+    /// Names of the packages it depends on.
+    pub dependencies: Vec<String>,
+    /// Whether it has no library target, only binaries (besides tests, examples and
+    /// benchmarks), so that no other crate can use its items.
+    pub bin_only: bool,
+    /// Sources of the example targets, relative to the workspace root: the directory of the
+    /// examples with a `main.rs`, and the file otherwise.
+    pub examples: Vec<PathBuf>,
+    /// Whether it can be published, i.e. it does not have `publish = false`.
+    pub publish: bool,
+    /// Whether it is a proc-macro crate, whose items are only used by the compiler at expansion
+    /// time, besides the helpers used within the crate.
+    pub proc_macro: bool,
+}
+
+pub fn members(workspace: &Path, metadata: &Metadata) -> Vec<Member> {
+    metadata
+        .workspace_packages()
+        .into_iter()
+        .map(|p| {
+            let relative = |path: &Path| {
+                path.strip_prefix(workspace)
+                    .or_else(|_| path.strip_prefix(metadata.workspace_root.as_std_path()))
+                    .unwrap_or(path)
+                    .to_path_buf()
+            };
+            Member {
+                name: p.name.to_string(),
+                version: p.version.to_string(),
+                dir: relative(p.manifest_path.parent().unwrap().as_std_path()),
+                dependencies: p.dependencies.iter().map(|d| d.name.clone()).collect(),
+                bin_only: is_bin_only(p),
+                examples: p
+                    .targets
+                    .iter()
+                    .filter(|t| t.is_kind(cargo_metadata::TargetKind::Example))
+                    .map(|t| {
+                        let path = t.src_path.as_std_path();
+                        match path.parent() {
+                            Some(dir) if path.file_name() == Some("main.rs".as_ref()) => {
+                                relative(dir)
+                            }
+                            _ => relative(path),
+                        }
+                    })
+                    .collect(),
+                publish: p.publish.as_ref().is_none_or(|r| !r.is_empty()),
+                proc_macro: p
+                    .targets
+                    .iter()
+                    .any(|t| t.is_kind(cargo_metadata::TargetKind::ProcMacro)),
+            }
+        })
+        .collect()
+}
+
+fn is_bin_only(package: &cargo_metadata::Package) -> bool {
+    use cargo_metadata::TargetKind;
+    let kinds = || package.targets.iter().flat_map(|t| &t.kind);
+    kinds().any(|k| *k == TargetKind::Bin)
+        && !kinds().any(|k| {
+            matches!(
+                k,
+                TargetKind::Lib
+                    | TargetKind::RLib
+                    | TargetKind::DyLib
+                    | TargetKind::CDyLib
+                    | TargetKind::StaticLib
+                    | TargetKind::ProcMacro
+            )
+        })
+}
+
+/// Metadata of a workspace used by the analysis, loaded once per run. It has no members outside
+/// of a Cargo workspace, or if `cargo metadata` fails.
+#[derive(Default)]
+pub struct Workspace {
+    pub members: Vec<Member>,
+    /// See [`external_path_dependencies`].
+    pub external_path_dependencies: Vec<PathBuf>,
+    /// Failure of `cargo metadata`, if any.
+    pub error: Option<String>,
+}
+
+impl Workspace {
+    pub fn load(workspace: &Path) -> Self {
+        if !workspace.join("Cargo.toml").exists() {
+            return Self::default();
+        }
+        match load(workspace) {
+            Ok(metadata) => Self {
+                members: members(workspace, &metadata),
+                external_path_dependencies: external_path_dependencies(&metadata),
+                error: None,
+            },
+            Err(e) => {
+                log::debug!("Not classifying the members: {}", e);
+                Self {
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                }
+            }
+        }
+    }
+}
+
+/// Members without a library target.
+pub fn bin_only_crates(members: &[Member]) -> Vec<&Member> {
+    members.iter().filter(|m| m.bin_only).collect()
+}
+
+/// Proc-macro members.
+pub fn proc_macro_crates(members: &[Member]) -> Vec<&Member> {
+    members.iter().filter(|m| m.proc_macro).collect()
+}
+
+/// Members whose items can only be used in their own crate: the ones without a library target,
+/// and the proc-macro crates, which can only export their macros.
+pub fn crate_private(members: &[Member]) -> Vec<&Member> {
+    members
+        .iter()
+        .filter(|m| m.bin_only || m.proc_macro)
+        .collect()
+}
+
+/// Members with a library target that can be published, whose items may be used by crates
+/// outside of the workspace.
+pub fn publishable_libraries(members: &[Member]) -> Vec<&Member> {
+    members
+        .iter()
+        .filter(|m| m.publish && !m.bin_only)
+        .collect()
+}
+
+/// Sources of the example targets of the members, relative to the workspace root.
+pub fn examples(members: &[Member]) -> Vec<PathBuf> {
+    members.iter().flat_map(|m| m.examples.clone()).collect()
+}
+
+/// Member owning a path relative to the workspace root, i.e. the one with the deepest directory
+/// containing it.
+pub fn owner<'a>(members: &'a [Member], path: &Path) -> Option<&'a Member> {
+    members
+        .iter()
+        .filter(|m| path.starts_with(&m.dir))
+        .max_by_key(|m| m.dir.components().count())
+}
+
+/// Directories of the path dependencies of the members that are outside of the workspace root.
+pub fn external_path_dependencies(metadata: &Metadata) -> Vec<PathBuf> {
+    let root = metadata.workspace_root.as_std_path();
+    let mut dirs: Vec<PathBuf> = metadata
+        .workspace_packages()
+        .into_iter()
+        .flat_map(|p| &p.dependencies)
+        .filter_map(|d| d.path.as_ref())
+        .map(|p| p.as_std_path().to_path_buf())
+        .filter(|p| !p.starts_with(root))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/// Directories of the members, without the ones nested in another member.
+pub fn member_dirs(members: &[Member]) -> Vec<PathBuf> {
+    let dirs: Vec<&PathBuf> = members.iter().map(|m| &m.dir).collect();
+    let mut roots: Vec<PathBuf> = dirs
+        .iter()
+        .filter(|d| !dirs.iter().any(|o| o != *d && d.starts_with(o)))
+        .map(|d| d.to_path_buf())
+        .collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// Names of the given members and of the members depending on them, directly or not.
+pub fn with_dependents(members: &[Member], names: &[String]) -> HashSet<String> {
+    let mut selected: HashSet<String> = names.iter().cloned().collect();
+    loop {
+        let dependents = members
+            .iter()
+            .filter(|m| !selected.contains(&m.name))
+            .filter(|m| m.dependencies.iter().any(|d| selected.contains(d)))
+            .map(|m| m.name.clone())
+            .collect::<Vec<_>>();
+        if dependents.is_empty() {
+            return selected;
+        }
+        selected.extend(dependents);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(name: &str, dir: &str, dependencies: &[&str]) -> Member {
+        Member {
+            name: name.into(),
+            version: "0.1.0".into(),
+            dir: dir.into(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            bin_only: false,
+            examples: vec![],
+            publish: true,
+            proc_macro: false,
+        }
+    }
+
+    fn members() -> Vec<Member> {
+        vec![
+            member("root", "", &[]),
+            member("core", "crates/core", &[]),
+            member("macros", "crates/core/macros", &[]),
+            member("app", "crates/app", &["core", "serde"]),
+            member("cli", "cli", &["app"]),
+        ]
+    }
+
+    #[test]
+    fn with_dependents() {
+        let members = members();
+        let names = |selected: &[&str]| {
+            let selected = selected.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            let mut names = super::with_dependents(&members, &selected)
+                .into_iter()
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&["core"]), ["app", "cli", "core"]);
+        assert_eq!(names(&["app"]), ["app", "cli"]);
+        assert_eq!(names(&["macros"]), ["macros"]);
+        assert_eq!(names(&[]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn member_dirs() {
+        let members = members();
+        assert_eq!(super::member_dirs(&members), [PathBuf::from("")]);
+        assert_eq!(
+            super::member_dirs(&members[1..]),
+            [
+                PathBuf::from("cli"),
+                "crates/app".into(),
+                "crates/core".into()
+            ]
+        );
+    }
+
+    #[test]
+    fn owner() {
+        let members = members();
+        let owner = |path: &str| super::owner(&members, Path::new(path)).map(|m| m.name.as_str());
+        assert_eq!(owner("crates/core/src/lib.rs"), Some("core"));
+        assert_eq!(owner("crates/core/macros/src/lib.rs"), Some("macros"));
+        assert_eq!(owner("crates/core2/src/lib.rs"), Some("root"));
+        assert_eq!(owner("src/main.rs"), Some("root"));
+        assert_eq!(
+            super::owner(&members[1..], Path::new("src/main.rs")).map(|m| m.name.as_str()),
+            None
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use crate::{metadata, AnalysisFlags, Finding, Input};
+
+/// Version of the metrics document, incremented on incompatible changes.
+pub const METRICS_VERSION: u32 = 1;
+
+/// JSON Schema of the metrics document.
+pub const METRICS_SCHEMA: &str = include_str!("../schema/metrics.schema.json");
+
+/// Aggregate counts of a `check` run, e.g. for dashboards, as described by [`METRICS_SCHEMA`].
+#[derive(Serialize)]
+struct Metrics {
+    version: u32,
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+    total: usize,
+    /// Findings per selected workspace member, including the ones without any.
+    crates: BTreeMap<String, usize>,
+    /// Findings per item kind, including the kinds without any.
+    kinds: BTreeMap<String, usize>,
+    /// Duration of the analysis, including the indexing.
+    duration_seconds: f64,
+    /// Time since the index files were last modified, if they are files.
+    index_age_seconds: Option<u64>,
+}
+
+/// Write the metrics document of a run.
+pub fn write(
+    args: &AnalysisFlags,
+    metadata: &metadata::Workspace,
+    findings: &[Finding],
+    duration: Duration,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let mut crates: BTreeMap<String, usize> =
+        crate::indexer::select(args, metadata.members.clone())
+            .into_iter()
+            .map(|m| (m.name, 0))
+            .collect();
+    let mut kinds: BTreeMap<String, usize> =
+        args.kinds.iter().map(|k| (k.to_string(), 0)).collect();
+    for f in findings {
+        *crates.entry(f.package().to_string()).or_default() += 1;
+        *kinds.entry(f.kind.to_string()).or_default() += 1;
+    }
+    let now = SystemTime::now();
+    let metrics = Metrics {
+        version: METRICS_VERSION,
+        timestamp: now.duration_since(SystemTime::UNIX_EPOCH)?.as_secs(),
+        total: findings.len(),
+        crates,
+        kinds,
+        duration_seconds: duration.as_secs_f64(),
+        index_age_seconds: index_files(args)
+            .iter()
+            .map(|p| std::fs::metadata(p).and_then(|m| m.modified()).ok())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|m| m.into_iter().min())
+            .and_then(|oldest| now.duration_since(oldest).ok())
+            .map(|age| age.as_secs()),
+    };
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    serde_json::to_writer_pretty(&mut out, &metrics)?;
+    std::io::Write::write_all(&mut out, b"\n")?;
+    log::info!("Wrote the metrics to {:?}", path);
+    Ok(())
+}
+
+/// Files of the index, empty if it is not read from files.
+fn index_files(args: &AnalysisFlags) -> Vec<PathBuf> {
+    match args.input {
+        Input::Scip => crate::scip_paths(args)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| !crate::cache::is_stdin(p))
+            .collect(),
+        Input::Lsif => vec![args.workspace.join(&args.lsif)],
+        Input::RustdocJson => vec![],
+    }
+}
+
+/// Badge in the shields.io endpoint format, see https://shields.io/badges/endpoint-badge.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Badge {
+    schema_version: u32,
+    label: &'static str,
+    message: String,
+    color: &'static str,
+}
+
+/// Write a shields.io endpoint badge with the number of findings, colored by the thresholds
+/// up to which it is green, yellow and orange, and red above.
+pub fn write_badge(findings: usize, thresholds: &[usize], path: &Path) -> anyhow::Result<()> {
+    const COLORS: [&str; 4] = ["brightgreen", "yellow", "orange", "red"];
+    let level = thresholds
+        .iter()
+        .position(|t| findings <= *t)
+        .unwrap_or(thresholds.len());
+    let badge = Badge {
+        schema_version: 1,
+        label: "unused pub",
+        message: findings.to_string(),
+        color: COLORS[level.min(COLORS.len() - 1)],
+    };
+    std::fs::write(path, serde_json::to_string(&badge)? + "\n")?;
+    log::info!("Wrote the badge to {:?}", path);
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+
+use log::*;
+
+/// Locations of the CODEOWNERS file searched by GitHub, relative to the repository root.
+const LOCATIONS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Owners of the workspace files, according to a CODEOWNERS file.
+pub struct CodeOwners {
+    /// Patterns with their owners, the last matching one taking precedence.
+    rules: Vec<(String, Vec<String>)>,
+    /// Path of the workspace relative to the repository root, with `/` separators.
+    prefix: String,
+}
+
+impl CodeOwners {
+    /// Load the given CODEOWNERS file, relative to the workspace root, or the one of the
+    /// repository containing the workspace if any.
+    pub fn load(workspace: &Path, path: Option<&Path>) -> anyhow::Result<Option<Self>> {
+        let root = repository_root(workspace).unwrap_or_else(|| workspace.to_path_buf());
+        let path = match path {
+            Some(path) => workspace.join(path),
+            None => match LOCATIONS.iter().map(|l| root.join(l)).find(|p| p.exists()) {
+                Some(path) => path,
+                None => return Ok(None),
+            },
+        };
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {:?}: {}", path, e))?;
+        debug!("Using the code owners of {:?}", path);
+        let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+        let prefix = canonical(workspace)
+            .strip_prefix(canonical(&root))
+            .map(crate::index::slash_path)
+            .unwrap_or_default();
+        Ok(Some(Self {
+            rules: parse(&contents),
+            prefix,
+        }))
+    }
+
+    /// Owners of a file, relative to the workspace root.
+    pub fn owners(&self, path: &str) -> &[String] {
+        let path = if self.prefix.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.prefix, path)
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| matches(pattern, &path))
+            .map_or(&[], |(_, owners)| owners.as_slice())
+    }
+}
+
+/// Closest ancestor of the workspace with a `.git` directory or file.
+fn repository_root(workspace: &Path) -> Option<PathBuf> {
+    let workspace = workspace.canonicalize().ok()?;
+    workspace
+        .ancestors()
+        .find(|d| d.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+fn parse(contents: &str) -> Vec<(String, Vec<String>)> {
+    contents
+        .lines()
+        .map(|l| l.split_once(" #").map_or(l, |(rule, _)| rule).trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            // The pattern ends at the first whitespace not escaped with a backslash
+            let end = l
+                .char_indices()
+                .find(|&(i, c)| c.is_whitespace() && !l[..i].ends_with('\\'))
+                .map_or(l.len(), |(i, _)| i);
+            let owners = l[end..].split_whitespace().map(String::from).collect();
+            (l[..end].replace("\\ ", " "), owners)
+        })
+        .collect()
+}
+
+/// Whether a CODEOWNERS pattern, with the gitignore syntax, matches a path relative to the
+/// repository root. Patterns matching a directory match the files under it.
+fn matches(pattern: &str, path: &str) -> bool {
+    // Patterns with a slash other than at the end are relative to the root
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let pattern = pattern.trim_start_matches('/');
+    let directory = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    // Unlike in gitignore, `dir/*` does not match the files of the subdirectories
+    let files_only = pattern.ends_with("/*");
+    let components: Vec<&str> = path.split('/').collect();
+    // Candidate subpaths: prefixes of the path (for directories), at any depth if unanchored
+    let starts = if anchored { 0..1 } else { 0..components.len() };
+    starts.into_iter().any(|start| {
+        (start + 1..=components.len()).any(|end| {
+            // A directory pattern does not match the file itself
+            (!directory || end < components.len())
+                && (!files_only || end == components.len())
+                && glob(
+                    pattern.as_bytes(),
+                    components[start..end].join("/").as_bytes(),
+                )
+        })
+    })
+}
+
+/// Glob matching, where `*` and `?` do not match `/`, and `**` matches any sequence.
+pub(crate) fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        // `**/` matches whole components
+        [b'*', b'*', b'/', rest @ ..] => (0..=text.len())
+            .filter(|&i| i == 0 || text[i - 1] == b'/')
+            .any(|i| glob(rest, &text[i..])),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob(rest, &text[i..])),
+        [b'?', rest @ ..] => text.first().is_some_and(|&c| c != b'/') && glob(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && glob(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches() {
+        assert!(super::matches("*", "src/lib.rs"));
+        assert!(super::matches("*.rs", "src/lib.rs"));
+        assert!(!super::matches("*.rs", "src/lib.rsx"));
+        // Unanchored directories match at any depth, anchored ones from the root
+        assert!(super::matches("apps/", "web/apps/main.rs"));
+        assert!(!super::matches("/apps/", "web/apps/main.rs"));
+        assert!(super::matches("/apps/", "apps/main.rs"));
+        assert!(!super::matches("apps/", "apps"));
+        assert!(super::matches("docs/*", "docs/index.md"));
+        assert!(!super::matches("docs/*", "docs/guide/index.md"));
+        assert!(super::matches("docs/**/*.md", "docs/guide/index.md"));
+        assert!(super::matches("crates/core", "crates/core/src/lib.rs"));
+        assert!(!super::matches(
+            "crates/core",
+            "other/crates/core/src/lib.rs"
+        ));
+    }
+
+    #[test]
+    fn glob() {
+        assert!(super::glob(b"a?c", b"abc"));
+        assert!(!super::glob(b"a?c", b"a/c"));
+        assert!(!super::glob(b"a*", b"a/b"));
+        assert!(super::glob(b"a/**", b"a/b/c"));
+        assert!(super::glob(b"**/c", b"c"));
+        assert!(super::glob(b"**/c", b"a/b/c"));
+        assert!(!super::glob(b"**/c", b"a/bc"));
+    }
+
+    #[test]
+    fn owners() {
+        let owners = CodeOwners {
+            rules: parse("# Owners\n* @all\n/src/ @core @docs # Rust\nsrc/my\\ file.rs @me\n"),
+            prefix: "ws".into(),
+        };
+        assert_eq!(owners.owners("README.md"), ["@all"]);
+        // Relative to the repository root, where the last matching rule takes precedence
+        assert_eq!(owners.owners("src/lib.rs"), ["@all"]);
+        let owners = CodeOwners {
+            prefix: String::new(),
+            ..owners
+        };
+        assert_eq!(owners.owners("src/lib.rs"), ["@core", "@docs"]);
+        assert_eq!(owners.owners("src/my file.rs"), ["@me"]);
+    }
+}
@@ -0,0 +1,830 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use log::*;
+use scip::types::symbol_information::Kind;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, Config};
+use crate::index::{Index, Sym, Symbol};
+use crate::{bindings, metadata, search, AnalysisFlags, Finding, ItemKind};
+
+/// Declarations that may be unused, by symbol.
+pub type Candidates<'a> = HashMap<Sym, &'a Symbol>;
+
+/// Inputs of the passes.
+pub struct Context<'a> {
+    pub index: &'a Index,
+    pub args: &'a AnalysisFlags,
+    pub config: &'a Config,
+    /// Metadata of the workspace, loaded once for all the passes.
+    pub metadata: &'a metadata::Workspace,
+}
+
+/// Callbacks invoked during the analysis, e.g. to collect metrics or filter the candidates
+/// further. All of them do nothing by default.
+pub trait Hooks {
+    /// A declaration entering the passes, which is dropped if `false` is returned.
+    fn on_candidate(&mut self, _index: &Index, _candidate: &Symbol) -> bool {
+        true
+    }
+
+    /// A candidate removed by a pass, or by the scoping to the requested packages and kinds.
+    fn on_filtered(&mut self, _index: &Index, _pass: &str, _candidate: &Symbol) {}
+
+    /// A finding, which is dropped if `false` is returned.
+    fn on_finding(&mut self, _finding: &Finding) -> bool {
+        true
+    }
+}
+
+/// No hooks.
+impl Hooks for () {}
+
+/// Both hooks, in order. Candidates and findings are dropped if either drops them.
+impl<A: Hooks, B: Hooks> Hooks for (A, B) {
+    fn on_candidate(&mut self, index: &Index, candidate: &Symbol) -> bool {
+        self.0.on_candidate(index, candidate) & self.1.on_candidate(index, candidate)
+    }
+
+    fn on_filtered(&mut self, index: &Index, pass: &str, candidate: &Symbol) {
+        self.0.on_filtered(index, pass, candidate);
+        self.1.on_filtered(index, pass, candidate);
+    }
+
+    fn on_finding(&mut self, finding: &Finding) -> bool {
+        self.0.on_finding(finding) & self.1.on_finding(finding)
+    }
+}
+
+/// Run a filter, calling [`Hooks::on_filtered`] for the removed candidates, which are returned.
+pub(crate) fn filter<'a>(
+    index: &Index,
+    name: &str,
+    candidates: &mut Candidates<'a>,
+    hooks: &mut dyn Hooks,
+    f: impl FnOnce(&mut Candidates<'a>) -> anyhow::Result<()>,
+) -> anyhow::Result<Candidates<'a>> {
+    let mut removed = candidates.clone();
+    f(candidates)?;
+    removed.retain(|symbol, candidate| {
+        let filtered = !candidates.contains_key(symbol);
+        if filtered {
+            hooks.on_filtered(index, name, candidate);
+        }
+        filtered
+    });
+    Ok(removed)
+}
+
+/// A filter pass, which removes the candidates that it considers used.
+pub trait Pass: Send + Sync {
+    /// Name, as used in the `passes` configuration.
+    fn name(&self) -> &str;
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()>;
+
+    /// Whether the pass applies to the workspace, e.g. is configured. Disabled passes are skipped,
+    /// and left out of the statistics. Enabled by default.
+    fn enabled(&self, _ctx: &Context) -> bool {
+        true
+    }
+
+    /// Number of removed candidates by reason, for passes combining several criteria. Empty by
+    /// default.
+    fn reasons(&self, _ctx: &Context, _removed: &Candidates) -> BTreeMap<String, usize> {
+        BTreeMap::new()
+    }
+}
+
+/// Remove the candidates that are referenced in the index, more than `--max-uses` times.
+///
+/// For the items of proc-macro crates, only the references within their crate are counted, as
+/// the indexer may attribute to them the occurrences in the expanded code of their users.
+pub struct References;
+
+impl Pass for References {
+    fn name(&self) -> &str {
+        "references"
+    }
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()> {
+        let index = ctx.index;
+        let proc_macro = metadata::proc_macro_crates(&ctx.metadata.members);
+        let scopes: HashMap<Sym, PathBuf> = candidates
+            .values()
+            .filter_map(|d| Some((d.symbol, member_dir(index, &proc_macro, d)?)))
+            .collect();
+        let mut uses: HashMap<Sym, usize> = HashMap::new();
+        for o in &index.occurrences {
+            if o.is_definition() || !candidates.contains_key(&o.symbol) {
+                continue;
+            }
+            let path = Path::new(&index.documents[o.document]);
+            if scopes
+                .get(&o.symbol)
+                .is_none_or(|dir| path.starts_with(dir))
+            {
+                *uses.entry(o.symbol).or_default() += 1;
+            }
+        }
+        candidates.retain(|s, _| uses.get(s).copied().unwrap_or_default() <= ctx.args.max_uses);
+        Ok(())
+    }
+}
+
+/// Remove mains (which are never called), methods in tests (test methods are never called) and
+/// trait methods (which may be called implicitly), except with `--trait-defaults` for the default
+/// methods that no implementation overrides.
+///
+/// Inherent methods named like a method of a trait of the workspace that their type does not
+/// implement, e.g. left over after the removal of the implementation, are removed as well, with
+/// the [`ORPHANED_TRAIT_METHOD`] reason, to be listed separately.
+// TODO: For the first two, only remove #[test] and #[main], #[tokio::main] methods.
+pub struct Heuristics;
+
+impl Pass for Heuristics {
+    fn name(&self) -> &str {
+        "heuristics"
+    }
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()> {
+        let traits = Self::traits(ctx);
+        candidates.retain(|_, d| Self::reason(ctx.index, &traits, d).is_none());
+        Ok(())
+    }
+
+    fn reasons(&self, ctx: &Context, removed: &Candidates) -> BTreeMap<String, usize> {
+        let traits = Self::traits(ctx);
+        let mut reasons = BTreeMap::new();
+        for d in removed.values() {
+            if let Some(reason) = Self::reason(ctx.index, &traits, d) {
+                *reasons.entry(reason.to_string()).or_default() += 1;
+            }
+        }
+        reasons
+    }
+}
+
+/// Reason of the [`Heuristics`] for the orphaned trait methods.
+pub const ORPHANED_TRAIT_METHOD: &str = "orphaned trait method";
+
+/// Trait names, and the default methods of traits not subject to the trait heuristic.
+struct Traits<'a> {
+    names: HashSet<&'a str>,
+    defaults: HashSet<Sym>,
+    /// Names of the traits declaring each method name.
+    methods: HashMap<&'a str, Vec<&'a str>>,
+    /// Implemented traits, by type name.
+    implemented: HashSet<(&'a str, &'a str)>,
+    /// Methods of trait implementations according to the relationships of the index.
+    implementing: HashSet<Sym>,
+}
+
+impl Heuristics {
+    fn traits<'a>(ctx: &Context<'a>) -> Traits<'a> {
+        let index = ctx.index;
+        Traits {
+            names: index
+                .symbols
+                .values()
+                .filter(|s| s.kind == Kind::Trait)
+                .map(|s| index.str(s.display_name))
+                .collect(),
+            defaults: if ctx.args.trait_defaults {
+                trait_defaults(index)
+            } else {
+                HashSet::new()
+            },
+            methods: index
+                .symbols
+                .values()
+                .filter(|s| matches!(s.kind, Kind::Method | Kind::TraitMethod))
+                .filter_map(|s| {
+                    let parent = crate::api::parent(index.str(s.symbol))?;
+                    let t = index.symbols.get(&index.strings.get(&parent)?)?;
+                    (t.kind == Kind::Trait)
+                        .then(|| (index.str(s.display_name), index.str(t.display_name)))
+                })
+                .into_group_map(),
+            implemented: index
+                .symbols
+                .values()
+                .filter_map(|s| match implementation(index.str(s.symbol))? {
+                    (ty, Some(t)) => Some((ty, t)),
+                    (_, None) => None,
+                })
+                .collect(),
+            implementing: index.implementations.values().flatten().copied().collect(),
+        }
+    }
+
+    /// Candidates removed by the heuristics as orphaned trait methods.
+    pub(crate) fn orphaned(ctx: &Context, removed: &HashSet<Sym>) -> HashSet<Sym> {
+        let traits = Self::traits(ctx);
+        removed
+            .iter()
+            .filter(|s| {
+                ctx.index.symbols.get(s).is_some_and(|d| {
+                    Self::reason(ctx.index, &traits, d) == Some(ORPHANED_TRAIT_METHOD)
+                })
+            })
+            .copied()
+            .collect()
+    }
+
+    /// First heuristic considering a candidate used, if any.
+    fn reason(index: &Index, traits: &Traits, d: &Symbol) -> Option<&'static str> {
+        let symbol = index.str(d.symbol);
+        if index.str(d.display_name) == "main" {
+            Some("main")
+        } else if symbol.contains("test") {
+            Some("test")
+        } else if d
+            .signature_path
+            .as_ref()
+            .is_some_and(|p| p.contains("test"))
+        {
+            Some("test path")
+        } else if Self::is_orphaned(index, traits, d) {
+            Some(ORPHANED_TRAIT_METHOD)
+        } else if !traits.defaults.contains(&d.symbol)
+            && traits.names.iter().any(|t| symbol.contains(t))
+        {
+            Some("trait name")
+        } else {
+            None
+        }
+    }
+
+    /// Whether a method of an inherent implementation is named like a method of a trait that its
+    /// type does not implement.
+    fn is_orphaned(index: &Index, traits: &Traits, d: &Symbol) -> bool {
+        let Some((ty, None)) = implementation(index.str(d.symbol)) else {
+            return false;
+        };
+        !traits.implementing.contains(&d.symbol)
+            && traits
+                .methods
+                .get(index.str(d.display_name))
+                .is_some_and(|ts| ts.iter().all(|t| !traits.implemented.contains(&(ty, *t))))
+    }
+}
+
+/// Type and trait (for trait implementations) of the implementation declaring a method, e.g.
+/// `("S", Some("Trait"))` for `impl#[S][Trait]method().`.
+pub(crate) fn implementation(symbol: &str) -> Option<(&str, Option<&str>)> {
+    let rest = &symbol[symbol.rfind("impl#[")? + "impl#[".len()..];
+    let (ty, rest) = rest.split_once(']')?;
+    let t = rest
+        .strip_prefix('[')
+        .and_then(|r| r.split_once(']'))
+        .map(|(t, _)| t);
+    Some((ty, t))
+}
+
+/// Methods with a default body declared by a trait of the workspace, that no implementation of
+/// the index overrides. Their calls resolve to the trait method, so they are unused if not
+/// referenced.
+///
+/// Overrides are found with the implementation relationships if the indexer provides them, and
+/// otherwise by name, e.g. `impl#[S][Trait]method().` for `Trait#method().`, which may also match
+/// the implementations of another trait with the same name.
+fn trait_defaults(index: &Index) -> HashSet<Sym> {
+    let overridden: HashSet<Sym> = index.implementations.values().flatten().copied().collect();
+    // Names of the overriding methods, e.g. `[Trait]method().`
+    let overrides: HashSet<&str> = index
+        .symbols
+        .values()
+        .filter_map(|s| {
+            let symbol = index.str(s.symbol);
+            let start = symbol.rfind("impl#")?;
+            Some(&symbol[start + symbol[start..].rfind('[')?..])
+        })
+        .collect();
+    index
+        .symbols
+        .values()
+        // Required methods are trait methods, and provided ones methods
+        .filter(|s| s.kind == Kind::Method && !overridden.contains(&s.symbol))
+        .filter_map(|s| {
+            let symbol = index.str(s.symbol);
+            let parent = crate::api::parent(symbol)?;
+            let t = index.symbols.get(&index.strings.get(&parent)?)?;
+            let name = format!(
+                "[{}]{}",
+                index.str(t.display_name),
+                symbol.strip_prefix(&parent)?
+            );
+            (t.kind == Kind::Trait && !overrides.contains(name.as_str())).then_some(s.symbol)
+        })
+        .collect()
+}
+
+/// Remove the candidates named as a token in more than `--max-uses` invocations of the component
+/// macros of UI frameworks, e.g. `Counter` in `html! { <Counter /> }`, as some of these
+/// references are missing from the index.
+///
+/// The items of binary-only and proc-macro crates are only looked up in their crate.
+pub struct ComponentMacros;
+
+pub const MACROS_PASS: &str = "macros";
+
+impl Pass for ComponentMacros {
+    fn name(&self) -> &str {
+        MACROS_PASS
+    }
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()> {
+        let index = ctx.index;
+        let counts = Self::counts(ctx, candidates)?;
+        candidates.retain(|_, d| {
+            counts
+                .get(index.str(d.display_name))
+                .copied()
+                .unwrap_or_default()
+                <= ctx.args.max_uses
+        });
+        Ok(())
+    }
+}
+
+impl ComponentMacros {
+    /// Number of invocations of the component macros naming each candidate.
+    pub(crate) fn counts<'a>(
+        ctx: &Context<'a>,
+        candidates: &Candidates<'a>,
+    ) -> anyhow::Result<HashMap<&'a str, usize>> {
+        let macros = match &ctx.config.component_macros {
+            Some(macros) => macros.clone(),
+            None => crate::components::DEFAULT_MACROS.map(String::from).to_vec(),
+        };
+        let (names, scopes) = scoped_names(ctx, candidates);
+        let counts =
+            crate::components::count(ctx.args, &search_roots(ctx)?, &macros, &names, &scopes);
+        Ok(names.into_iter().zip(counts).collect())
+    }
+}
+
+/// Remove the candidates registered as the handlers of web routes more than `--max-uses` times,
+/// e.g. in Rocket's `routes![index]`, actix-web's `.service(index)` or axum's `get(index)`, as
+/// the routing macros and attributes may hide these references from the index.
+///
+/// The items of binary-only and proc-macro crates are only looked up in their crate.
+pub struct Routes;
+
+pub const ROUTES_PASS: &str = "routes";
+
+impl Pass for Routes {
+    fn name(&self) -> &str {
+        ROUTES_PASS
+    }
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()> {
+        let index = ctx.index;
+        let counts = Self::counts(ctx, candidates)?;
+        candidates.retain(|_, d| {
+            counts
+                .get(index.str(d.display_name))
+                .copied()
+                .unwrap_or_default()
+                <= ctx.args.max_uses
+        });
+        Ok(())
+    }
+}
+
+impl Routes {
+    /// Number of route registrations naming each candidate.
+    pub(crate) fn counts<'a>(
+        ctx: &Context<'a>,
+        candidates: &Candidates<'a>,
+    ) -> anyhow::Result<HashMap<&'a str, usize>> {
+        let (names, scopes) = scoped_names(ctx, candidates);
+        let counts = crate::routes::count(ctx.args, &search_roots(ctx)?, &names, &scopes);
+        Ok(names.into_iter().zip(counts).collect())
+    }
+}
+
+/// Remove the candidates whose name appears more than once in the workspace files (or than
+/// `--max-uses` plus one), e.g. in macros or templates that the index misses. Doc comments and
+/// Markdown files are left to the [`Documentation`] pass, unless the configuration counts the
+/// latter as usages.
+///
+/// The names of items of binary-only and proc-macro crates are only searched in their crate.
+pub struct Search;
+
+impl Pass for Search {
+    fn name(&self) -> &str {
+        "search"
+    }
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()> {
+        let index = ctx.index;
+        let counts = Self::counts(ctx, candidates)?;
+        candidates.retain(|_, d| {
+            counts
+                .get(index.str(d.display_name))
+                .copied()
+                .unwrap_or_default()
+                <= 1 + ctx.args.max_uses
+        });
+        Ok(())
+    }
+}
+
+impl Search {
+    /// Number of lines containing the name of each candidate in the searched files.
+    pub(crate) fn counts<'a>(
+        ctx: &Context<'a>,
+        candidates: &Candidates<'a>,
+    ) -> anyhow::Result<HashMap<&'a str, usize>> {
+        Self::count_lines(ctx, candidates, search::Lines::Code)
+    }
+
+    fn count_lines<'a>(
+        ctx: &Context<'a>,
+        candidates: &Candidates<'a>,
+        lines: search::Lines,
+    ) -> anyhow::Result<HashMap<&'a str, usize>> {
+        let (names, scopes) = scoped_names(ctx, candidates);
+        let rules = search::Rules::new(ctx.config)?;
+        let counts = search::count(
+            ctx.args,
+            &rules,
+            &search_roots(ctx)?,
+            &names,
+            &scopes,
+            lines,
+        )?;
+        Ok(names.into_iter().zip(counts).collect())
+    }
+
+    /// Lines containing the name of a candidate in the searched files, as counted when it is the
+    /// only candidate with this name.
+    pub(crate) fn lines(
+        ctx: &Context,
+        candidate: &Symbol,
+        lines: search::Lines,
+    ) -> anyhow::Result<Vec<(String, usize, String)>> {
+        let private = metadata::crate_private(&ctx.metadata.members);
+        let scope = member_dir(ctx.index, &private, candidate);
+        search::lines(
+            ctx.args,
+            &search::Rules::new(ctx.config)?,
+            &search_roots(ctx)?,
+            ctx.index.str(candidate.display_name),
+            scope.as_deref(),
+            lines,
+        )
+    }
+}
+
+/// Remove the candidates whose name appears in the documentation, i.e. the doc comments and the
+/// Markdown files such as the READMEs, more than `--max-uses` times besides their own doc comment.
+/// They are then listed separately rather than considered used, as the documentation alone may
+/// not justify keeping them. The Markdown files are skipped if the configuration counts them as
+/// usages or ignores them.
+pub struct Documentation;
+
+pub const DOCUMENTATION_PASS: &str = "documentation";
+
+impl Pass for Documentation {
+    fn name(&self) -> &str {
+        DOCUMENTATION_PASS
+    }
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()> {
+        let index = ctx.index;
+        let counts = Search::count_lines(ctx, candidates, search::Lines::Documentation)?;
+        let mut sources = crate::symbols::Sources::new(&ctx.args.workspace);
+        let mut own = HashMap::<Sym, usize>::new();
+        for o in &index.occurrences {
+            let Some(d) = candidates.get(&o.symbol).filter(|_| o.is_definition()) else {
+                continue;
+            };
+            // Lines of the doc comment and attributes just above the definition
+            let (path, name) = (&index.documents[o.document], index.str(d.display_name));
+            let mut line = o.span().start_line;
+            while let Some(l) = line.checked_sub(1).and_then(|l| sources.line(path, l)) {
+                let l = l.trim_start();
+                if !l.starts_with("//") && !l.starts_with("#[") {
+                    break;
+                }
+                let documentation = search::Lines::Documentation;
+                if l.contains(name)
+                    && documentation.matches(search::Format::Rust, l.as_bytes(), 0, 0)
+                {
+                    *own.entry(o.symbol).or_default() += 1;
+                }
+                line -= 1;
+            }
+        }
+        candidates.retain(|s, d| {
+            let mentions = counts
+                .get(index.str(d.display_name))
+                .copied()
+                .unwrap_or_default();
+            mentions.saturating_sub(own.get(s).copied().unwrap_or_default()) <= ctx.args.max_uses
+        });
+        Ok(())
+    }
+}
+
+/// Remove the candidates whose name appears in the string literals of the Rust files more than
+/// `--max-uses` times, e.g. as they are looked up by name at runtime in a plugin registry or a
+/// dispatch table. They are then listed separately with a low confidence rather than considered
+/// used.
+pub struct Strings;
+
+pub const STRINGS_PASS: &str = "strings";
+
+impl Pass for Strings {
+    fn name(&self) -> &str {
+        STRINGS_PASS
+    }
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()> {
+        let index = ctx.index;
+        let counts = Search::count_lines(ctx, candidates, search::Lines::Strings)?;
+        candidates.retain(|_, d| {
+            counts
+                .get(index.str(d.display_name))
+                .copied()
+                .unwrap_or_default()
+                <= ctx.args.max_uses
+        });
+        Ok(())
+    }
+}
+
+/// Remove the candidates exported to another language, e.g. with `#[wasm_bindgen]`, whose names
+/// in this language appear in its configured sources more than `--max-uses` times. They are then
+/// listed separately, as only used from this language, while the unused exports are reported.
+pub struct Bindings(pub &'static bindings::Language);
+
+impl Pass for Bindings {
+    fn name(&self) -> &str {
+        self.0.pass
+    }
+
+    fn enabled(&self, ctx: &Context) -> bool {
+        self.0.is_configured(&ctx.config.bindings)
+    }
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()> {
+        let workspace = &ctx.args.workspace;
+        bindings::check_headers(ctx.index, workspace, self.0, &ctx.config.bindings);
+        let counts = self.counts(ctx, candidates);
+        candidates.retain(|s, _| counts.get(s).is_none_or(|n| *n <= ctx.args.max_uses));
+        Ok(())
+    }
+}
+
+impl Bindings {
+    /// Names in the language of the exported candidates, and their number of lines in its
+    /// sources.
+    pub(crate) fn counts(&self, ctx: &Context, candidates: &Candidates) -> HashMap<Sym, usize> {
+        if !self.0.is_configured(&ctx.config.bindings) {
+            return HashMap::new();
+        }
+        let workspace = &ctx.args.workspace;
+        let exports = bindings::exports(ctx.index, workspace, self.0, candidates);
+        let names = exports.values().flatten().map(String::as_str).collect();
+        let lines = bindings::count(workspace, self.0, &ctx.config.bindings, &names);
+        exports
+            .iter()
+            .map(|(s, names)| {
+                let n = names.iter().filter_map(|n| lines.get(n)).sum();
+                (*s, n)
+            })
+            .collect()
+    }
+}
+
+/// Records the candidates removed by a pass, e.g. the [`Documentation`] one.
+pub struct RemovedBy(pub &'static str, pub HashSet<Sym>);
+
+impl Hooks for RemovedBy {
+    fn on_filtered(&mut self, _index: &Index, pass: &str, candidate: &Symbol) {
+        if pass == self.0 {
+            self.1.insert(candidate.symbol);
+        }
+    }
+}
+
+/// Sorted names of the candidates, with the directory of their crate if all the candidates with
+/// this name are in the same binary-only or proc-macro crate.
+fn scoped_names<'a>(
+    ctx: &Context<'a>,
+    candidates: &Candidates<'a>,
+) -> (Vec<&'a str>, Vec<Option<PathBuf>>) {
+    let index = ctx.index;
+    let names = candidates
+        .values()
+        .map(|d| index.str(d.display_name))
+        .unique()
+        .sorted()
+        .collect_vec();
+    let private = metadata::crate_private(&ctx.metadata.members);
+    let scopes = names
+        .iter()
+        .map(|name| {
+            let scopes = candidates
+                .values()
+                .filter(|d| index.str(d.display_name) == *name)
+                .map(|d| member_dir(index, &private, d))
+                .unique()
+                .collect_vec();
+            match scopes[..] {
+                [Some(ref dir)] => Some(dir.clone()),
+                _ => None,
+            }
+        })
+        .collect_vec();
+    (names, scopes)
+}
+
+/// Directory of the crate of a candidate if it is one of the given members, e.g. the ones
+/// without a library target, whose items can only be used in their crate, no other crate being
+/// able to depend on it.
+fn member_dir(index: &Index, members: &[&metadata::Member], d: &Symbol) -> Option<PathBuf> {
+    let package = crate::symbol_package(index.str(d.symbol))?;
+    let member = members
+        .iter()
+        .find(|m| m.name.replace('-', "_") == package)?;
+    Some(member.dir.clone())
+}
+
+/// Directories to search, relative to the workspace root: the configured source roots, the
+/// members of virtual workspaces, or otherwise the whole workspace (if empty), along with the
+/// other workspaces and the path dependencies outside of the workspace.
+fn search_roots(ctx: &Context) -> anyhow::Result<Vec<PathBuf>> {
+    let (roots, mut extra) = workspace_roots(ctx);
+    extra.extend(crate::other_workspaces(ctx.args, ctx.config));
+    if extra.is_empty() {
+        return Ok(roots);
+    }
+    // The whole workspace is no longer implied by empty roots
+    let roots = if roots.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        roots
+    };
+    Ok(roots.into_iter().chain(extra).collect())
+}
+
+/// Roots within the workspace, and the path dependencies outside of it.
+fn workspace_roots(ctx: &Context) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let workspace = &ctx.args.workspace;
+    if !ctx.config.source_roots.is_empty() || !workspace.join("Cargo.toml").exists() {
+        return (ctx.config.source_roots.clone(), vec![]);
+    }
+    if let Some(e) = &ctx.metadata.error {
+        warn!("Searching the whole workspace: {}", e);
+        return (vec![], vec![]);
+    }
+    let external = ctx.metadata.external_path_dependencies.clone();
+    if !external.is_empty() {
+        warn!(
+            "Also searching the path dependencies outside of the workspace: {}",
+            external.iter().map(|p| p.display()).join(", ")
+        );
+    }
+    if !metadata::is_virtual(workspace) {
+        return (vec![], external);
+    }
+    let mut roots = metadata::member_dirs(&ctx.metadata.members);
+    // Excluded directories still count as usages, unless --exclude-usages is passed
+    if !ctx.args.exclude_usages {
+        roots.extend(metadata::excluded_dirs(workspace));
+    }
+    debug!(
+        "Searching the members of the virtual workspace: {:?}",
+        roots
+    );
+    (roots, external)
+}
+
+/// A filter run as an external command, e.g. to encode the conventions of an organization.
+///
+/// The command receives the candidates as a JSON array on stdin, and writes the array of the
+/// candidates to keep on stdout.
+pub struct External {
+    name: String,
+    command: String,
+}
+
+impl From<&config::Filter> for External {
+    fn from(filter: &config::Filter) -> Self {
+        Self {
+            name: filter.name.clone(),
+            command: filter.command.clone(),
+        }
+    }
+}
+
+/// A candidate, as exchanged with external filters.
+#[derive(Serialize)]
+struct ExternalCandidate<'a> {
+    symbol: &'a str,
+    display_name: &'a str,
+    kind: Option<ItemKind>,
+    /// Path of the definition, relative to the workspace root.
+    path: Option<&'a str>,
+    /// Zero-based line of the definition.
+    line: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct Kept {
+    symbol: String,
+}
+
+impl Pass for External {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run<'a>(&self, ctx: &Context<'a>, candidates: &mut Candidates<'a>) -> anyhow::Result<()> {
+        let index = ctx.index;
+        let definitions: HashMap<Sym, (&str, usize)> = index
+            .occurrences
+            .iter()
+            .filter(|o| o.is_definition() && candidates.contains_key(&o.symbol))
+            .map(|o| {
+                let location = (index.documents[o.document].as_str(), o.span().start_line);
+                (o.symbol, location)
+            })
+            .collect();
+        let input = candidates
+            .values()
+            .map(|c| {
+                let definition = definitions.get(&c.symbol);
+                ExternalCandidate {
+                    symbol: index.str(c.symbol),
+                    display_name: index.str(c.display_name),
+                    kind: ItemKind::from_scip(c.kind),
+                    path: definition.map(|d| d.0),
+                    line: definition.map(|d| d.1),
+                }
+            })
+            .collect_vec();
+        let cmd = self.command.split_whitespace().collect_vec();
+        let (program, cmd_args) = cmd
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("The command of the filter {} is empty", self.name))?;
+        let output = duct::cmd(*program, cmd_args)
+            .dir(&ctx.args.workspace)
+            .stdin_bytes(serde_json::to_vec(&input)?)
+            .read()
+            .map_err(|e| anyhow::anyhow!("The filter {} failed: {}", self.name, e))?;
+        let kept: Vec<Kept> = serde_json::from_str(&output)
+            .map_err(|e| anyhow::anyhow!("Invalid output of the filter {}: {}", self.name, e))?;
+        let kept: HashSet<&str> = kept.iter().map(|k| k.symbol.as_str()).collect();
+        candidates.retain(|_, c| kept.contains(index.str(c.symbol)));
+        Ok(())
+    }
+}
+
+/// The built-in passes, in their default order.
+pub fn builtin() -> Vec<Box<dyn Pass>> {
+    let mut passes: Vec<Box<dyn Pass>> = vec![
+        Box::new(References),
+        Box::new(Heuristics),
+        Box::new(ComponentMacros),
+        Box::new(Routes),
+        Box::new(Search),
+        Box::new(Strings),
+        Box::new(Documentation),
+    ];
+    // The exports only used from other languages are listed separately
+    passes.extend(bindings::LANGUAGES.map(|l| Box::new(Bindings(l)) as Box<dyn Pass>));
+    passes
+}
+
+/// Passes to run, in the order of the `passes` configuration if set, and otherwise all the
+/// available ones.
+pub fn select(
+    config: &Config,
+    available: Vec<Box<dyn Pass>>,
+) -> anyhow::Result<Vec<Box<dyn Pass>>> {
+    let Some(names) = &config.passes else {
+        return Ok(available);
+    };
+    let mut available: HashMap<String, Box<dyn Pass>> = available
+        .into_iter()
+        .map(|p| (p.name().to_string(), p))
+        .collect();
+    let known = available.keys().sorted().join(", ");
+    names
+        .iter()
+        .map(|name| {
+            available.remove(name).ok_or_else(|| {
+                anyhow::anyhow!("Unknown or repeated pass {:?} (available: {})", name, known)
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,126 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::components::{self, is_identifier};
+use crate::AnalysisFlags;
+
+/// Macros of Rocket listing the handlers to mount, e.g. `routes![index, login]`.
+const MACROS: [&str; 2] = ["routes", "catchers"];
+
+/// Methods of actix-web registering handlers, e.g. `.service(index)` or `.to(index)`.
+const METHODS: [&str; 4] = ["service", "default_service", "route", "to"];
+
+/// Method routers of axum and actix-web, e.g. `get(index)`, `routing::post(create)` or
+/// `web::to(index)`, also chained as in `get(show).post(update)`.
+const ROUTERS: [&str; 10] = [
+    "get", "post", "put", "delete", "patch", "head", "options", "trace", "any", "to",
+];
+
+/// Count the registrations of routes naming each name as a token in the Rust files under the roots,
+/// relative to the workspace root, inside the scope of the name if it has one.
+///
+/// The route attributes, e.g. `#[get("/")]`, are not evidence of usage by themselves, as the
+/// handlers still have to be registered.
+pub fn count(
+    args: &AnalysisFlags,
+    roots: &[PathBuf],
+    names: &[&str],
+    scopes: &[Option<PathBuf>],
+) -> Vec<usize> {
+    components::count_with(args, roots, names, scopes, |contents| {
+        let mut registrations = components::invocations(contents, &MACROS);
+        registrations.extend(calls(contents));
+        registrations
+    })
+}
+
+/// Identifiers of the arguments of each call registering handlers in the contents of a Rust file,
+/// outside of the comments and string literals.
+fn calls(contents: &str) -> Vec<HashSet<&str>> {
+    let bytes = contents.as_bytes();
+    let mut calls = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(end) = components::literal_end(contents, i) {
+            i = end;
+            continue;
+        }
+        if !is_identifier(bytes[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && is_identifier(bytes[i]) {
+            i += 1;
+        }
+        let name = &contents[start..i];
+        let mut open = i;
+        while bytes.get(open).is_some_and(|c| c.is_ascii_whitespace()) {
+            open += 1;
+        }
+        if bytes.get(open) != Some(&b'(') {
+            continue;
+        }
+        // Character before the name, and the one before the dot of a method call
+        let before = |end: usize| {
+            bytes[..end]
+                .iter()
+                .rposition(|c| !c.is_ascii_whitespace())
+                .map(|p| (p, bytes[p]))
+        };
+        let method = match before(start) {
+            Some((p, b'.')) => Some(before(p).map(|(_, c)| c)),
+            _ => None,
+        };
+        let registers = match method {
+            Some(receiver) => {
+                METHODS.contains(&name) || (ROUTERS.contains(&name) && receiver == Some(b')'))
+            }
+            None => ROUTERS.contains(&name),
+        };
+        if registers {
+            calls.push(components::tokens(contents, open).0);
+        }
+    }
+    calls
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn calls() {
+        let contents = r#"
+fn app() {
+    App::new()
+        .service(index)
+        .route("/", web::get().to(list))
+        .default_service(web::to(missing));
+    Router::new().route("/", get(show).post(update));
+    let value = values.get(key); // get(commented)
+    let name = "get(in_string)";
+}
+"#;
+        let calls = super::calls(contents)
+            .into_iter()
+            .map(|tokens| tokens.into_iter().collect::<BTreeSet<_>>())
+            .collect::<Vec<_>>();
+        let set = |tokens: &[&'static str]| tokens.iter().copied().collect::<BTreeSet<_>>();
+        // Registering methods and routers, also chained, but not other calls named like routers
+        assert_eq!(
+            calls,
+            [
+                set(&["index"]),
+                set(&["get", "list", "to", "web"]),
+                set(&[]),
+                set(&["list"]),
+                set(&["missing", "to", "web"]),
+                set(&["missing"]),
+                set(&["get", "post", "show", "update"]),
+                set(&["show"]),
+                set(&["update"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashSet;
+use std::io::BufRead;
+use std::path::Path;
+
+use crate::AnalysisFlags;
+
+/// Positions (paths relative to the workspace root, and 0-based lines) of the items reported by
+/// rustc's `dead_code` lint, from the JSON messages of `cargo check`, read from
+/// `--rustc-messages` or by running it on the workspace with the features of the index.
+pub fn dead_code(args: &AnalysisFlags) -> anyhow::Result<HashSet<(String, usize)>> {
+    let messages = match &args.rustc_messages {
+        Some(path) => std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read the rustc messages {:?}: {}", path, e))?,
+        None => {
+            let mut check = vec!["check", "--workspace", "--message-format", "json"];
+            let features = args.features.join(",");
+            if !args.features.is_empty() {
+                check.extend(["--features", &features]);
+            }
+            if args.all_features {
+                check.push("--all-features");
+            }
+            if args.no_default_features {
+                check.push("--no-default-features");
+            }
+            log::info!("Running cargo {}", check.join(" "));
+            // Warnings of crates that are already built are replayed by cargo
+            let output = duct::cmd("cargo", check)
+                .dir(&args.workspace)
+                .stderr_null()
+                .stdout_capture()
+                .unchecked()
+                .run()?;
+            output.stdout
+        }
+    };
+    let mut dead = HashSet::new();
+    for line in messages.lines() {
+        let line = line?;
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        if message["reason"] != "compiler-message"
+            || message["message"]["code"]["code"] != "dead_code"
+        {
+            continue;
+        }
+        // Items warned about together, e.g. several unused methods of an impl block, each have a
+        // primary span on their name
+        for span in message["message"]["spans"].as_array().into_iter().flatten() {
+            let (Some(path), Some(line)) =
+                (span["file_name"].as_str(), span["line_start"].as_u64())
+            else {
+                continue;
+            };
+            if span["is_primary"] != true || line == 0 {
+                continue;
+            }
+            let path = Path::new(path);
+            let path = path.strip_prefix(&args.workspace).unwrap_or(path);
+            dead.insert((path.to_string_lossy().into_owned(), line as usize - 1));
+        }
+    }
+    log::debug!("{} items reported by the dead_code lint", dead.len());
+    Ok(dead)
+}
@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use log::*;
+use scip::types::{symbol_information::Kind, SymbolRole};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::index::{self, Index, Occurrence, Symbol};
+use crate::metadata;
+
+/// The subset of the rustdoc JSON format used here.
+#[derive(Deserialize)]
+struct Crate {
+    root: Value,
+    /// Whether private items are documented too, with `--document-private-items`.
+    #[serde(default)]
+    includes_private: bool,
+    index: HashMap<String, Item>,
+    paths: HashMap<String, Summary>,
+    external_crates: HashMap<String, ExternalCrate>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    #[serde(default)]
+    crate_id: u32,
+    name: Option<String>,
+    span: Option<Span>,
+    #[serde(default)]
+    links: HashMap<String, Value>,
+    /// Object with a single field, named after the kind of the item.
+    inner: Value,
+}
+
+#[derive(Deserialize)]
+struct Span {
+    filename: String,
+    /// One-based line and column.
+    begin: (usize, usize),
+    end: (usize, usize),
+}
+
+#[derive(Deserialize)]
+struct Summary {
+    crate_id: u32,
+    path: Vec<String>,
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct ExternalCrate {
+    name: String,
+}
+
+/// Identifiers are integers in recent format versions, and strings in older ones.
+fn id(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn ids<'a>(value: &'a Value, field: &str) -> impl Iterator<Item = String> + 'a {
+    value
+        .get(field)
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(id)
+}
+
+/// Identifiers of the items referenced by a value, e.g. in paths of the signature.
+fn references(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::Object(o) => {
+            for (k, v) in o {
+                if k == "id" {
+                    out.extend(id(v));
+                } else {
+                    references(v, out);
+                }
+            }
+        }
+        Value::Array(a) => a.iter().for_each(|v| references(v, out)),
+        _ => {}
+    }
+}
+
+impl Item {
+    fn kind(&self) -> Option<(&str, &Value)> {
+        let (kind, inner) = self.inner.as_object()?.iter().next()?;
+        Some((kind, inner))
+    }
+}
+
+/// Where a function is declared.
+enum Parent<'a> {
+    Impl(&'a Value),
+    Trait(&'a str),
+}
+
+/// Package name and version of a crate, which are used for the symbols.
+struct Package<'a> {
+    name: &'a str,
+    version: &'a str,
+}
+
+impl Package<'_> {
+    /// Symbols are formatted like rust-analyzer's, so that findings have the same fingerprints
+    /// with both inputs.
+    fn symbol(&self, descriptors: &str) -> String {
+        format!(
+            "rust-analyzer cargo {} {} {}",
+            self.name, self.version, descriptors
+        )
+    }
+}
+
+fn type_name(ty: &Value) -> String {
+    let name = ty
+        .pointer("/resolved_path/path")
+        .or_else(|| ty.get("primitive"))
+        .and_then(|p| p.as_str())
+        .unwrap_or("_");
+    name.rsplit("::").next().unwrap_or(name).to_string()
+}
+
+/// Add the declarations and references of a crate to the index.
+fn add_crate(
+    index: &mut Index,
+    documents: &mut HashMap<String, usize>,
+    krate: &Crate,
+    package: &Package,
+    packages: &HashMap<String, Package>,
+) {
+    // Module path and parent of the items, from the root module
+    let mut modules = HashMap::<String, String>::default();
+    let mut parents = HashMap::<String, (String, Parent)>::default();
+    let mut stack = id(&krate.root)
+        .map(|root| vec![(root, String::new())])
+        .unwrap_or_default();
+    while let Some((module, prefix)) = stack.pop() {
+        let Some((_, inner)) = krate.index.get(&module).and_then(|i| i.kind()) else {
+            continue;
+        };
+        for child in ids(inner, "items") {
+            let Some(item) = krate.index.get(&child) else {
+                continue;
+            };
+            let Some((kind, child_inner)) = item.kind() else {
+                continue;
+            };
+            match kind {
+                "module" => {
+                    let name = item.name.as_deref().unwrap_or_default();
+                    stack.push((child.clone(), format!("{}{}/", prefix, name)));
+                }
+                "trait" => {
+                    let name = item.name.as_deref().unwrap_or_default();
+                    for method in ids(child_inner, "items") {
+                        parents.insert(method, (prefix.clone(), Parent::Trait(name)));
+                    }
+                }
+                _ => {}
+            }
+            // Implementations of types, declared alongside them
+            for i in ids(child_inner, "impls") {
+                let Some((_, impl_)) = krate.index.get(&i).and_then(|i| i.kind()) else {
+                    continue;
+                };
+                for method in ids(impl_, "items") {
+                    parents.insert(method, (prefix.clone(), Parent::Impl(impl_)));
+                }
+            }
+            modules.insert(child, prefix.clone());
+        }
+    }
+
+    // Symbols of the functions of this crate
+    let mut symbols = HashMap::<&str, String>::default();
+    for (id, item) in &krate.index {
+        let (Some(name), Some(("function", _))) = (&item.name, item.kind()) else {
+            continue;
+        };
+        let (descriptors, kind) = match parents.get(id) {
+            Some((prefix, Parent::Impl(impl_))) => {
+                let ty = impl_.get("for").map(type_name).unwrap_or_default();
+                let trait_ = impl_
+                    .pointer("/trait/path")
+                    .and_then(|p| p.as_str())
+                    .map(|t| format!("[{}]", t.rsplit("::").next().unwrap_or(t)))
+                    .unwrap_or_default();
+                (
+                    format!("{}impl#[{}]{}{}().", prefix, ty, trait_, name),
+                    Kind::Method,
+                )
+            }
+            Some((prefix, Parent::Trait(t))) => {
+                (format!("{}{}#{}().", prefix, t, name), Kind::Method)
+            }
+            None => match modules.get(id) {
+                Some(prefix) => (format!("{}{}().", prefix, name), Kind::Function),
+                None => continue,
+            },
+        };
+        let symbol = package.symbol(&descriptors);
+        let sym = index.strings.intern(&symbol);
+        index.symbols.insert(
+            sym,
+            Symbol {
+                symbol: sym,
+                display_name: index.strings.intern(name),
+                kind,
+                signature_path: None,
+            },
+        );
+        symbols.insert(id, symbol);
+    }
+    // Traits, whose implementations are skipped by the analysis
+    for item in krate.index.values() {
+        if let (Some(name), Some(("trait", _))) = (&item.name, item.kind()) {
+            let symbol = package.symbol(&format!("{}#", name));
+            let sym = index.strings.intern(&symbol);
+            index.symbols.insert(
+                sym,
+                Symbol {
+                    symbol: sym,
+                    display_name: index.strings.intern(name),
+                    kind: Kind::Trait,
+                    signature_path: None,
+                },
+            );
+        }
+    }
+
+    let mut document = |index: &mut Index, span: &Span| {
+        // Paths have the platform separators, i.e. `\` on Windows
+        let filename = span.filename.replace('\\', "/");
+        *documents.entry(filename.clone()).or_insert_with(|| {
+            index.documents.push(filename);
+            index.documents.len() - 1
+        })
+    };
+    for (id, item) in &krate.index {
+        let Some(span) = &item.span else {
+            continue;
+        };
+        let doc = document(index, span);
+        let (line, column) = (span.begin.0 as i32 - 1, span.begin.1 as i32 - 1);
+        if let Some(symbol) = symbols.get(id.as_str()) {
+            index.occurrences.push(Occurrence {
+                symbol: index.strings.intern(symbol),
+                document: doc,
+                range: vec![line, column, column],
+                roles: SymbolRole::Definition as i32,
+                enclosing_range: vec![],
+            });
+        }
+        // References in signatures and intra-doc links. Calls in function bodies are not part
+        // of rustdoc's output, and are only found by the search pass.
+        let mut referenced = item.links.values().filter_map(self::id).collect::<Vec<_>>();
+        references(&item.inner, &mut referenced);
+        for r in referenced {
+            let symbol = match (symbols.get(r.as_str()), krate.paths.get(&r)) {
+                (Some(symbol), _) => symbol.clone(),
+                // Function of another workspace crate
+                (None, Some(summary)) if summary.kind == "function" && summary.crate_id != 0 => {
+                    let (Some(package), Some(path)) = (
+                        krate
+                            .external_crates
+                            .get(&summary.crate_id.to_string())
+                            .and_then(|c| packages.get(&c.name)),
+                        summary.path.get(1..),
+                    ) else {
+                        continue;
+                    };
+                    package.symbol(&format!("{}().", path.join("/")))
+                }
+                _ => continue,
+            };
+            index.occurrences.push(Occurrence {
+                symbol: index.strings.intern(&symbol),
+                document: doc,
+                range: vec![line, column, column],
+                roles: 0,
+                enclosing_range: vec![],
+            });
+        }
+    }
+}
+
+/// A function exported by a crate, as documented by rustdoc.
+pub struct Exported {
+    /// Path relative to the workspace root.
+    pub path: String,
+    pub name: String,
+    /// Zero-based lines of the beginning and end of the item.
+    pub lines: (usize, usize),
+}
+
+/// Functions and methods part of the public API of the publishable library crates, from their
+/// rustdoc JSON output in `target/doc`, which only documents the items reachable from the crate
+/// root (including the re-exported ones).
+pub fn exported(workspace: &Path, members: &[metadata::Member]) -> anyhow::Result<Vec<Exported>> {
+    let dir = crate::cache::target_dir(workspace).join("doc");
+    let mut exported = vec![];
+    for member in metadata::publishable_libraries(members) {
+        let path = dir.join(format!("{}.json", member.name.replace('-', "_")));
+        if !path.exists() {
+            warn!(
+                path:% = path.display();
+                "No rustdoc JSON for {} at {:?}, its public API is not excluded. Generate it with `cargo +nightly rustdoc -p {} -- -Z unstable-options --output-format json`.",
+                member.name, path, member.name
+            );
+            continue;
+        }
+        let reader = std::io::BufReader::new(std::fs::File::open(&path)?);
+        let krate: Crate = serde_json::from_reader(reader)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {:?}: {}", path, e))?;
+        if krate.includes_private {
+            warn!(
+                "The rustdoc JSON of {} includes the private items, its public API is not excluded",
+                member.name
+            );
+            continue;
+        }
+        exported.extend(krate.index.into_values().filter_map(|item| {
+            let (Some(name), Some(("function", _))) = (&item.name, item.kind()) else {
+                return None;
+            };
+            let span = item.span.as_ref().filter(|_| item.crate_id == 0)?;
+            Some(Exported {
+                path: span.filename.replace('\\', "/"),
+                name: name.clone(),
+                lines: (span.begin.0 - 1, span.end.0 - 1),
+            })
+        }));
+    }
+    Ok(exported)
+}
+
+/// Build an index from the rustdoc JSON output of the workspace members, in `target/doc`.
+pub fn load_index(workspace: &Path) -> anyhow::Result<Index> {
+    let members = metadata::members(workspace, &metadata::load(workspace)?);
+    let dir = crate::cache::target_dir(workspace).join("doc");
+    let mut crates = vec![];
+    for member in &members {
+        let crate_name = member.name.replace('-', "_");
+        let path: PathBuf = dir.join(format!("{}.json", crate_name));
+        if !path.exists() {
+            warn!(path:% = path.display(); "No rustdoc JSON for {} at {:?}", member.name, path);
+            continue;
+        }
+        let reader = std::io::BufReader::new(std::fs::File::open(&path)?);
+        let krate: Crate = serde_json::from_reader(reader)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {:?}: {}", path, e))?;
+        crates.push((crate_name, krate));
+    }
+    anyhow::ensure!(
+        !crates.is_empty(),
+        "No rustdoc JSON found in {:?}. Generate it for each member with `cargo +nightly rustdoc -p <member> -- -Z unstable-options --output-format json`.",
+        dir
+    );
+
+    let packages: HashMap<String, Package> = members
+        .iter()
+        .map(|m| {
+            (
+                m.name.replace('-', "_"),
+                Package {
+                    name: &m.name,
+                    version: &m.version,
+                },
+            )
+        })
+        .collect();
+    let root = workspace
+        .canonicalize()
+        .unwrap_or_else(|_| workspace.to_path_buf());
+    let mut index = Index {
+        project_root: index::dir_uri(&root),
+        ..Default::default()
+    };
+    let mut documents = HashMap::default();
+    for (name, krate) in &crates {
+        add_crate(
+            &mut index,
+            &mut documents,
+            krate,
+            &packages[name],
+            &packages,
+        );
+    }
+    Ok(index)
+}
@@ -0,0 +1,864 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use itertools::Itertools;
+use log::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, Markdown};
+use crate::{cache, AnalysisFlags};
+
+/// Match counts of the files searched in the previous runs, by name.
+#[derive(Serialize, Deserialize, Default)]
+struct Cache {
+    /// Hash of the [`Rules`], as the counts depend on them.
+    rules: u64,
+    /// Names searched in any of the files, to which the counts refer by index.
+    names: Vec<String>,
+    files: HashMap<PathBuf, FileCounts>,
+}
+
+impl Cache {
+    /// Index of each name into [`Cache::names`], adding the missing ones.
+    fn intern(&mut self, names: &[&str]) -> Vec<usize> {
+        let mut ids: HashMap<&str, usize> = self
+            .names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.as_str(), i))
+            .collect();
+        let mut added = vec![];
+        let interned = names
+            .iter()
+            .map(|n| {
+                *ids.entry(n).or_insert_with(|| {
+                    added.push(n.to_string());
+                    self.names.len() + added.len() - 1
+                })
+            })
+            .collect();
+        self.names.extend(added);
+        interned
+    }
+}
+
+/// Counts of the previous search of each kind of lines in this process, which spares reading the
+/// cache in the daemon.
+static MEMORY: Mutex<[Option<Cache>; 3]> = Mutex::new([None, None, None]);
+
+/// Lines in which the names are searched.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Lines {
+    /// Lines of the files with the searched extensions, except doc comments, Markdown files
+    /// (unless they count as usages) and the string literals of the Rust files. In templates,
+    /// only the expressions and tags count.
+    Code,
+    /// Doc comments of the Rust files, and the Markdown files, e.g. the READMEs, unless they
+    /// count as usages or are ignored.
+    Documentation,
+    /// String literals of the Rust files, outside of the doc comments.
+    Strings,
+}
+
+impl Lines {
+    fn cache(self) -> &'static str {
+        match self {
+            Lines::Code => "search.bin",
+            Lines::Documentation => "documentation.bin",
+            Lines::Strings => "strings.bin",
+        }
+    }
+
+    /// Whether a match between byte offsets in a line of a searched file is of this kind.
+    pub(crate) fn matches(self, format: Format, line: &[u8], start: usize, end: usize) -> bool {
+        if format == Format::Template {
+            return self == Lines::Code && in_template_code(line, start) && {
+                let identifier =
+                    |i: Option<&u8>| i.is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_');
+                !identifier(start.checked_sub(1).and_then(|i| line.get(i)))
+                    && !identifier(line.get(end))
+            };
+        }
+        // Markdown files are only searched for the kind of lines given by the configuration
+        if format == Format::Markdown {
+            return self != Lines::Strings;
+        }
+        let documentation = format == Format::Rust && {
+            let line = line.trim_ascii_start();
+            ["///", "//!", "/**", "/*!", "#[doc", "#![doc"]
+                .iter()
+                .any(|p| line.starts_with(p.as_bytes()))
+        };
+        if documentation {
+            return self == Lines::Documentation;
+        }
+        let string = format == Format::Rust && in_string(line, start);
+        match self {
+            Lines::Code => !string,
+            Lines::Strings => string,
+            Lines::Documentation => false,
+        }
+    }
+}
+
+/// Format of a searched file, which determines how its lines are classified.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Rust,
+    Markdown,
+    /// Askama, Tera or Handlebars templates, e.g. HTML files.
+    Template,
+    Other,
+}
+
+const MARKDOWN: [&str; 2] = ["md", "markdown"];
+
+const TEMPLATES: [&str; 8] = [
+    "html",
+    "htm",
+    "jinja",
+    "jinja2",
+    "j2",
+    "tera",
+    "hbs",
+    "handlebars",
+];
+
+impl Format {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("rs") => Format::Rust,
+            Some(e) if MARKDOWN.contains(&e) => Format::Markdown,
+            Some(e) if TEMPLATES.contains(&e) => Format::Template,
+            _ => Format::Other,
+        }
+    }
+}
+
+/// Whether a byte offset of a Rust line is inside a string literal opened on this line.
+///
+/// Strings spanning several lines and raw strings containing backslashes are not recognized.
+fn in_string(line: &[u8], offset: usize) -> bool {
+    let mut string = false;
+    let mut i = 0;
+    while i < offset.min(line.len()) {
+        match (string, line[i]) {
+            (true, b'\\') => i += 1,
+            (true, b'"') => string = false,
+            (false, b'"') => string = true,
+            // Character literals, e.g. `'"'`, and comments
+            (false, b'\'') if line.get(i + 2) == Some(&b'\'') => i += 2,
+            (false, b'/') if line.get(i + 1) == Some(&b'/') => return false,
+            _ => {}
+        }
+        i += 1;
+    }
+    string
+}
+
+/// Whether a byte offset of a template line is inside an expression (`{{ }}`) or a tag
+/// (`{% %}`), e.g. a variable, a method call or a filter, rather than in the text or a comment
+/// (`{# #}`, or `{{! }}` in Handlebars). Expressions and tags opened on a previous line are
+/// recognized if the line closes them after the offset.
+fn in_template_code(line: &[u8], offset: usize) -> bool {
+    // Whether the last opened delimiter is code rather than a comment, if still open
+    let mut inside = None;
+    let mut delimited = false;
+    let mut i = 0;
+    while i < offset.min(line.len()) {
+        match &line[i..] {
+            [b'{', b'{', b'!', ..] | [b'{', b'#', ..] => inside = Some(false),
+            [b'{', b'{' | b'%', ..] => inside = Some(true),
+            [b'}', b'}', ..] | [b'%' | b'#', b'}', ..] => inside = None,
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        delimited = true;
+        i += 2;
+    }
+    if let Some(code) = inside {
+        return code;
+    }
+    // Closed after the offset before being opened again
+    let rest = line.get(offset..).unwrap_or_default();
+    !delimited
+        && rest.windows(2).find_map(|w| match w {
+            b"}}" | b"%}" => Some(true),
+            b"{{" | b"{%" | b"{#" | b"#}" => Some(false),
+            _ => None,
+        }) == Some(true)
+}
+
+/// Settings of the search from the configuration, by file extension.
+#[derive(Clone, Default)]
+pub struct Rules {
+    /// Extensions searched regardless of `--extensions`.
+    searched: HashSet<String>,
+    /// Extensions never searched.
+    skipped: HashSet<String>,
+    /// Patterns of which the lines must match one, capturing the names that they use.
+    patterns: HashMap<String, Vec<Pattern>>,
+    /// Patterns of the files matching globs relative to the workspace root, which are searched.
+    globs: Vec<(String, Vec<Pattern>)>,
+    markdown: Markdown,
+    /// Hash of the settings, as the cached counts depend on them.
+    hash: u64,
+}
+
+/// A regular expression, and its capture group with the name of an item.
+#[derive(Clone)]
+struct Pattern {
+    regex: regex::bytes::Regex,
+    group: usize,
+}
+
+/// Identifier standing for `{name}` in the patterns.
+const NAME: &str = "(?P<name>[A-Za-z_][A-Za-z0-9_]*)";
+
+impl Rules {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let settings = format!(
+            "{:?}{:?}{:?}{:?}",
+            config.extensions, config.registrations, config.markdown, config.patterns
+        );
+        let mut rules = Self {
+            markdown: config.markdown,
+            hash: cache::hash(settings.as_bytes()),
+            ..Default::default()
+        };
+        for (extension, settings) in &config.extensions {
+            if settings.search.unwrap_or(true) {
+                rules.searched.insert(extension.clone());
+            } else {
+                rules.skipped.insert(extension.clone());
+            }
+            if let Some(pattern) = &settings.pattern {
+                let pattern = Self::pattern(extension, pattern)?;
+                rules
+                    .patterns
+                    .entry(extension.clone())
+                    .or_default()
+                    .push(pattern);
+            }
+        }
+        for registration in &config.registrations {
+            let regex = regex::bytes::Regex::new(&registration.regex).map_err(|e| {
+                anyhow::anyhow!("Invalid registration regex {:?}: {}", registration.regex, e)
+            })?;
+            anyhow::ensure!(
+                regex.captures_len() > 1,
+                "The registration regex {:?} has no capture group for the name",
+                registration.regex
+            );
+            for extension in &registration.extensions {
+                rules.searched.insert(extension.clone());
+                rules
+                    .patterns
+                    .entry(extension.clone())
+                    .or_default()
+                    .push(Pattern {
+                        regex: regex.clone(),
+                        group: 1,
+                    });
+            }
+        }
+        for (glob, patterns) in &config.patterns {
+            anyhow::ensure!(!patterns.is_empty(), "No pattern for the {} files", glob);
+            let patterns = patterns
+                .iter()
+                .map(|p| Self::pattern(glob, p))
+                .collect::<anyhow::Result<_>>()?;
+            rules.globs.push((glob.clone(), patterns));
+        }
+        Ok(rules)
+    }
+
+    /// Regular expression of a pattern, with `{name}` capturing an identifier.
+    fn pattern(files: &str, pattern: &str) -> anyhow::Result<Pattern> {
+        anyhow::ensure!(
+            pattern.matches("{name}").count() == 1,
+            "The pattern of the {} files must contain `{{name}}` once: {:?}",
+            files,
+            pattern
+        );
+        let regex = regex::bytes::Regex::new(&pattern.replace("{name}", NAME))
+            .map_err(|e| anyhow::anyhow!("Invalid pattern for the {} files: {}", files, e))?;
+        let group = regex
+            .capture_names()
+            .position(|n| n == Some("name"))
+            .unwrap();
+        Ok(Pattern { regex, group })
+    }
+
+    /// Patterns of a file, by extension and by glob, if any.
+    fn patterns_of(&self, workspace: &Path, path: &Path) -> Option<Vec<&Pattern>> {
+        let extension = path.extension().and_then(|e| e.to_str());
+        let globbed = self
+            .globs
+            .iter()
+            .filter(|(glob, _)| Self::globbed(glob, workspace, path));
+        let patterns: Vec<&Pattern> = extension
+            .and_then(|e| self.patterns.get(e))
+            .into_iter()
+            .chain(globbed.map(|(_, patterns)| patterns))
+            .flatten()
+            .collect();
+        (!patterns.is_empty()).then_some(patterns)
+    }
+
+    /// Whether a glob matches a file, relative to the workspace root.
+    fn globbed(glob: &str, workspace: &Path, path: &Path) -> bool {
+        path.strip_prefix(workspace).is_ok_and(|relative| {
+            let relative = crate::index::slash_path(relative);
+            crate::owners::glob(glob.as_bytes(), relative.as_bytes())
+        })
+    }
+}
+
+/// Names captured by the patterns in a line, without their path, e.g. `run` for `jobs::run` or
+/// `jobs.run`.
+fn captured<'l>(patterns: &[&Pattern], line: &'l [u8]) -> HashSet<&'l [u8]> {
+    patterns
+        .iter()
+        .flat_map(|p| p.regex.captures_iter(line).filter_map(|c| c.get(p.group)))
+        .map(|m| {
+            let name = m.as_bytes();
+            let start = name
+                .iter()
+                .rposition(|c| *c == b':' || *c == b'.')
+                .map_or(0, |i| i + 1);
+            &name[start..]
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct FileCounts {
+    len: u64,
+    modified: Option<SystemTime>,
+    /// Hash of the contents, in case the modification time changed but not the contents.
+    hash: u64,
+    /// Bitset of the names for which the file was searched, by index into [`Cache::names`].
+    searched: Vec<u64>,
+    /// Number of lines matching each searched name, if any, by index into [`Cache::names`].
+    counts: Vec<(usize, usize)>,
+}
+
+impl FileCounts {
+    fn new(len: u64, modified: Option<SystemTime>, hash: u64) -> Self {
+        Self {
+            len,
+            modified,
+            hash,
+            searched: vec![],
+            counts: vec![],
+        }
+    }
+
+    fn is_searched(&self, name: usize) -> bool {
+        self.searched
+            .get(name / 64)
+            .is_some_and(|w| w >> (name % 64) & 1 == 1)
+    }
+
+    /// Record the counts of a search for some names, given by their index into
+    /// [`Cache::names`].
+    fn add(&mut self, names: &[usize], counts: Vec<(usize, usize)>) {
+        for &name in names {
+            if self.searched.len() <= name / 64 {
+                self.searched.resize(name / 64 + 1, 0);
+            }
+            self.searched[name / 64] |= 1 << (name % 64);
+        }
+        self.counts
+            .extend(counts.into_iter().map(|(i, c)| (names[i], c)));
+    }
+}
+
+#[derive(Clone)]
+struct Searcher<'a> {
+    /// A single automaton over all the names allows scanning each line once.
+    automaton: aho_corasick::AhoCorasick,
+    /// Lines are first filtered with ripgrep's SIMD literal search, before finding the matching names.
+    matcher: grep_regex::RegexMatcher,
+    searcher: grep_searcher::Searcher,
+    names: &'a [&'a str],
+    lines: Lines,
+    rules: &'a Rules,
+    workspace: &'a Path,
+}
+
+impl<'a> Searcher<'a> {
+    fn new(
+        names: &'a [&'a str],
+        lines: Lines,
+        rules: &'a Rules,
+        workspace: &'a Path,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            automaton: aho_corasick::AhoCorasick::new(names)?,
+            matcher: grep_regex::RegexMatcherBuilder::new().build_literals(names)?,
+            searcher: grep_searcher::SearcherBuilder::new()
+                .binary_detection(grep_searcher::BinaryDetection::quit(b'\x00'))
+                // SAFETY: As in ripgrep, files truncated during the search may cause a SIGBUS.
+                .memory_map(unsafe { grep_searcher::MmapChoice::auto() })
+                .build(),
+            names,
+            lines,
+            rules,
+            workspace,
+        })
+    }
+
+    /// Search either a file or its already read contents.
+    fn search(&mut self, path: &Path, contents: Option<&[u8]>) -> Vec<(usize, usize)> {
+        let mut counts = vec![0usize; self.names.len()];
+        let mut matched = HashSet::<usize>::default();
+        let format = Format::of(path);
+        let pattern = self.rules.patterns_of(self.workspace, path);
+        let sink = grep_searcher::sinks::Bytes(|_, line| {
+            let captured = pattern.as_ref().map(|p| captured(p, line));
+            // Count each name at most once per line
+            matched.extend(
+                self.automaton
+                    .find_overlapping_iter(line)
+                    .filter(|m| self.lines.matches(format, line, m.start(), m.end()))
+                    .filter(|m| {
+                        captured
+                            .as_ref()
+                            .is_none_or(|c| c.contains(&line[m.start()..m.end()]))
+                    })
+                    .map(|m| m.pattern().as_usize()),
+            );
+            for i in matched.drain() {
+                counts[i] += 1;
+            }
+            Ok(true)
+        });
+        let res = match contents {
+            Some(contents) => self.searcher.search_slice(&self.matcher, contents, sink),
+            None => self.searcher.search_path(&self.matcher, path, sink),
+        };
+        if let Err(e) = res {
+            warn!(path:% = path.display(); "Failed to search {:?}: {}", path, e);
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .filter(|(_, c)| *c > 0)
+            .collect()
+    }
+}
+
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Whether the beginning of some contents contains a NUL byte, like git and ripgrep.
+fn is_binary(contents: &[u8]) -> bool {
+    contents[..contents.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+fn looks_binary(path: &Path) -> std::io::Result<bool> {
+    let mut buf = [0; BINARY_SNIFF_LEN];
+    let n = std::io::Read::read(&mut std::fs::File::open(path)?, &mut buf)?;
+    Ok(is_binary(&buf[..n]))
+}
+
+/// Outcome of searching a file.
+enum Outcome {
+    Skipped,
+    /// Counts reused from the cache, which may lack some of the names.
+    Cached(FileCounts),
+    Searched(FileCounts),
+}
+
+/// Search a file for all the names, unless its contents did not change since the previous run.
+///
+/// `previous` is `None` when the cache is disabled. `ids` are the indices of the names into
+/// [`Cache::names`].
+fn search_file(
+    searcher: &mut Searcher,
+    ids: &[usize],
+    path: &Path,
+    metadata: &std::fs::Metadata,
+    previous: Option<&Cache>,
+) -> Outcome {
+    let (len, modified) = (metadata.len(), metadata.modified().ok());
+    let Some(previous) = previous else {
+        if looks_binary(path).unwrap_or(true) {
+            warn!(path:% = path.display(); "Skipping {:?}, which looks binary", path);
+            return Outcome::Skipped;
+        }
+        let mut entry = FileCounts::new(len, modified, 0);
+        entry.add(ids, searcher.search(path, None));
+        return Outcome::Searched(entry);
+    };
+    let entry = previous.files.get(path);
+    if let Some(e) = entry.filter(|e| e.len == len && e.modified == modified && modified.is_some())
+    {
+        return Outcome::Cached(e.clone());
+    }
+    let contents = match std::fs::read(path) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!(path:% = path.display(); "Failed to read {:?}: {}", path, e);
+            return Outcome::Skipped;
+        }
+    };
+    if is_binary(&contents) {
+        warn!(path:% = path.display(); "Skipping {:?}, which looks binary", path);
+        return Outcome::Skipped;
+    }
+    let hash = cache::hash(&contents);
+    match entry {
+        Some(e) if e.hash == hash => Outcome::Cached(FileCounts {
+            modified,
+            ..e.clone()
+        }),
+        _ => {
+            let mut entry = FileCounts::new(len, modified, hash);
+            entry.add(ids, searcher.search(path, Some(&contents)));
+            Outcome::Searched(entry)
+        }
+    }
+}
+
+/// Count the lines containing each name in the workspace files with the given extensions, where
+/// it is of the given kind, with the Markdown files for the documentation or the usages according
+/// to the configuration, and only the Rust files for the string literals.
+///
+/// With the cache, the files whose contents did not change since the previous run are only
+/// searched for the names that they were not searched for yet.
+///
+/// Only the `roots` are searched if any, relative to the workspace root. The lines of a name are
+/// only counted inside its scope if it has one, e.g. the crate of a binary.
+pub fn count(
+    args: &AnalysisFlags,
+    rules: &Rules,
+    roots: &[PathBuf],
+    names: &[&str],
+    scopes: &[Option<PathBuf>],
+    lines: Lines,
+) -> anyhow::Result<Vec<usize>> {
+    let workspace = &args.workspace;
+    let use_cache = !args.no_cache;
+    let mut totals = vec![0usize; names.len()];
+    if names.is_empty() {
+        return Ok(totals);
+    }
+    let searcher = Searcher::new(names, lines, rules, workspace)?;
+
+    let cache_path = cache::dir(workspace).join(lines.cache());
+    let mut previous = use_cache.then(|| {
+        MEMORY.lock().unwrap()[lines as usize]
+            .take()
+            .or_else(|| cache::read::<Cache>(&cache_path))
+            .filter(|c| c.rules == rules.hash)
+            .unwrap_or_else(|| Cache {
+                rules: rules.hash,
+                ..Default::default()
+            })
+    });
+    let ids = match &mut previous {
+        Some(previous) => previous.intern(names),
+        None => (0..names.len()).collect(),
+    };
+
+    let scopes = scopes
+        .iter()
+        .map(|s| s.as_ref().map(|s| workspace.join(s)))
+        .collect_vec();
+    let files = files(args, rules, roots, lines);
+
+    let results: Vec<(PathBuf, Outcome)> = files
+        .into_par_iter()
+        .map_with(searcher, |searcher, (metadata, path)| {
+            let outcome = search_file(searcher, &ids, &path, &metadata, previous.as_ref());
+            (path, outcome)
+        })
+        .collect();
+
+    // The cached files are searched for their missing names, with one searcher for each set of
+    // missing names, which is usually shared by all the files
+    let mut entries = vec![];
+    let mut missing: HashMap<Vec<usize>, Vec<(PathBuf, FileCounts)>> = HashMap::new();
+    let (mut n_searched, mut n_cached, mut n_partial) = (0, 0, 0);
+    for (path, outcome) in results {
+        match outcome {
+            Outcome::Skipped => {}
+            Outcome::Searched(entry) => {
+                n_searched += 1;
+                entries.push((path, entry));
+            }
+            Outcome::Cached(entry) => {
+                let names = (0..names.len())
+                    .filter(|i| !entry.is_searched(ids[*i]))
+                    .collect_vec();
+                if names.is_empty() {
+                    n_cached += 1;
+                    entries.push((path, entry));
+                } else {
+                    n_partial += 1;
+                    missing.entry(names).or_default().push((path, entry));
+                }
+            }
+        }
+    }
+    for (indices, files) in missing {
+        let subset = indices.iter().map(|i| names[*i]).collect_vec();
+        let subset_ids = indices.iter().map(|i| ids[*i]).collect_vec();
+        let searcher = Searcher::new(&subset, lines, rules, workspace)?;
+        entries.par_extend(files.into_par_iter().map_with(
+            searcher,
+            |searcher, (path, mut entry)| {
+                entry.add(&subset_ids, searcher.search(&path, None));
+                (path, entry)
+            },
+        ));
+    }
+
+    // Index of the names into the searched ones
+    let queried: HashMap<usize, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let mut current = previous.map(|p| Cache {
+        files: Default::default(),
+        ..p
+    });
+    for (path, entry) in entries {
+        for (id, c) in &entry.counts {
+            let Some(&i) = queried.get(id) else {
+                continue;
+            };
+            if scopes[i].as_ref().is_none_or(|s| path.starts_with(s)) {
+                totals[i] += c;
+            }
+        }
+        if let Some(current) = &mut current {
+            current.files.insert(path, entry);
+        }
+    }
+    debug!(
+        "Searched {} files, reused cached counts for {}, and searched {} for the uncached names",
+        n_searched, n_cached, n_partial
+    );
+    if let Some(current) = current {
+        cache::write(&cache_path, &current);
+        MEMORY.lock().unwrap()[lines as usize] = Some(current);
+    }
+    Ok(totals)
+}
+
+/// Files to search, with their metadata, under the roots: the Rust and Markdown files for the
+/// documentation, the Rust files for the string literals, and otherwise those with the given
+/// extensions, searched according to the configuration or matching the globs of its patterns.
+/// Only the files of `--files-from` are searched if it is given. Markdown files are only searched for
+/// the documentation or the usages, as configured.
+fn files(
+    args: &AnalysisFlags,
+    rules: &Rules,
+    roots: &[PathBuf],
+    lines: Lines,
+) -> Vec<(std::fs::Metadata, PathBuf)> {
+    let workspace = &args.workspace;
+    let extensions: HashSet<&str> = match lines {
+        Lines::Code => args
+            .extensions
+            .iter()
+            .chain(&rules.searched)
+            .map(String::as_str)
+            .filter(|e| !MARKDOWN.contains(e) && !rules.skipped.contains(*e))
+            .chain(
+                MARKDOWN
+                    .into_iter()
+                    .filter(|_| rules.markdown == Markdown::Usage),
+            )
+            .collect(),
+        Lines::Documentation => MARKDOWN
+            .into_iter()
+            .filter(|_| rules.markdown == Markdown::Documentation)
+            .chain(["rs"])
+            .collect(),
+        Lines::Strings => ["rs"].into(),
+    };
+    let excluded: Vec<PathBuf> = if args.exclude_usages {
+        crate::metadata::excluded_dirs(workspace)
+            .iter()
+            .map(|e| workspace.join(e))
+            .collect()
+    } else {
+        vec![]
+    };
+    let roots = if roots.is_empty() {
+        vec![workspace.clone()]
+    } else {
+        roots.iter().map(|r| workspace.join(r)).collect()
+    };
+    roots
+        .iter()
+        .flat_map(|root| {
+            // Symbolic links may lead outside of the workspace, or into cycles
+            walkdir::WalkDir::new(root)
+                .min_depth(1)
+                .follow_links(args.follow_symlinks)
+                .into_iter()
+                .filter_entry(|e| {
+                    !e.path().join("CACHEDIR.TAG").exists()
+                        && !excluded.iter().any(|x| e.path() == x)
+                })
+        })
+        .filter_map(|e| match e {
+            Ok(e) => Some(e),
+            Err(e) => {
+                match e.loop_ancestor() {
+                    Some(ancestor) => warn!(
+                        "Not following the symbolic link {:?}, which loops back to {:?}",
+                        e.path().unwrap_or(ancestor),
+                        ancestor
+                    ),
+                    None => debug!("Skipping a file: {}", e),
+                }
+                None
+            }
+        })
+        // Files reached through several links are only searched once
+        .unique_by(|f| {
+            if args.follow_symlinks {
+                f.path().canonicalize().unwrap_or_else(|_| f.path().into())
+            } else {
+                f.path().into()
+            }
+        })
+        .filter(|f| {
+            f.file_type().is_file()
+                && (f
+                    .path()
+                    .extension()
+                    .and_then(|f| f.to_str())
+                    .is_some_and(|e| extensions.iter().any(|x| *x == e))
+                    || lines == Lines::Code
+                        && rules
+                            .globs
+                            .iter()
+                            .any(|(glob, _)| Rules::globbed(glob, workspace, f.path())))
+        })
+        .filter(|f| {
+            args.files_from
+                .as_ref()
+                .is_none_or(|l| l.contains(workspace, f.path()))
+        })
+        .filter_map(|f| Some((f.metadata().ok()?, f.into_path())))
+        .filter(|(metadata, path)| {
+            let skip = metadata.len() > args.max_file_size;
+            if skip {
+                debug!(
+                    "Skipping {:?} ({} bytes), which exceeds --max-file-size",
+                    path,
+                    metadata.len()
+                );
+            }
+            !skip
+        })
+        .collect()
+}
+
+/// Rust files under the roots, e.g. to scan the invocations of macros.
+pub fn rust_files(args: &AnalysisFlags, roots: &[PathBuf]) -> Vec<PathBuf> {
+    files(args, &Rules::default(), roots, Lines::Strings)
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect()
+}
+
+/// Lines containing a name of the given kind in the searched files, as the path relative to the
+/// workspace root, the zero-based line and its contents, bypassing the cache. Only the lines
+/// inside the scope are returned if there is one.
+pub fn lines(
+    args: &AnalysisFlags,
+    rules: &Rules,
+    roots: &[PathBuf],
+    name: &str,
+    scope: Option<&Path>,
+    kind: Lines,
+) -> anyhow::Result<Vec<(String, usize, String)>> {
+    let workspace = &args.workspace;
+    let scope = scope.map(|s| workspace.join(s));
+    let matcher = grep_regex::RegexMatcherBuilder::new().build_literals(&[name])?;
+    let mut searcher = grep_searcher::SearcherBuilder::new()
+        .binary_detection(grep_searcher::BinaryDetection::quit(b'\x00'))
+        .build();
+    let mut lines = vec![];
+    for (_, path) in files(args, rules, roots, kind) {
+        if scope.as_ref().is_some_and(|s| !path.starts_with(s)) {
+            continue;
+        }
+        let format = Format::of(&path);
+        let pattern = rules.patterns_of(workspace, &path);
+        let relative = path
+            .strip_prefix(workspace)
+            .map(crate::index::slash_path)
+            .unwrap_or_else(|_| path.display().to_string());
+        let sink = grep_searcher::sinks::Lossy(|number, line| {
+            let captured = pattern.as_ref().map(|p| captured(p, line.as_bytes()));
+            if !line.match_indices(name).any(|(i, _)| {
+                kind.matches(format, line.as_bytes(), i, i + name.len())
+                    && captured
+                        .as_ref()
+                        .is_none_or(|c| c.contains(name.as_bytes()))
+            }) {
+                return Ok(true);
+            }
+            lines.push((
+                relative.clone(),
+                number as usize - 1,
+                line.trim().to_string(),
+            ));
+            Ok(true)
+        });
+        if let Err(e) = searcher.search_path(&matcher, &path, sink) {
+            warn!(path:% = path.display(); "Failed to search {:?}: {}", path, e);
+        }
+    }
+    lines.sort();
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn in_string() {
+        let line = br#"let s = "name"; // "comment""#;
+        assert!(!super::in_string(line, 4));
+        assert!(super::in_string(line, 9));
+        assert!(!super::in_string(line, 15));
+        assert!(!super::in_string(line, 20));
+        // Escaped quotes and character literals
+        let line = br#"f("a \" name", '"', name)"#;
+        assert!(super::in_string(line, 8));
+        let offset = line.len() - 5;
+        assert!(!super::in_string(line, offset));
+    }
+
+    #[test]
+    fn in_template_code() {
+        let line = b"Hello {{ user.name }} {# name #} {% if name %}name{% endif %}";
+        let at = |needle: &str, from: usize| {
+            from + line[from..]
+                .windows(needle.len())
+                .position(|w| w == needle.as_bytes())
+                .unwrap()
+        };
+        assert!(!super::in_template_code(line, 0));
+        assert!(super::in_template_code(line, at("name", 0)));
+        assert!(!super::in_template_code(line, at("name", 20)));
+        assert!(super::in_template_code(line, at("name", 32)));
+        assert!(!super::in_template_code(line, at("name", 40)));
+        // Handlebars comments
+        assert!(!super::in_template_code(b"{{! name }}", 4));
+        // Opened on a previous line, and closed on this one
+        assert!(super::in_template_code(b"  user.name }}", 7));
+        assert!(!super::in_template_code(b"  name {{ x }}", 2));
+    }
+}
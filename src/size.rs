@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use crate::components;
+use crate::index::Index;
+use crate::symbols::Sources;
+use crate::Finding;
+
+/// Number of lines of the definition of each finding, by symbol, including its doc comment and
+/// attributes, as they would be deleted with it.
+///
+/// The body is delimited by the enclosing range of the index if it provides it, and otherwise by
+/// matching the braces in the source. Findings whose source cannot be read are missing.
+pub fn lines<'a>(
+    index: &Index,
+    workspace: &std::path::Path,
+    findings: &[Finding<'a>],
+) -> HashMap<&'a str, usize> {
+    let enclosing: HashMap<&str, usize> = index
+        .occurrences
+        .iter()
+        .filter(|o| o.is_definition())
+        .filter_map(|o| Some((index.str(o.symbol), o.enclosing_span()?.end_line)))
+        .collect();
+    let mut sources = Sources::new(workspace);
+    findings
+        .iter()
+        .filter_map(|f| {
+            let start = f.span.start_line;
+            let end = match enclosing.get(f.symbol) {
+                Some(end) => *end,
+                None => {
+                    let lines = sources.lines(f.path)?.get(start..)?;
+                    start + body_end(&lines.join("\n"))?
+                }
+            };
+            let mut first = start;
+            while let Some(l) = first.checked_sub(1).and_then(|l| sources.line(f.path, l)) {
+                let l = l.trim_start();
+                if !l.starts_with("///") && !l.starts_with("#[") {
+                    break;
+                }
+                first -= 1;
+            }
+            Some((f.symbol, end.max(start) - first + 1))
+        })
+        .collect()
+}
+
+/// Line of the end of the item declared on the first line of the contents: the one closing its
+/// braces, or the one of the semicolon ending a declaration without a body.
+///
+/// Braces in comments and in string and character literals are skipped, including in the
+/// literals spanning several lines.
+fn body_end(contents: &str) -> Option<usize> {
+    let bytes = contents.as_bytes();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(end) = components::literal_end(contents, i) {
+            i = end;
+            continue;
+        }
+        match bytes[i] {
+            b'{' => depth += 1,
+            b'}' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    break;
+                }
+            }
+            b';' if depth == 0 => break,
+            _ => {}
+        }
+        i += 1;
+    }
+    (i < bytes.len()).then(|| bytes[..i].iter().filter(|b| **b == b'\n').count())
+}
+
+/// A number with thousands separators, e.g. `4,200`.
+pub fn separated(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn body_end() {
+        assert_eq!(super::body_end("fn f() {\n    g();\n}\nfn g() {}"), Some(2));
+        assert_eq!(super::body_end("fn f();\nfn g() {}"), Some(0));
+        assert_eq!(super::body_end("struct S {\n    a: u8,\n"), None);
+        // Braces in literals spanning several lines, and in comments
+        let source = "fn f() {\n    let s = \"{\n\\\"}\";\n    /* }\n */ // }\n    '}';\n}";
+        assert_eq!(super::body_end(source), Some(6));
+        let source = "fn f() {\n    let s = r#\"\n\"}\"#;\n    &'a ();\n}\n";
+        assert_eq!(super::body_end(source), Some(4));
+    }
+}
@@ -0,0 +1,177 @@
+use annotate_snippets::{AnnotationKind, Level, Origin, Renderer, Snippet};
+
+use std::collections::HashMap;
+
+use crate::explain::Explanation;
+use crate::index::Encoding;
+use crate::{read_source, AnalysisFlags, Finding, PassStats};
+
+/// Print the findings like rustc diagnostics, with the source line, a caret span under the name,
+/// and a note on each pass that kept them, with the evidence of `--explain` if available, and on
+/// the number of lines of their definition.
+pub fn report(
+    args: &AnalysisFlags,
+    findings: &[Finding],
+    passes: &[PassStats],
+    explanations: &HashMap<&str, Vec<Explanation>>,
+    sizes: &HashMap<&str, usize>,
+    out: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    let notes = passes
+        .iter()
+        .filter_map(|p| Some(format!("{}: {}", p.name, note(args, &p.name)?)))
+        .collect::<Vec<_>>();
+    let blames = if args.blame {
+        crate::blame::blame(&args.workspace, &findings.iter().collect::<Vec<_>>())
+    } else {
+        Default::default()
+    };
+    // Colored like the rest of the output, e.g. when forwarded by the daemon
+    let renderer = if colored::control::SHOULD_COLORIZE.should_colorize() {
+        Renderer::styled()
+    } else {
+        Renderer::plain()
+    };
+    let mut sources = Sources::default();
+    for f in findings {
+        let line = sources.line(args, f.path, f.span.start_line).to_string();
+        let mut messages = vec![];
+        for r in &f.references {
+            let line = sources.line(args, &r.path, r.span.start_line);
+            messages.push(format!(
+                "used at {}:{}:{}",
+                r.path,
+                r.span.start_line + 1,
+                Encoding::Utf32.column(line, r.span.start_column) + 1
+            ));
+        }
+        if f.proc_macro {
+            messages.push("in a proc-macro crate, whose items may be used by the expanded code: only the references within the crate are counted".into());
+        }
+        if f.rustc_dead_code {
+            messages.push("also reported by rustc's dead_code lint".into());
+        }
+        if let Some(b) = blames.get(&(f.path.to_string(), f.span.start_line)) {
+            messages.push(format!(
+                "last changed by {} on {} in {}",
+                b.author,
+                b.date(),
+                &b.commit[..8]
+            ));
+        }
+        let explained = explanations.get(f.symbol).map(|e| {
+            e.iter()
+                .map(|e| format!("{}: {}", e.pass, e.evidence))
+                .collect::<Vec<_>>()
+        });
+        messages.extend(explained.unwrap_or_else(|| notes.clone()));
+        if let Some(n) = sizes.get(f.symbol) {
+            messages.push(format!(
+                "{} line{} with its documentation",
+                n,
+                if *n == 1 { "" } else { "s" }
+            ));
+        }
+        if args.show_fingerprints {
+            messages.push(format!("fingerprint {}", f.fingerprint()));
+        }
+
+        let title = format!("possibly unused pub {} `{}`", f.kind, f.display_name);
+        let label = if f.bin_only {
+            "make it private, the crate has no library target"
+        } else {
+            "unused in the workspace"
+        };
+        // Columns are in bytes, and the name may have moved since the index was generated
+        let (start, end) = (f.span.start_column, f.span.end_column);
+        let located =
+            f.span.start_line == f.span.end_line && line.get(start..end) == Some(f.display_name);
+        let group = if located {
+            Level::WARNING.primary_title(title).element(
+                Snippet::source(line.as_str())
+                    .line_start(f.span.start_line + 1)
+                    .path(f.path)
+                    .annotation(AnnotationKind::Primary.span(start..end).label(label)),
+            )
+        } else {
+            Level::WARNING.primary_title(title).element(
+                Origin::path(f.path)
+                    .line(f.span.start_line + 1)
+                    .char_column(Encoding::Utf32.column(&line, start) + 1),
+            )
+        };
+        let group = group.elements(messages.iter().map(|m| Level::NOTE.message(m.as_str())));
+        writeln!(out, "{}\n", renderer.render(&[group]))?;
+    }
+    Ok(())
+}
+
+/// Lines of the source files, read once.
+#[derive(Default)]
+struct Sources<'a>(HashMap<&'a str, Vec<String>>);
+
+impl<'a> Sources<'a> {
+    /// A line of a file, empty if it cannot be read.
+    fn line(&mut self, args: &AnalysisFlags, path: &'a str, line: usize) -> &str {
+        self.0
+            .entry(path)
+            .or_insert_with(|| {
+                read_source(&args.workspace.join(path))
+                    .map(|c| c.lines().map(String::from).collect())
+                    .unwrap_or_default()
+            })
+            .get(line)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+}
+
+/// Why a finding was kept by a pass, or `None` for the steps that are not filters.
+fn note(args: &AnalysisFlags, pass: &str) -> Option<String> {
+    let uses = match args.max_uses {
+        0 => "no".to_string(),
+        n => format!("at most {}", n),
+    };
+    // Transitively dead items are referenced by the other findings
+    let transitive = if args.transitive {
+        ", or only used by other findings"
+    } else {
+        ""
+    };
+    if let Some(l) = crate::bindings::language(pass) {
+        return Some(format!(
+            "{} lines of the {} sources naming it",
+            uses, l.label
+        ));
+    }
+    Some(match pass {
+        "declarations" | "packages, kinds" | "ignored" => return None,
+        "workspace.exclude" => "not in a directory excluded from the workspace".into(),
+        "examples" => "not defined in an example".into(),
+        "proc-macro entry points" => "not a macro of a proc-macro crate".into(),
+        "public API" => "not reachable from the root of a crate whose API is kept".into(),
+        "references" => format!("{} references in the index{}", uses, transitive),
+        "heuristics" => "not a main function, test or trait item".into(),
+        crate::passes::MACROS_PASS => {
+            format!("{} invocations of the component macros naming it", uses)
+        }
+        crate::passes::ROUTES_PASS => {
+            format!("{} route registrations naming it", uses)
+        }
+        "search" => format!(
+            "{} occurrences of the name in the workspace files besides the definition{}",
+            uses, transitive
+        ),
+        crate::api::RUSTDOC_PASS => "not in the public API documented by rustdoc".into(),
+        crate::passes::STRINGS_PASS => "not named in the string literals".into(),
+        crate::passes::DOCUMENTATION_PASS => {
+            "not mentioned in the doc comments or Markdown files, besides its own".into()
+        }
+        "transitive" => "unused, or only used by other findings".into(),
+        crate::MACRO_GENERATED => "not generated by a macro invocation".into(),
+        "min lines" => format!("at least {} lines long", args.min_lines),
+        "files from" => "defined in a file of --files-from".into(),
+        "rustc dead code" => "not reported by rustc's dead_code lint".into(),
+        _ => "kept".into(),
+    })
+}
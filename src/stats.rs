@@ -0,0 +1,270 @@
+use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet};
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::index::{Index, Sym};
+use crate::symbols::{visibility, Sources};
+use crate::{find_unused, load_index, symbol_package, Config, ItemKind, OutputFormat, StatsFlags};
+
+/// Numbers of `pub` items.
+#[derive(Serialize, Default, Clone, Copy)]
+struct Counts {
+    total: usize,
+    /// Referenced from another crate.
+    cross_crate: usize,
+    /// Only referenced from their crate.
+    intra_crate: usize,
+    /// Without any reference in the index.
+    unreferenced: usize,
+    /// Reported by `check`.
+    reported: usize,
+}
+
+impl Counts {
+    /// Percentage of the `pub` items that are reported, which can be compared across crates of
+    /// different sizes.
+    fn dead_api(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        // Rounded to a tenth of percent
+        (1000.0 * self.reported as f64 / self.total as f64).round() / 10.0
+    }
+}
+
+impl std::ops::AddAssign for Counts {
+    fn add_assign(&mut self, other: Self) {
+        self.total += other.total;
+        self.cross_crate += other.cross_crate;
+        self.intra_crate += other.intra_crate;
+        self.unreferenced += other.unreferenced;
+        self.reported += other.reported;
+    }
+}
+
+#[derive(Serialize)]
+struct Row {
+    kind: ItemKind,
+    #[serde(flatten)]
+    counts: Counts,
+    dead_api_percent: f64,
+}
+
+#[derive(Serialize)]
+struct CrateStats {
+    name: String,
+    kinds: Vec<Row>,
+    /// Over all the kinds.
+    dead_api_percent: f64,
+}
+
+#[derive(Serialize)]
+struct Stats {
+    crates: Vec<CrateStats>,
+    workspace: Vec<Row>,
+    dead_api_percent: f64,
+}
+
+/// Sum of the counts of the rows.
+fn total(rows: &[Row]) -> Counts {
+    let mut total = Counts::default();
+    for r in rows {
+        total += r.counts;
+    }
+    total
+}
+
+/// Definition of a function or method, and crates referencing it.
+#[derive(Default)]
+struct Usage<'a> {
+    /// Document, line and column.
+    definition: Option<(usize, usize, usize)>,
+    crates: HashSet<&'a str>,
+}
+
+/// Crate of each document, i.e. the package of the symbols defined in it.
+fn document_crates(index: &Index) -> HashMap<usize, String> {
+    let mut crates = HashMap::new();
+    for o in index.occurrences.iter().filter(|o| o.is_definition()) {
+        if let Entry::Vacant(e) = crates.entry(o.document) {
+            if let Some(package) = symbol_package(index.str(o.symbol)) {
+                e.insert(package);
+            }
+        }
+    }
+    crates
+}
+
+pub fn stats(args: StatsFlags) -> anyhow::Result<()> {
+    let workspace = &args.analysis.workspace;
+    let index = load_index(&args.analysis)?;
+    let config = Config::load(workspace)?;
+    let (findings, _) = find_unused(&index, &args.analysis, &config)?;
+    let reported: HashSet<&str> = findings.iter().map(|f| f.symbol).collect();
+    let document_crates = document_crates(&index);
+
+    let packages: HashSet<String> = args
+        .analysis
+        .packages
+        .iter()
+        .map(|p| p.replace('-', "_"))
+        .collect();
+    let mut usages: HashMap<Sym, Usage> = index
+        .symbols
+        .values()
+        .filter(|s| {
+            ItemKind::from_scip(s.kind).is_some_and(|k| args.analysis.kinds.contains(&k))
+                && (packages.is_empty()
+                    || symbol_package(index.str(s.symbol)).is_some_and(|p| packages.contains(&p)))
+        })
+        .map(|s| (s.symbol, Usage::default()))
+        .collect();
+    for o in &index.occurrences {
+        let Some(usage) = usages.get_mut(&o.symbol) else {
+            continue;
+        };
+        if o.is_definition() {
+            let span = o.span();
+            usage
+                .definition
+                .get_or_insert((o.document, span.start_line, span.start_column));
+        } else {
+            usage.crates.insert(
+                document_crates
+                    .get(&o.document)
+                    .map(String::as_str)
+                    .unwrap_or_default(),
+            );
+        }
+    }
+
+    let mut sources = Sources::new(workspace);
+    let mut crates: BTreeMap<String, BTreeMap<ItemKind, Counts>> = BTreeMap::new();
+    for (symbol, usage) in usages {
+        let s = &index.symbols[&symbol];
+        let name = index.str(s.symbol);
+        let (Some(kind), Some(package), Some((document, line, column))) = (
+            ItemKind::from_scip(s.kind),
+            symbol_package(name),
+            usage.definition,
+        ) else {
+            continue;
+        };
+        let is_pub = sources
+            .line(&index.documents[document], line)
+            .is_some_and(|l| visibility(l, column) == "pub");
+        if !is_pub {
+            continue;
+        }
+        let counts = crates
+            .entry(package.clone())
+            .or_default()
+            .entry(kind)
+            .or_default();
+        counts.total += 1;
+        if usage.crates.is_empty() {
+            counts.unreferenced += 1;
+        } else if usage.crates.iter().any(|c| *c != package) {
+            counts.cross_crate += 1;
+        } else {
+            counts.intra_crate += 1;
+        }
+        if reported.contains(name) {
+            counts.reported += 1;
+        }
+    }
+
+    let mut totals: BTreeMap<ItemKind, Counts> = BTreeMap::new();
+    for (kind, counts) in crates.values().flatten() {
+        *totals.entry(*kind).or_default() += *counts;
+    }
+    let rows = |kinds: BTreeMap<ItemKind, Counts>| {
+        kinds
+            .into_iter()
+            .map(|(kind, counts)| Row {
+                kind,
+                counts,
+                dead_api_percent: counts.dead_api(),
+            })
+            .collect::<Vec<_>>()
+    };
+    let workspace = rows(totals);
+    let stats = Stats {
+        dead_api_percent: total(&workspace).dead_api(),
+        workspace,
+        crates: crates
+            .into_iter()
+            .map(|(name, kinds)| {
+                let kinds = rows(kinds);
+                CrateStats {
+                    name,
+                    dead_api_percent: total(&kinds).dead_api(),
+                    kinds,
+                }
+            })
+            .collect(),
+    };
+
+    let mut out = std::io::stdout().lock();
+    match args.format {
+        OutputFormat::Json => serde_json::to_writer_pretty(&mut out, &stats)?,
+        OutputFormat::Text => print(&stats, &mut out)?,
+    }
+    Ok(())
+}
+
+fn print(stats: &Stats, out: &mut dyn std::io::Write) -> anyhow::Result<()> {
+    writeln!(
+        out,
+        "{:<24} {:<10} {:>6} {:>12} {:>12} {:>12} {:>9} {:>9}",
+        "Crate",
+        "Kind",
+        "Pub",
+        "Cross-crate",
+        "Intra-crate",
+        "Unreferenced",
+        "Reported",
+        "Dead API"
+    )?;
+    let rows = stats
+        .crates
+        .iter()
+        .flat_map(|c| c.kinds.iter().map(move |r| (c.name.as_str(), r)))
+        .chain(stats.workspace.iter().map(|r| ("(workspace)", r)));
+    for (name, r) in rows {
+        let c = &r.counts;
+        writeln!(
+            out,
+            "{:<24} {:<10} {:>6} {:>12} {:>12} {:>12} {:>9} {:>8.1}%",
+            name,
+            r.kind.to_string(),
+            c.total,
+            c.cross_crate,
+            c.intra_crate,
+            c.unreferenced,
+            c.reported,
+            r.dead_api_percent
+        )?;
+    }
+    writeln!(out)?;
+    writeln!(out, "Dead API per crate, from the highest:")?;
+    let crates = stats
+        .crates
+        .iter()
+        .map(|c| (c.name.as_str(), total(&c.kinds), c.dead_api_percent))
+        .sorted_by(|a, b| b.2.total_cmp(&a.2).then(a.0.cmp(b.0)))
+        .chain([(
+            "(workspace)",
+            total(&stats.workspace),
+            stats.dead_api_percent,
+        )]);
+    for (name, c, percent) in crates {
+        writeln!(
+            out,
+            "{:<24} {:>5.1}% ({} of {} pub items reported)",
+            name, percent, c.reported, c.total
+        )?;
+    }
+    Ok(())
+}
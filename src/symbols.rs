@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use colored::Colorize;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::index::{self, Index, Sym, Symbol};
+use crate::{
+    find_unused_with, load_index, locations, passes, symbol_package, Config, ItemKind, SymbolsFlags,
+};
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum Format {
+    /// One line per symbol, grouped by file.
+    Text,
+    Json,
+    /// Comma-separated values, with a header.
+    Csv,
+}
+
+/// A declared function or method.
+#[derive(Serialize)]
+struct Entry {
+    symbol: String,
+    /// Rust path of the symbol.
+    path: Option<String>,
+    kind: ItemKind,
+    /// File of the definition, relative to the workspace root.
+    file: Option<String>,
+    /// Zero-based line of the definition.
+    line: Option<usize>,
+    /// Visibility qualifier in the source, e.g. `pub` or `pub(crate)`, `private` without any.
+    /// Items of traits and trait implementations have the visibility of the trait.
+    visibility: Option<String>,
+    /// Number of references in the index.
+    references: usize,
+    /// Whether it is reported by `check`.
+    reported: bool,
+    /// Pass that removed it from the candidates, or `ignored` if it is in the `ignore`
+    /// configuration.
+    removed_by: Option<String>,
+}
+
+/// Record the pass removing each candidate.
+#[derive(Default)]
+struct Removals(HashMap<Sym, String>);
+
+impl passes::Hooks for Removals {
+    fn on_filtered(&mut self, _index: &Index, pass: &str, candidate: &Symbol) {
+        self.0.insert(candidate.symbol, pass.to_string());
+    }
+}
+
+pub fn symbols(args: SymbolsFlags) -> anyhow::Result<()> {
+    let workspace = &args.analysis.workspace;
+    let index = load_index(&args.analysis)?;
+    let config = Config::load(workspace)?;
+    let mut removals = Removals::default();
+    let (findings, _) = find_unused_with(
+        &index,
+        &args.analysis,
+        &config,
+        passes::builtin(),
+        &mut removals,
+    )?;
+    let reported: HashSet<&str> = findings.iter().map(|f| f.symbol).collect();
+
+    let packages: HashSet<String> = args
+        .analysis
+        .packages
+        .iter()
+        .map(|p| p.replace('-', "_"))
+        .collect();
+    let selected: HashMap<Sym, (&Symbol, ItemKind)> = index
+        .symbols
+        .values()
+        .filter_map(|s| {
+            let kind = ItemKind::from_scip(s.kind)?;
+            let package = symbol_package(index.str(s.symbol));
+            (args.analysis.kinds.contains(&kind)
+                && (packages.is_empty() || package.is_some_and(|p| packages.contains(&p))))
+            .then_some((s.symbol, (s, kind)))
+        })
+        .collect();
+    let mut locations = locations(&index, |s| selected.contains_key(&s));
+    let mut sources = Sources::new(workspace);
+    let entries = selected
+        .into_iter()
+        .map(|(sym, (s, kind))| {
+            let (definition, references) = locations.remove(&sym).unwrap_or_default();
+            let symbol = index.str(s.symbol);
+            let reported = reported.contains(symbol);
+            Entry {
+                symbol: symbol.into(),
+                path: index::rust_path(symbol),
+                kind,
+                visibility: definition.as_ref().and_then(|d| {
+                    let line = sources.line(&d.path, d.span.start_line)?;
+                    Some(visibility(line, d.span.start_column))
+                }),
+                file: definition.as_ref().map(|d| d.path.clone()),
+                line: definition.as_ref().map(|d| d.span.start_line),
+                references: references.len(),
+                reported,
+                removed_by: match removals.0.get(&sym) {
+                    Some(pass) => Some(pass.clone()),
+                    None if !reported => Some("ignored".into()),
+                    None => None,
+                },
+            }
+        })
+        .sorted_by(|a, b| (&a.file, a.line, &a.symbol).cmp(&(&b.file, b.line, &b.symbol)))
+        .collect_vec();
+
+    let mut out = std::io::stdout().lock();
+    match args.format {
+        Format::Json => serde_json::to_writer_pretty(&mut out, &entries)?,
+        Format::Csv => write_csv(&entries, &mut out)?,
+        Format::Text => write_text(&entries, &mut out)?,
+    }
+    Ok(())
+}
+
+/// Lines of the workspace files, read on demand.
+pub(crate) struct Sources<'a> {
+    workspace: &'a Path,
+    files: HashMap<String, Option<Vec<String>>>,
+}
+
+impl<'a> Sources<'a> {
+    pub(crate) fn new(workspace: &'a Path) -> Self {
+        Self {
+            workspace,
+            files: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn line(&mut self, path: &str, line: usize) -> Option<&str> {
+        self.lines(path)?.get(line).map(String::as_str)
+    }
+
+    /// Lines of a file, if it can be read.
+    pub(crate) fn lines(&mut self, path: &str) -> Option<&[String]> {
+        let workspace = self.workspace;
+        let lines = self.files.entry(path.into()).or_insert_with(|| {
+            let contents = crate::read_source(&workspace.join(path)).ok()?;
+            Some(contents.lines().map(String::from).collect())
+        });
+        lines.as_deref()
+    }
+}
+
+/// Visibility qualifier before the name of a definition.
+pub(crate) fn visibility(line: &str, column: usize) -> String {
+    let prefix = line.get(..column).unwrap_or(line);
+    let Some(start) = prefix.rmatch_indices("pub").map(|(i, _)| i).find(|&i| {
+        prefix[..i]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric())
+    }) else {
+        return "private".into();
+    };
+    let rest = &prefix[start + 3..];
+    match rest.trim_start().strip_prefix('(') {
+        Some(inner) => match inner.find(')') {
+            Some(end) => format!("pub({})", inner[..end].trim()),
+            None => "pub".into(),
+        },
+        None => "pub".into(),
+    }
+}
+
+/// Whether the definition on a line has a doc comment, i.e. a `///` or `/**` comment or a
+/// `#[doc]` attribute among the comments and attributes above it.
+pub(crate) fn is_documented(sources: &mut Sources, path: &str, line: usize) -> bool {
+    let mut line = line;
+    while line > 0 {
+        line -= 1;
+        let Some(l) = sources.line(path, line).map(str::trim_start) else {
+            return false;
+        };
+        if (l.starts_with("///") && !l.starts_with("////"))
+            || l.starts_with("/**")
+            || l.starts_with("#[doc")
+        {
+            return true;
+        }
+        if !l.starts_with("//") && !l.starts_with("#[") {
+            return false;
+        }
+    }
+    false
+}
+
+/// Whether the name of a function definition is not preceded by `fn`, e.g. as it is an argument
+/// of the macro invocation generating the function.
+pub(crate) fn is_macro_generated(line: &str, column: usize) -> bool {
+    let prefix = line.get(..column).unwrap_or(line);
+    let prefix = prefix.strip_suffix("r#").unwrap_or(prefix);
+    !prefix.trim_end().strip_suffix("fn").is_some_and(|p| {
+        p.chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric() && c != '_')
+    })
+}
+
+fn write_text(entries: &[Entry], out: &mut dyn std::io::Write) -> anyhow::Result<()> {
+    for (file, entries) in &entries.iter().group_by(|e| &e.file) {
+        writeln!(
+            out,
+            "{}",
+            file.as_deref().unwrap_or("<no definition>").yellow()
+        )?;
+        for e in entries {
+            let status = match &e.removed_by {
+                Some(pass) => format!("removed by {}", pass),
+                None => "reported".into(),
+            };
+            writeln!(
+                out,
+                "{:<4} {:<12} {:<6} {} {}",
+                e.line.map_or("?".into(), |l| (l + 1).to_string()).blue(),
+                e.visibility.as_deref().unwrap_or("?"),
+                e.references,
+                e.path.as_deref().unwrap_or(&e.symbol),
+                status.dimmed()
+            )?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Quote a CSV field if needed.
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv(entries: &[Entry], out: &mut dyn std::io::Write) -> anyhow::Result<()> {
+    writeln!(
+        out,
+        "symbol,path,kind,file,line,visibility,references,reported,removed_by"
+    )?;
+    for e in entries {
+        let fields = [
+            e.symbol.clone(),
+            e.path.clone().unwrap_or_default(),
+            e.kind.to_string(),
+            e.file.clone().unwrap_or_default(),
+            e.line.map(|l| l.to_string()).unwrap_or_default(),
+            e.visibility.clone().unwrap_or_default(),
+            e.references.to_string(),
+            e.reported.to_string(),
+            e.removed_by.clone().unwrap_or_default(),
+        ];
+        writeln!(out, "{}", fields.iter().map(|f| csv_field(f)).join(","))?;
+    }
+    Ok(())
+}
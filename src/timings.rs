@@ -0,0 +1,100 @@
+use std::borrow::Cow;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Serialize)]
+struct Phase {
+    name: Cow<'static, str>,
+    #[serde(rename = "seconds", serialize_with = "as_secs")]
+    duration: Duration,
+    /// Resident memory at the end of the phase, in bytes.
+    rss: Option<u64>,
+    /// Peak resident memory at the end of the phase, in bytes.
+    peak_rss: Option<u64>,
+}
+
+fn as_secs<S: serde::Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(d.as_secs_f64())
+}
+
+static PHASES: Mutex<Vec<Phase>> = Mutex::new(Vec::new());
+
+/// Records the duration of a phase when dropped.
+pub struct Guard {
+    name: Cow<'static, str>,
+    start: Instant,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let (rss, peak_rss) = memory();
+        let seconds = self.start.elapsed().as_secs_f64();
+        log::debug!(phase = self.name.as_ref(), seconds; "Phase {} took {:.3}s", self.name, seconds);
+        PHASES.lock().unwrap().push(Phase {
+            name: std::mem::take(&mut self.name),
+            duration: self.start.elapsed(),
+            rss,
+            peak_rss,
+        });
+    }
+}
+
+/// Start timing a phase, which ends when the returned guard is dropped.
+pub fn phase(name: impl Into<Cow<'static, str>>) -> Guard {
+    Guard {
+        name: name.into(),
+        start: Instant::now(),
+    }
+}
+
+/// Current and peak resident memory, in bytes. Only available on Linux.
+fn memory() -> (Option<u64>, Option<u64>) {
+    let Ok(status) = std::fs::read_to_string("/proc/self/status") else {
+        return (None, None);
+    };
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|l| l.strip_prefix(name))
+            .and_then(|v| v.trim().trim_end_matches(" kB").parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    };
+    (field("VmRSS:"), field("VmHWM:"))
+}
+
+/// Print the recorded phases to stderr.
+pub fn print(format: Format) {
+    let phases = PHASES.lock().unwrap();
+    match format {
+        Format::Json => eprintln!("{}", serde_json::to_string(&*phases).unwrap()),
+        Format::Text => {
+            let mib = |b: Option<u64>| {
+                b.map_or_else(
+                    || "-".into(),
+                    |b| format!("{:.1}", b as f64 / (1 << 20) as f64),
+                )
+            };
+            eprintln!(
+                "{:<20} {:>10} {:>10} {:>10}",
+                "Phase", "Time (s)", "RSS (MiB)", "Peak (MiB)"
+            );
+            for p in phases.iter() {
+                eprintln!(
+                    "{:<20} {:>10.3} {:>10} {:>10}",
+                    p.name,
+                    p.duration.as_secs_f64(),
+                    mib(p.rss),
+                    mib(p.peak_rss)
+                );
+            }
+        }
+    }
+}
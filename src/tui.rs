@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use itertools::Itertools;
+use log::*;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::{editor, fix, Config, Finding, ItemKind};
+
+const HELP: &str =
+    "↑/↓ move · e edit · i ignore · f queue fix · c crate · t kind · q apply & quit · esc abort";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Ignore,
+    Fix,
+}
+
+struct App<'a> {
+    workspace: &'a Path,
+    findings: Vec<Finding<'a>>,
+    marks: HashMap<usize, Mark>,
+    crates: Vec<&'a str>,
+    crate_filter: Option<usize>,
+    kind_filter: Option<ItemKind>,
+    /// Indices of the findings matching the filters.
+    visible: Vec<usize>,
+    state: ListState,
+    sources: HashMap<&'a str, Vec<String>>,
+    status: String,
+}
+
+impl<'a> App<'a> {
+    fn new(workspace: &'a Path, findings: Vec<Finding<'a>>) -> Self {
+        let crates = findings
+            .iter()
+            .map(|f| f.package())
+            .unique()
+            .sorted()
+            .collect();
+        let mut app = Self {
+            workspace,
+            findings,
+            marks: Default::default(),
+            crates,
+            crate_filter: None,
+            kind_filter: None,
+            visible: vec![],
+            state: ListState::default(),
+            sources: Default::default(),
+            status: HELP.into(),
+        };
+        app.refilter();
+        app
+    }
+
+    fn refilter(&mut self) {
+        let krate = self.crate_filter.map(|i| self.crates[i]);
+        self.visible = (0..self.findings.len())
+            .filter(|&i| {
+                let f = &self.findings[i];
+                krate.is_none_or(|k| f.package() == k)
+                    && self.kind_filter.is_none_or(|k| f.kind == k)
+            })
+            .collect();
+        self.state.select((!self.visible.is_empty()).then_some(0));
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.state
+            .selected()
+            .and_then(|i| self.visible.get(i).copied())
+    }
+
+    fn toggle(&mut self, mark: Mark) {
+        let Some(i) = self.selected() else {
+            return;
+        };
+        if self.marks.get(&i) == Some(&mark) {
+            self.marks.remove(&i);
+        } else {
+            self.marks.insert(i, mark);
+        }
+        self.state.select_next();
+    }
+
+    fn source(&mut self, path: &'a str) -> &[String] {
+        self.sources.entry(path).or_insert_with(|| {
+            crate::read_source(&self.workspace.join(path))
+                .map(|c| c.lines().map(String::from).collect())
+                .unwrap_or_default()
+        })
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [list, preview] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let items = self.visible.iter().map(|&i| {
+            let f = &self.findings[i];
+            let (marker, style) = match self.marks.get(&i) {
+                Some(Mark::Ignore) => ("I ", Style::default().fg(Color::DarkGray)),
+                Some(Mark::Fix) => ("F ", Style::default().fg(Color::Green)),
+                None => ("  ", Style::default()),
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw(marker),
+                Span::styled(f.display_name, style.add_modifier(Modifier::BOLD)),
+                Span::styled(
+                    format!("  {}:{}", f.path, f.span.start_line + 1),
+                    style.fg(Color::Yellow),
+                ),
+            ]))
+        });
+        let title = format!(
+            " {} findings · crate: {} · kind: {} ",
+            self.visible.len(),
+            self.crate_filter.map_or("all", |i| self.crates[i]),
+            self.kind_filter
+                .map_or_else(|| "all".into(), |k| k.to_string())
+        );
+        let items = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(items, list, &mut self.state);
+
+        if let Some(i) = self.selected() {
+            let (path, line) = (self.findings[i].path, self.findings[i].span.start_line);
+            let height = preview.height.saturating_sub(2) as usize;
+            let first = line.saturating_sub(height / 3);
+            let lines = self
+                .source(path)
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(height)
+                .map(|(n, l)| {
+                    let style = if n == line {
+                        Style::default().add_modifier(Modifier::REVERSED)
+                    } else {
+                        Style::default()
+                    };
+                    Line::from(vec![
+                        Span::styled(format!("{:<5}", n + 1), Style::default().fg(Color::Blue)),
+                        Span::styled(l.clone(), style),
+                    ])
+                })
+                .collect_vec();
+            frame.render_widget(
+                Paragraph::new(lines).block(Block::bordered().title(format!(" {} ", path))),
+                preview,
+            );
+        } else {
+            frame.render_widget(Block::bordered(), preview);
+        }
+        frame.render_widget(Paragraph::new(self.status.as_str()), status);
+    }
+
+    /// Returns whether the marks should be applied, or `None` to keep running.
+    fn handle_key(&mut self, terminal: &mut DefaultTerminal, key: KeyCode) -> Option<bool> {
+        match key {
+            KeyCode::Char('q') => return Some(true),
+            KeyCode::Esc => return Some(false),
+            KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+            KeyCode::Char('i') => self.toggle(Mark::Ignore),
+            KeyCode::Char('f') => self.toggle(Mark::Fix),
+            KeyCode::Char('c') if !self.crates.is_empty() => {
+                self.crate_filter = match self.crate_filter {
+                    None => Some(0),
+                    Some(i) if i + 1 < self.crates.len() => Some(i + 1),
+                    Some(_) => None,
+                };
+                self.refilter();
+            }
+            KeyCode::Char('t') => {
+                self.kind_filter = match self.kind_filter {
+                    None => Some(ItemKind::Function),
+                    Some(ItemKind::Function) => Some(ItemKind::Method),
+                    Some(ItemKind::Method) => None,
+                };
+                self.refilter();
+            }
+            KeyCode::Char('e') => {
+                if let Some(i) = self.selected() {
+                    let f = &self.findings[i];
+                    let path = self.workspace.join(f.path);
+                    ratatui::restore();
+                    let res = editor::open(&path, f.span.start_line + 1, f.span.start_column + 1);
+                    *terminal = ratatui::init();
+                    // The file may have been modified.
+                    self.sources.remove(f.path);
+                    self.status = match res {
+                        Ok(()) => HELP.into(),
+                        Err(e) => e.to_string(),
+                    };
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Triage findings interactively. On exit, ignored findings are added to the configuration and
+/// queued fixes are applied.
+pub fn run(workspace: &Path, findings: Vec<Finding>) -> anyhow::Result<()> {
+    if findings.is_empty() {
+        return Ok(());
+    }
+    let mut app = App::new(workspace, findings);
+    let mut terminal = ratatui::init();
+    let apply = loop {
+        if let Err(e) = terminal.draw(|f| app.draw(f)) {
+            break Err(e);
+        }
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                if let Some(apply) = app.handle_key(&mut terminal, key.code) {
+                    break Ok(apply);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    ratatui::restore();
+    if !apply? {
+        return Ok(());
+    }
+
+    let marked = |mark| {
+        app.marks
+            .iter()
+            .filter(move |(_, m)| **m == mark)
+            .map(|(i, _)| *i)
+            .collect::<HashSet<_>>()
+    };
+    let ignored = marked(Mark::Ignore);
+    if !ignored.is_empty() {
+        let fingerprints = ignored
+            .iter()
+            .map(|&i| app.findings[i].fingerprint())
+            .collect_vec();
+        Config::add_ignored(workspace, &fingerprints)?;
+        info!(
+            "Added {} findings to the ignore list in {:?}",
+            fingerprints.len(),
+            Config::path(workspace)
+        );
+    }
+    let fixed = marked(Mark::Fix);
+    if !fixed.is_empty() {
+        let findings = app
+            .findings
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| fixed.contains(i))
+            .map(|(_, f)| f)
+            .collect_vec();
+        fix::apply(workspace, &findings)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use colored::Colorize;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::index::{self, Index};
+use crate::{load_index, locations, Location, OutputFormat, WhoUsesFlags};
+
+/// References to a symbol matching the query.
+#[derive(Serialize)]
+struct Usages {
+    symbol: String,
+    /// Rust path of the symbol.
+    path: Option<String>,
+    display_name: String,
+    definition: Option<Location>,
+    references: Vec<Location>,
+}
+
+/// Whether a symbol matches a query, i.e. is equal to it or has a path ending with it.
+pub(crate) fn matches(query: &str, symbol: &str, path: Option<&str>) -> bool {
+    let query = query.trim_start_matches("::");
+    symbol == query
+        || path.is_some_and(|p| {
+            p == query
+                || p.strip_suffix(query)
+                    .is_some_and(|prefix| prefix.ends_with("::"))
+        })
+}
+
+pub fn who_uses(args: WhoUsesFlags) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    if let Some(res) = crate::daemon::forward(&args.analysis) {
+        return res;
+    }
+    let index = load_index(&args.analysis)?;
+    write(&index, &args, &mut std::io::stdout().lock())
+}
+
+/// Write the usages of the symbols matching the query, failing if there are none.
+pub(crate) fn write(
+    index: &Index,
+    args: &WhoUsesFlags,
+    out: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    let usages = usages(index, &args.symbol);
+    anyhow::ensure!(
+        !usages.is_empty(),
+        "No symbol matching {:?} in the index",
+        args.symbol
+    );
+    match args.format {
+        OutputFormat::Json => serde_json::to_writer_pretty(&mut *out, &usages)?,
+        OutputFormat::Text => print(&args.analysis.workspace, &usages, out)?,
+    }
+    Ok(())
+}
+
+/// Usages of the symbols matching the query, sorted by path.
+fn usages(index: &Index, query: &str) -> Vec<Usages> {
+    let symbols: HashMap<_, _> = index
+        .symbols
+        .values()
+        .filter_map(|s| {
+            let symbol = index.str(s.symbol);
+            let path = index::rust_path(symbol);
+            matches(query, symbol, path.as_deref()).then_some((s.symbol, (s, path)))
+        })
+        .collect();
+    let mut locations = locations(index, |s| symbols.contains_key(&s));
+    symbols
+        .into_iter()
+        .map(|(sym, (s, path))| {
+            let (definition, references) = locations.remove(&sym).unwrap_or_default();
+            Usages {
+                symbol: index.str(s.symbol).into(),
+                path,
+                display_name: index.str(s.display_name).into(),
+                definition,
+                references,
+            }
+        })
+        .sorted_by(|a, b| (&a.path, &a.symbol).cmp(&(&b.path, &b.symbol)))
+        .collect()
+}
+
+fn print(workspace: &Path, usages: &[Usages], out: &mut dyn std::io::Write) -> anyhow::Result<()> {
+    let mut files = HashMap::<String, Vec<String>>::new();
+    let mut line = |l: &Location| {
+        let lines = files.entry(l.path.clone()).or_insert_with(|| {
+            crate::read_source(&workspace.join(&l.path))
+                .map(|c| c.lines().map(String::from).collect())
+                .unwrap_or_default()
+        });
+        lines
+            .get(l.span.start_line)
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    let position = |l: &Location| {
+        format!(
+            "{}:{}:{}",
+            l.path,
+            l.span.start_line + 1,
+            l.span.start_column + 1
+        )
+    };
+    for u in usages {
+        writeln!(
+            out,
+            "{} {}",
+            u.path.as_deref().unwrap_or(&u.symbol).yellow(),
+            format!("({} references)", u.references.len()).dimmed()
+        )?;
+        if let Some(d) = &u.definition {
+            writeln!(out, "  {} {}", "defined at".dimmed(), position(d))?;
+        }
+        for r in &u.references {
+            writeln!(out, "  {} {}", position(r), line(r).dimmed())?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use std::collections::{HashMap, HashSet};
+
+use colored::Colorize;
+use itertools::Itertools;
+use scip::types::symbol_information::Kind;
+
+use crate::config::Config;
+use crate::index::{self, Index, Sym, Symbol};
+use crate::{locations, metadata, passes, run_passes, search, AnalysisFlags, Location};
+
+/// Decisions of the analysis on the traced symbols.
+#[derive(Default)]
+struct Trace {
+    targets: HashSet<Sym>,
+    /// Traced symbols entering the passes.
+    collected: HashSet<Sym>,
+    /// Pass removing each traced symbol.
+    removed: HashMap<Sym, String>,
+}
+
+impl passes::Hooks for Trace {
+    fn on_candidate(&mut self, _index: &Index, candidate: &Symbol) -> bool {
+        if self.targets.contains(&candidate.symbol) {
+            self.collected.insert(candidate.symbol);
+        }
+        true
+    }
+
+    fn on_filtered(&mut self, _index: &Index, pass: &str, candidate: &Symbol) {
+        if self.targets.contains(&candidate.symbol) {
+            self.removed.insert(candidate.symbol, pass.to_string());
+        }
+    }
+}
+
+/// Print how the analysis decided whether to report the symbols matching a query: whether they
+/// were collected, the pass removing them, and the references or lines counted as usages.
+pub fn why(
+    index: &Index,
+    args: &AnalysisFlags,
+    config: &Config,
+    query: &str,
+    out: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    let targets: HashMap<Sym, (&Symbol, Option<String>)> = index
+        .symbols
+        .values()
+        .filter_map(|s| {
+            let path = index::rust_path(index.str(s.symbol));
+            crate::who_uses::matches(query, index.str(s.symbol), path.as_deref())
+                .then_some((s.symbol, (s, path)))
+        })
+        .collect();
+    anyhow::ensure!(
+        !targets.is_empty(),
+        "No symbol matching {:?} in the index",
+        query
+    );
+    let mut trace = Trace {
+        targets: targets.keys().copied().collect(),
+        ..Default::default()
+    };
+    let metadata = metadata::Workspace::load(&args.workspace);
+    let (findings, _) = run_passes(
+        index,
+        args,
+        config,
+        &metadata,
+        passes::builtin(),
+        &mut trace,
+    )?;
+    let reported: HashSet<&str> = findings.iter().map(|f| f.symbol).collect();
+    let mut locations = locations(index, |s| targets.contains_key(&s));
+    let ctx = passes::Context {
+        index,
+        args,
+        config,
+        metadata: &metadata,
+    };
+    let position = |l: &Location| {
+        format!(
+            "{}:{}:{}",
+            l.path,
+            l.span.start_line + 1,
+            l.span.start_column + 1
+        )
+    };
+
+    for (sym, (s, path)) in targets
+        .into_iter()
+        .sorted_by(|a, b| (&a.1 .1, index.str(a.0)).cmp(&(&b.1 .1, index.str(b.0))))
+    {
+        let symbol = index.str(sym);
+        let (definition, references) = locations.remove(&sym).unwrap_or_default();
+        writeln!(
+            out,
+            "{} {}",
+            path.as_deref().unwrap_or(symbol).yellow(),
+            format!("({})", symbol).dimmed()
+        )?;
+        match &definition {
+            Some(d) => writeln!(out, "  defined at {}", position(d))?,
+            None => writeln!(out, "  no definition in the index")?,
+        }
+        if !trace.collected.contains(&sym) {
+            let reason = if s.kind == Kind::Method || s.kind == Kind::Function {
+                "dropped by a hook"
+            } else {
+                "only functions and methods are"
+            };
+            writeln!(
+                out,
+                "  {} ({})",
+                "not collected as a candidate".bold(),
+                reason
+            )?;
+            writeln!(out)?;
+            continue;
+        }
+        writeln!(out, "  collected as a candidate")?;
+        writeln!(
+            out,
+            "  {} references in the index (at most {} allowed by --max-uses)",
+            references.len(),
+            args.max_uses
+        )?;
+        for r in &references {
+            writeln!(out, "    {}", position(r))?;
+        }
+        let removed = trace.removed.get(&sym);
+        if removed.is_none_or(|p| p == "search") {
+            let lines = passes::Search::lines(&ctx, s, search::Lines::Code)?;
+            writeln!(
+                out,
+                "  {} lines containing `{}` in the searched files (at most {} allowed)",
+                lines.len(),
+                index.str(s.display_name),
+                1 + args.max_uses
+            )?;
+            for (path, line, contents) in &lines {
+                writeln!(out, "    {}:{} {}", path, line + 1, contents.dimmed())?;
+            }
+        }
+        if removed.is_none_or(|p| p == passes::STRINGS_PASS) {
+            let lines = passes::Search::lines(&ctx, s, search::Lines::Strings)?;
+            writeln!(
+                out,
+                "  {} lines naming `{}` in string literals (at most {} allowed)",
+                lines.len(),
+                index.str(s.display_name),
+                args.max_uses
+            )?;
+            for (path, line, contents) in &lines {
+                writeln!(out, "    {}:{} {}", path, line + 1, contents.dimmed())?;
+            }
+        }
+        if removed.is_none_or(|p| p == passes::DOCUMENTATION_PASS) {
+            let lines = passes::Search::lines(&ctx, s, search::Lines::Documentation)?;
+            writeln!(
+                out,
+                "  {} lines mentioning `{}` in the documentation (at most {} allowed besides its doc comment)",
+                lines.len(),
+                index.str(s.display_name),
+                args.max_uses
+            )?;
+            for (path, line, contents) in &lines {
+                writeln!(out, "    {}:{} {}", path, line + 1, contents.dimmed())?;
+            }
+        }
+        let verdict = match removed {
+            Some(pass) if pass == "packages, kinds" => {
+                "not in the requested packages or kinds".green()
+            }
+            Some(pass) if pass == passes::STRINGS_PASS => {
+                "only named in string literals, listed separately".yellow()
+            }
+            Some(pass) if pass == passes::DOCUMENTATION_PASS => {
+                "only used in the documentation, listed separately".yellow()
+            }
+            Some(pass) if crate::bindings::language(pass).is_some() => format!(
+                "only used from {}, listed separately",
+                crate::bindings::language(pass).unwrap().label
+            )
+            .yellow(),
+            Some(pass) if pass == crate::MACRO_GENERATED => {
+                "generated by a macro invocation, not reported with --macro-generated".green()
+            }
+            Some(pass) => format!("removed by the {} pass", pass).green(),
+            None if reported.contains(symbol) => "reported".red(),
+            None if args.min_lines > 0 || args.hide_rustc_dead_code => {
+                "ignored by the configuration, --min-lines or --hide-rustc-dead-code".green()
+            }
+            None => "ignored by the configuration".green(),
+        };
+        writeln!(out, "  {}", verdict.bold())?;
+        writeln!(out)?;
+    }
+    Ok(())
+}